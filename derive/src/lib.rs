@@ -0,0 +1,68 @@
+//! Derive macros for dc_web
+//!
+//! `#[derive(TableRow)]` maps each field to a table cell:
+//!
+//! - `#[column(n)]`: text of the n-th cell(trimmed)
+//! - `#[column(n, converter)]`: converted by `dc_web::table_row::convert::converter`(e.g. `date`, `digits`, `state`)
+//! - `#[onclick]`: script of the row's onclick handler
+
+extern crate proc_macro;
+extern crate syn;
+#[macro_use] extern crate quote;
+
+use proc_macro::TokenStream;
+use syn::{Body, VariantData, Field, MetaItem, NestedMetaItem, Lit, Ident};
+
+#[proc_macro_derive(TableRow, attributes(column, onclick))]
+pub fn derive_table_row(input: TokenStream) -> TokenStream
+{
+	let ast = syn::parse_derive_input(&input.to_string()).expect("Failed to parse the derive input");
+	if !ast.generics.lifetimes.is_empty() || !ast.generics.ty_params.is_empty()
+	{
+		panic!("#[derive(TableRow)] does not support generic structs");
+	}
+	let fields = match ast.body
+	{
+		Body::Struct(VariantData::Struct(ref fields)) => fields,
+		_ => panic!("#[derive(TableRow)] is only for structs with named fields")
+	};
+	let columns = fields.iter().map(|f|
+	{
+		let name = f.ident.as_ref().unwrap();
+		let (converter, args) = converter_of(f);
+		quote!{ #name: #converter(#(#args),*) }
+	});
+	let name = &ast.ident;
+	// expanded by the macro_rules in dc_web, so that the paths resolve both inside and outside the crate
+	let expanded = quote!{ __dc_web_table_row!{ #name { #(#columns),* } } };
+	expanded.parse().unwrap()
+}
+
+/// Converter name and its arguments(the column index)
+fn converter_of(field: &Field) -> (Ident, Vec<usize>)
+{
+	let name = field.ident.as_ref().unwrap();
+	for a in &field.attrs
+	{
+		match a.value
+		{
+			MetaItem::Word(ref w) if w == "onclick" => return (Ident::new("onclick"), Vec::new()),
+			MetaItem::List(ref w, ref items) if w == "column" =>
+			{
+				let index = match items.get(0)
+				{
+					Some(&NestedMetaItem::Literal(Lit::Int(n, _))) => n as usize,
+					_ => panic!("Invalid column attribute for {}: the first argument must be the column index", name)
+				};
+				return match (items.get(1), items.len())
+				{
+					(None, _) => (Ident::new("text"), vec![index]),
+					(Some(&NestedMetaItem::MetaItem(MetaItem::Word(ref c))), 2) => (c.clone(), vec![index]),
+					_ => panic!("Invalid column attribute for {}: expected #[column(n)] or #[column(n, converter)]", name)
+				};
+			},
+			_ => ()
+		}
+	}
+	panic!("Missing #[column(n)] or #[onclick] for {}", name)
+}
@@ -0,0 +1,199 @@
+//! Browser-level connection multiplexing page sessions(flattened `Target.attachToTarget` sessions)
+
+use headless_chrome::{Session, Transport, WebSocketTransport, SessionReceiveEvent, RequestID, Event, ConnectionError, GoneReason};
+use headless_chrome::target::{TargetID, SessionID, TargetInfo, DetachedFromTarget};
+use std::rc::Rc;
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet, VecDeque};
+use serde::Serialize;
+use serde_json;
+use serde_json::Value as JValue;
+use GenericResult;
+
+/// Shared state of the browser connection
+struct Multiplexer
+{
+	transport: Box<Transport>, request_id: RequestID,
+	/// received messages per attached session
+	inboxes: HashMap<SessionID, VecDeque<String>>,
+	/// sessions detached by the browser
+	detached: HashSet<SessionID>
+}
+impl Multiplexer
+{
+	/// Receives a message and routes it to the inbox of its session.
+	/// Returns the message if it belongs to the browser itself
+	fn pump(&mut self) -> GenericResult<Option<String>>
+	{
+		#[derive(Deserialize)] #[serde(rename_all = "camelCase")] struct Routing<'s> { session_id: Option<&'s str> }
+
+		let s = self.transport.recv_text()?;
+		let sid = serde_json::from_str::<Routing>(&s)?.session_id.map(ToOwned::to_owned);
+		if let Some(sid) = sid
+		{
+			// messages for the sessions already dropped are discarded
+			if let Some(q) = self.inboxes.get_mut(&sid) { q.push_back(s); }
+			return Ok(None);
+		}
+		if let SessionReceiveEvent::Method { method, params } = serde_json::from_str(&s)?
+		{
+			if method == DetachedFromTarget::METHOD_NAME
+			{
+				let e: DetachedFromTarget = serde_json::from_value(params)?;
+				self.detached.insert(e.session_id);
+			}
+		}
+		Ok(Some(s))
+	}
+	fn send<P: Serialize>(&mut self, method: &str, params: &P, session_id: Option<&str>) -> GenericResult<RequestID>
+	{
+		#[derive(Serialize)] #[serde(rename_all = "camelCase")]
+		struct Payload<'s, P: Serialize + 's>
+		{
+			method: &'s str, id: RequestID, params: &'s P,
+			#[serde(skip_serializing_if = "Option::is_none")] session_id: Option<&'s str>
+		}
+		let id = self.request_id; self.request_id += 1;
+		self.transport.send_text(serde_json::to_string(&Payload { method, id, params, session_id })?).map(|_| id)
+	}
+	fn call<P: Serialize>(&mut self, method: &str, params: &P) -> GenericResult<JValue>
+	{
+		let id = self.send(method, params, None)?;
+		loop
+		{
+			if let Some(s) = self.pump()?
+			{
+				match serde_json::from_str(&s)?
+				{
+					SessionReceiveEvent::Result { id: rid, result } => if rid == id { return Ok(result); },
+					// errors of the commands sent without waiting(e.g. on dropping sessions) are not ours
+					e@SessionReceiveEvent::Error { .. } => if e.request_id() == Some(id) { return Err(e.error_text().unwrap().into()); },
+					_ => ()
+				}
+			}
+		}
+	}
+}
+
+/// Connection to the browser target. Cloning shares the underlying socket
+#[derive(Clone)]
+pub struct BrowserConnection { mux: Rc<RefCell<Multiplexer>> }
+impl BrowserConnection
+{
+	/// Connects to the browser endpoint(`webSocketDebuggerUrl` in `json/version`)
+	pub fn connect(addr: &str) -> GenericResult<Self>
+	{
+		WebSocketTransport::connect(addr).map(|t| BrowserConnection::new(Box::new(t)))
+	}
+	pub fn new(transport: Box<Transport>) -> Self
+	{
+		BrowserConnection
+		{
+			mux: Rc::new(RefCell::new(Multiplexer
+			{
+				transport, request_id: 1, inboxes: HashMap::new(), detached: HashSet::new()
+			}))
+		}
+	}
+
+	/// Calls a browser-level method
+	pub fn call<P: Serialize>(&self, method: &str, params: &P) -> GenericResult<JValue>
+	{
+		self.mux.borrow_mut().call(method, params)
+	}
+	pub fn get_targets(&self) -> GenericResult<Vec<TargetInfo>>
+	{
+		#[derive(Serialize)] struct Params {}
+		let mut r = self.call("Target.getTargets", &Params {})?;
+		serde_json::from_value(r["targetInfos"].take()).map_err(From::from)
+	}
+	pub fn create_target(&self, url: &str) -> GenericResult<TargetID>
+	{
+		#[derive(Serialize)] struct Params<'s> { url: &'s str }
+		let r = self.call("Target.createTarget", &Params { url })?;
+		Ok(r["targetId"].as_str().unwrap_or_else(|| api_corruption!(value_type)).to_owned())
+	}
+	pub fn close_target(&self, target_id: &str) -> GenericResult<()>
+	{
+		#[derive(Serialize)] #[serde(rename_all = "camelCase")] struct Params<'s> { target_id: &'s str }
+		self.call("Target.closeTarget", &Params { target_id }).map(drop)
+	}
+
+	/// Attaches to an existing target. The target is left open when the session is dropped
+	pub fn attach(&self, target_id: &str) -> GenericResult<Session>
+	{
+		self.attach_transport(target_id, false).map(|t| Session::new(Box::new(t)))
+	}
+	/// Attaches to the first page target(the initial page of a browser launched with a pipe)
+	pub fn attach_first_page(&self) -> GenericResult<Session>
+	{
+		let target = self.get_targets()?.into_iter().find(|t| t.type_ == "page").ok_or("No page target found in the browser")?;
+		self.attach(&target.target_id)
+	}
+	/// Opens a new page target and attaches to it. The target is closed when the session is dropped
+	pub fn open_page(&self, url: &str) -> GenericResult<Session>
+	{
+		let target_id = self.create_target(url)?;
+		self.attach_transport(&target_id, true).map(|t| Session::new(Box::new(t)))
+	}
+	fn attach_transport(&self, target_id: &str, close_target: bool) -> GenericResult<TargetSessionTransport>
+	{
+		#[derive(Serialize)] #[serde(rename_all = "camelCase")] struct Params<'s> { target_id: &'s str, flatten: bool }
+		let r = self.call("Target.attachToTarget", &Params { target_id, flatten: true })?;
+		let session_id = r["sessionId"].as_str().unwrap_or_else(|| api_corruption!(value_type)).to_owned();
+		self.mux.borrow_mut().inboxes.insert(session_id.clone(), VecDeque::new());
+		Ok(TargetSessionTransport { mux: self.mux.clone(), session_id, target_id: target_id.to_owned(), close_target })
+	}
+}
+
+/// Page session carried over a browser connection
+pub struct TargetSessionTransport
+{
+	mux: Rc<RefCell<Multiplexer>>, session_id: SessionID, target_id: TargetID, close_target: bool
+}
+impl TargetSessionTransport
+{
+	pub fn session_id(&self) -> &str { &self.session_id }
+	pub fn target_id(&self) -> &str { &self.target_id }
+}
+impl Transport for TargetSessionTransport
+{
+	fn send_text(&mut self, text: String) -> GenericResult<()>
+	{
+		let mut v: JValue = serde_json::from_str(&text)?;
+		if let JValue::Object(ref mut o) = v { o.insert("sessionId".to_owned(), JValue::String(self.session_id.clone())); }
+		self.mux.borrow_mut().transport.send_text(serde_json::to_string(&v)?)
+	}
+	fn recv_text(&mut self) -> GenericResult<String>
+	{
+		let mut mux = self.mux.borrow_mut();
+		loop
+		{
+			if let Some(s) = mux.inboxes.get_mut(&self.session_id).and_then(VecDeque::pop_front) { return Ok(s); }
+			if mux.detached.contains(&self.session_id)
+			{
+				let reason = format!("Session {} has been detached from the target {}", self.session_id, self.target_id);
+				return Err(ConnectionError::BrowserGone(GoneReason::Detached(reason)).into());
+			}
+			// browser-level messages are not interesting here
+			mux.pump()?;
+		}
+	}
+}
+impl Drop for TargetSessionTransport
+{
+	fn drop(&mut self)
+	{
+		#[derive(Serialize)] #[serde(rename_all = "camelCase")] struct Close<'s> { target_id: &'s str }
+		#[derive(Serialize)] #[serde(rename_all = "camelCase")] struct Detach<'s> { session_id: &'s str }
+
+		if let Ok(mut mux) = self.mux.try_borrow_mut()
+		{
+			mux.inboxes.remove(&self.session_id);
+			let detached = mux.detached.remove(&self.session_id);
+			// results of these commands are discarded by the pump
+			if self.close_target { mux.send("Target.closeTarget", &Close { target_id: &self.target_id }, None).ok(); }
+			else if !detached { mux.send("Target.detachFromTarget", &Detach { session_id: &self.session_id }, None).ok(); }
+		}
+	}
+}
@@ -0,0 +1,599 @@
+//! Offline DOM tree(from `DOMSnapshot.captureSnapshot`) and a query API over it
+
+use headless_chrome::dom_snapshot::CaptureSnapshotResult;
+use GenericResult;
+
+pub const ELEMENT_NODE: u32 = 1;
+pub const TEXT_NODE: u32 = 3;
+pub const DOCUMENT_NODE: u32 = 9;
+
+/// Layout box of a rendered node
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Rect { pub x: f64, pub y: f64, pub width: f64, pub height: f64 }
+
+/// A node in the tree
+#[derive(Debug, Clone)]
+pub struct NodeData
+{
+	pub node_type: u32,
+	/// `nodeName`(upper case for HTML elements, `#text` for texts)
+	pub name: String,
+	/// `nodeValue`(text of text nodes)
+	pub value: String,
+	pub attributes: Vec<(String, String)>,
+	/// Current value of form controls
+	pub input_value: Option<String>, pub input_checked: bool,
+	/// Layout box(`None` if not rendered)
+	pub bounds: Option<Rect>,
+	/// Computed styles requested on capture
+	pub styles: Vec<(String, String)>,
+	parent: Option<usize>, children: Vec<usize>
+}
+impl NodeData
+{
+	pub fn new(node_type: u32, name: &str, value: &str) -> Self
+	{
+		NodeData
+		{
+			node_type, name: name.to_owned(), value: value.to_owned(), attributes: Vec::new(),
+			input_value: None, input_checked: false, bounds: None, styles: Vec::new(), parent: None, children: Vec::new()
+		}
+	}
+	pub fn element(name: &str, attributes: Vec<(String, String)>) -> Self
+	{
+		NodeData { attributes, .. NodeData::new(ELEMENT_NODE, &name.to_uppercase(), "") }
+	}
+	pub fn text(value: &str) -> Self { NodeData::new(TEXT_NODE, "#text", value) }
+}
+
+/// Document tree. The document node is at index 0
+#[derive(Debug, Clone)]
+pub struct DomTree { url: String, frame_id: Option<String>, nodes: Vec<NodeData> }
+impl DomTree
+{
+	/// Empty document
+	pub fn new(url: &str) -> Self
+	{
+		DomTree { url: url.to_owned(), frame_id: None, nodes: vec![NodeData::new(DOCUMENT_NODE, "#document", "")] }
+	}
+	/// Appends a node as the last child of `parent` and returns its index
+	pub fn append(&mut self, parent: usize, mut node: NodeData) -> usize
+	{
+		let index = self.nodes.len();
+		node.parent = Some(parent); node.children.clear();
+		self.nodes.push(node); self.nodes[parent].children.push(index);
+		index
+	}
+	/// Builds the tree of a captured document. `computed_styles` is the list passed on capture
+	pub fn from_snapshot(snapshot: &CaptureSnapshotResult, document: usize, computed_styles: &[&str]) -> GenericResult<Self>
+	{
+		let doc = snapshot.documents.get(document).ok_or_else(|| format!("No document #{} in the snapshot", document))?;
+		let string = |i| snapshot.string(i).unwrap_or("").to_owned();
+		let ref n = doc.nodes;
+
+		let mut nodes: Vec<NodeData> = Vec::with_capacity(n.node_type.len());
+		for i in 0 .. n.node_type.len()
+		{
+			let mut node = NodeData::new(n.node_type[i], "", "");
+			node.name = n.node_name.get(i).map(|&s| string(s)).unwrap_or_default();
+			node.value = n.node_value.get(i).map(|&s| string(s)).unwrap_or_default();
+			if let Some(attrs) = n.attributes.get(i)
+			{
+				node.attributes = attrs.chunks(2).filter(|p| p.len() == 2).map(|p| (string(p[0]), string(p[1]))).collect();
+			}
+			// nodes are in pre-order: parents always come first
+			node.parent = n.parent_index.get(i).and_then(|&p| if p < 0 { None } else { Some(p as usize) });
+			if let Some(p) = node.parent
+			{
+				if p >= i { api_corruption!(invalid_format); }
+				nodes[p].children.push(i);
+			}
+			nodes.push(node);
+		}
+		for rare in &[&n.text_value, &n.input_value]
+		{
+			for (&i, &v) in rare.index.iter().zip(rare.value.iter()) { if let Some(x) = nodes.get_mut(i) { x.input_value = Some(string(v)); } }
+		}
+		for &i in &n.input_checked.index { if let Some(x) = nodes.get_mut(i) { x.input_checked = true; } }
+		let ref l = doc.layout;
+		for (li, &i) in l.node_index.iter().enumerate()
+		{
+			let node = match nodes.get_mut(i) { Some(x) => x, None => continue };
+			if let Some(b) = l.bounds.get(li).filter(|b| b.len() == 4)
+			{
+				node.bounds = Some(Rect { x: b[0], y: b[1], width: b[2], height: b[3] });
+			}
+			if let Some(st) = l.styles.get(li)
+			{
+				node.styles = computed_styles.iter().zip(st.iter()).map(|(&k, &v)| (k.to_owned(), string(v))).collect();
+			}
+		}
+		if nodes.is_empty() { nodes.push(NodeData::new(DOCUMENT_NODE, "#document", "")); }
+
+		Ok(DomTree { url: string(doc.document_url), frame_id: snapshot.string(doc.frame_id).map(ToOwned::to_owned), nodes })
+	}
+
+	pub fn url(&self) -> &str { &self.url }
+	/// Frame of the document(trees from snapshots only)
+	pub fn frame_id(&self) -> Option<&str> { self.frame_id.as_ref().map(|s| s as &str) }
+	pub fn len(&self) -> usize { self.nodes.len() }
+	pub fn document(&self) -> DomNode { DomNode { tree: self, index: 0 } }
+	pub fn node(&self, index: usize) -> Option<DomNode> { if index < self.nodes.len() { Some(DomNode { tree: self, index }) } else { None } }
+	/// Elements matching the selector in the document order
+	pub fn select(&self, selector: &str) -> GenericResult<Vec<DomNode>> { self.document().select(selector) }
+	pub fn select_first(&self, selector: &str) -> GenericResult<Option<DomNode>> { self.document().select_first(selector) }
+}
+
+/// Reference to a node in a `DomTree`
+#[derive(Debug, Clone, Copy)]
+pub struct DomNode<'t> { tree: &'t DomTree, index: usize }
+impl<'t> PartialEq for DomNode<'t>
+{
+	fn eq(&self, other: &Self) -> bool { self.tree as *const _ == other.tree as *const _ && self.index == other.index }
+}
+impl<'t> DomNode<'t>
+{
+	pub fn index(&self) -> usize { self.index }
+	pub fn data(&self) -> &'t NodeData { &self.tree.nodes[self.index] }
+	pub fn is_element(&self) -> bool { self.data().node_type == ELEMENT_NODE }
+	/// Lower-cased element name
+	pub fn tag_name(&self) -> String { self.data().name.to_lowercase() }
+	pub fn attribute(&self, name: &str) -> Option<&'t str>
+	{
+		self.data().attributes.iter().find(|&&(ref k, _)| k.eq_ignore_ascii_case(name)).map(|&(_, ref v)| v as &str)
+	}
+	pub fn id(&self) -> Option<&'t str> { self.attribute("id") }
+	pub fn has_class(&self, class: &str) -> bool
+	{
+		self.attribute("class").map(|c| c.split_whitespace().any(|x| x == class)).unwrap_or(false)
+	}
+	pub fn bounds(&self) -> Option<Rect> { self.data().bounds }
+	pub fn style(&self, name: &str) -> Option<&'t str>
+	{
+		self.data().styles.iter().find(|&&(ref k, _)| k == name).map(|&(_, ref v)| v as &str)
+	}
+	/// Whether the node has a layout box
+	pub fn is_rendered(&self) -> bool { self.data().bounds.is_some() }
+
+	pub fn parent(&self) -> Option<DomNode<'t>> { self.data().parent.map(|index| DomNode { tree: self.tree, index }) }
+	pub fn children(&self) -> Vec<DomNode<'t>>
+	{
+		self.data().children.iter().map(|&index| DomNode { tree: self.tree, index }).collect()
+	}
+	pub fn child_elements(&self) -> Vec<DomNode<'t>> { self.children().into_iter().filter(DomNode::is_element).collect() }
+	/// All descendants in the document order
+	pub fn descendants(&self) -> Vec<DomNode<'t>>
+	{
+		fn walk<'t>(n: DomNode<'t>, sink: &mut Vec<DomNode<'t>>)
+		{
+			for c in n.children() { sink.push(c); walk(c, sink); }
+		}
+		let mut v = Vec::new(); walk(*self, &mut v); v
+	}
+	/// Concatenated text of the descendant text nodes(`textContent`)
+	pub fn text_content(&self) -> String
+	{
+		if self.data().node_type == TEXT_NODE { return self.data().value.clone(); }
+		self.descendants().into_iter().filter(|n| n.data().node_type == TEXT_NODE).map(|n| &n.data().value as &str).collect()
+	}
+	/// `text_content` trimmed
+	pub fn text(&self) -> String { self.text_content().trim().to_owned() }
+
+	/// Descendant elements matching the selector in the document order(`querySelectorAll`)
+	pub fn select(&self, selector: &str) -> GenericResult<Vec<DomNode<'t>>>
+	{
+		let list = selector::parse(selector)?;
+		Ok(self.descendants().into_iter().filter(|n| n.is_element() && list.iter().any(|c| selector::matches(c, *n))).collect())
+	}
+	/// The first element matching the selector(`querySelector`)
+	pub fn select_first(&self, selector: &str) -> GenericResult<Option<DomNode<'t>>>
+	{
+		self.select(selector).map(|v| v.into_iter().next())
+	}
+
+	/// 1-based position among the sibling elements
+	fn element_position(&self) -> Option<(usize, usize)>
+	{
+		self.parent().map(|p|
+		{
+			let siblings = p.child_elements();
+			(siblings.iter().position(|x| x == self).unwrap_or(0) + 1, siblings.len())
+		})
+	}
+}
+
+/// CSS selector subset: type/`*`/`#id`/`.class`/`[attr]`(=, ~=, ^=, $=, *=), `:first-child`/`:last-child`/`:nth-child(an+b)`/`:not(...)`,
+/// descendant and `>` combinators, and selector lists
+mod selector
+{
+	use super::DomNode;
+	use GenericResult;
+
+	#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+	pub enum Combinator { Descendant, Child }
+	#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+	pub enum AttrOp { Exists, Equals, Includes, Prefix, Suffix, Contains }
+	#[derive(Debug, Clone)]
+	/// `NthChild(a, b)` is `:nth-child(an+b)`
+	pub enum Pseudo { FirstChild, LastChild, NthChild(i64, i64), Not(Compound) }
+	#[derive(Debug, Clone, Default)]
+	pub struct Compound
+	{
+		tag: Option<String>, id: Option<String>, classes: Vec<String>,
+		attributes: Vec<(String, AttrOp, String)>, pseudos: Vec<Pseudo>
+	}
+	/// Compounds from left to right. The combinator of the first one is not used
+	pub type Complex = Vec<(Combinator, Compound)>;
+
+	pub fn parse(selector: &str) -> GenericResult<Vec<Complex>>
+	{
+		let chars: Vec<char> = selector.chars().collect();
+		let mut p = Parser { chars, pos: 0 };
+		let mut list = vec![p.complex()?];
+		while p.eat(',') { list.push(p.complex()?); }
+		p.skip_ws();
+		if p.pos < p.chars.len() { return Err(format!("Unexpected {:?} in selector {:?}", p.chars[p.pos], selector).into()); }
+		Ok(list)
+	}
+	struct Parser { chars: Vec<char>, pos: usize }
+	impl Parser
+	{
+		fn peek(&self) -> Option<char> { self.chars.get(self.pos).cloned() }
+		fn skip_ws(&mut self) { while self.peek().map(char::is_whitespace).unwrap_or(false) { self.pos += 1; } }
+		fn eat(&mut self, c: char) -> bool
+		{
+			self.skip_ws();
+			if self.peek() == Some(c) { self.pos += 1; true } else { false }
+		}
+		fn ident(&mut self) -> GenericResult<String>
+		{
+			let start = self.pos;
+			while self.peek().map(|c| c.is_alphanumeric() || c == '-' || c == '_').unwrap_or(false) { self.pos += 1; }
+			if start == self.pos { return Err(format!("Identifier expected at {}", start).into()); }
+			Ok(self.chars[start .. self.pos].iter().collect())
+		}
+		fn value(&mut self) -> GenericResult<String>
+		{
+			self.skip_ws();
+			match self.peek()
+			{
+				Some(q) if q == '"' || q == '\'' =>
+				{
+					self.pos += 1; let start = self.pos;
+					while self.peek().map(|c| c != q).unwrap_or(false) { self.pos += 1; }
+					if self.peek().is_none() { return Err("Unterminated string in selector".into()); }
+					let v = self.chars[start .. self.pos].iter().collect(); self.pos += 1;
+					Ok(v)
+				},
+				_ => self.ident()
+			}
+		}
+		fn complex(&mut self) -> GenericResult<Complex>
+		{
+			self.skip_ws();
+			let mut v = vec![(Combinator::Descendant, self.compound()?)];
+			loop
+			{
+				let had_ws = self.peek().map(char::is_whitespace).unwrap_or(false);
+				self.skip_ws();
+				match self.peek()
+				{
+					Some('>') => { self.pos += 1; self.skip_ws(); v.push((Combinator::Child, self.compound()?)); },
+					Some(c) if had_ws && c != ',' && c != ')' => v.push((Combinator::Descendant, self.compound()?)),
+					_ => return Ok(v)
+				}
+			}
+		}
+		fn compound(&mut self) -> GenericResult<Compound>
+		{
+			let mut c = Compound::default();
+			let start = self.pos;
+			match self.peek()
+			{
+				Some('*') => { self.pos += 1; },
+				Some(x) if x.is_alphanumeric() => c.tag = Some(self.ident()?.to_lowercase()),
+				_ => ()
+			}
+			loop
+			{
+				match self.peek()
+				{
+					Some('#') => { self.pos += 1; c.id = Some(self.ident()?); },
+					Some('.') => { self.pos += 1; c.classes.push(self.ident()?); },
+					Some('[') =>
+					{
+						self.pos += 1; self.skip_ws();
+						let name = self.ident()?; self.skip_ws();
+						let op = match self.peek()
+						{
+							Some(']') => AttrOp::Exists,
+							Some('=') => AttrOp::Equals,
+							Some(x) =>
+							{
+								self.pos += 1;
+								match x
+								{
+									'~' => AttrOp::Includes, '^' => AttrOp::Prefix, '$' => AttrOp::Suffix, '*' => AttrOp::Contains,
+									_ => return Err(format!("Unknown attribute operator {:?}", x).into())
+								}
+							},
+							None => return Err("Unterminated attribute selector".into())
+						};
+						let value = if op == AttrOp::Exists { String::new() }
+						else
+						{
+							if self.peek() != Some('=') { return Err("Attribute operator expected".into()); }
+							self.pos += 1; self.value()?
+						};
+						if !self.eat(']') { return Err("Unterminated attribute selector".into()); }
+						c.attributes.push((name, op, value));
+					},
+					Some(':') =>
+					{
+						self.pos += 1;
+						let name = self.ident()?;
+						let pseudo = match &name as &str
+						{
+							"first-child" => Pseudo::FirstChild,
+							"last-child" => Pseudo::LastChild,
+							"nth-child" =>
+							{
+								if !self.eat('(') { return Err("Argument of :nth-child expected".into()); }
+								let start = self.pos;
+								while self.peek().map(|c| c != ')').unwrap_or(false) { self.pos += 1; }
+								if self.peek().is_none() { return Err("Unterminated :nth-child".into()); }
+								let arg: String = self.chars[start .. self.pos].iter().filter(|c| !c.is_whitespace()).collect();
+								self.pos += 1;
+								let (a, b) = parse_nth(&arg).ok_or_else(|| format!("Invalid argument of :nth-child: {:?}", arg))?;
+								Pseudo::NthChild(a, b)
+							},
+							"not" =>
+							{
+								if !self.eat('(') { return Err("Argument of :not expected".into()); }
+								self.skip_ws();
+								let inner = self.compound()?;
+								if !self.eat(')') { return Err("Unterminated :not".into()); }
+								Pseudo::Not(inner)
+							},
+							_ => return Err(format!("Unsupported pseudo-class :{}", name).into())
+						};
+						c.pseudos.push(pseudo);
+					},
+					_ => break
+				}
+			}
+			if start == self.pos { return Err(format!("Selector expected at {}", start).into()); }
+			Ok(c)
+		}
+	}
+
+	/// `an+b`, `odd`, `even` or an integer
+	fn parse_nth(arg: &str) -> Option<(i64, i64)>
+	{
+		match arg
+		{
+			"odd" => return Some((2, 1)), "even" => return Some((2, 0)), _ => ()
+		}
+		let n = match arg.find('n') { Some(n) => n, None => return arg.parse().ok().map(|b| (0, b)) };
+		let a = match &arg[.. n] { "" | "+" => 1, "-" => -1, x => x.parse().ok()? };
+		let b = match &arg[n + 1 ..] { "" => 0, x if x.starts_with('+') => x[1..].parse().ok()?, x => x.parse().ok()? };
+		Some((a, b))
+	}
+	fn matches_compound(c: &Compound, n: DomNode) -> bool
+	{
+		if !n.is_element() { return false; }
+		if let Some(ref t) = c.tag { if &n.tag_name() != t { return false; } }
+		if let Some(ref id) = c.id { if n.id() != Some(id) { return false; } }
+		if !c.classes.iter().all(|x| n.has_class(x)) { return false; }
+		let attrs_ok = c.attributes.iter().all(|&(ref name, op, ref v)| match n.attribute(name)
+		{
+			None => false,
+			Some(a) => match op
+			{
+				AttrOp::Exists => true, AttrOp::Equals => a == v, AttrOp::Includes => a.split_whitespace().any(|x| x == v),
+				AttrOp::Prefix => a.starts_with(v as &str), AttrOp::Suffix => a.ends_with(v as &str), AttrOp::Contains => a.contains(v as &str)
+			}
+		});
+		attrs_ok && c.pseudos.iter().all(|p| match *p
+		{
+			Pseudo::FirstChild => n.element_position().map(|(i, _)| i == 1).unwrap_or(false),
+			Pseudo::LastChild => n.element_position().map(|(i, len)| i == len).unwrap_or(false),
+			Pseudo::NthChild(a, b) => n.element_position().map(|(i, _)|
+			{
+				let d = i as i64 - b;
+				if a == 0 { d == 0 } else { d % a == 0 && d / a >= 0 }
+			}).unwrap_or(false),
+			Pseudo::Not(ref inner) => !matches_compound(inner, n)
+		})
+	}
+	/// Whether the element matches the complex selector(ancestors are looked up to the root, as `querySelectorAll` does)
+	pub fn matches(complex: &Complex, n: DomNode) -> bool
+	{
+		fn rec(parts: &[(Combinator, Compound)], n: DomNode) -> bool
+		{
+			let (&(comb, ref c), rest) = match parts.split_last() { Some(x) => x, None => return true };
+			if !matches_compound(c, n) { return false; }
+			if rest.is_empty() { return true; }
+			match comb
+			{
+				Combinator::Child => n.parent().map(|p| rec(rest, p)).unwrap_or(false),
+				Combinator::Descendant =>
+				{
+					let mut a = n.parent();
+					while let Some(p) = a { if rec(rest, p) { return true; } a = p.parent(); }
+					false
+				}
+			}
+		}
+		rec(complex, n)
+	}
+}
+
+#[cfg(test)]
+mod tests
+{
+	use super::*;
+	use serde_json;
+
+	fn el(t: &mut DomTree, parent: usize, name: &str, attributes: &[(&str, &str)]) -> usize
+	{
+		t.append(parent, NodeData::element(name, attributes.iter().map(|&(k, v)| (k.to_owned(), v.to_owned())).collect()))
+	}
+	fn text(t: &mut DomTree, parent: usize, value: &str) -> usize { t.append(parent, NodeData::text(value)) }
+	fn texts(v: &[DomNode]) -> Vec<String> { v.iter().map(DomNode::text).collect() }
+
+	/// Home page news boxes: `#mainContents > div.homeNewsBox > (h2, table)`
+	fn home_page() -> DomTree
+	{
+		let mut t = DomTree::new("https://example.com/campusHomepage");
+		let html = el(&mut t, 0, "html", &[]);
+		let body = el(&mut t, html, "body", &[]);
+		let main = el(&mut t, body, "div", &[("id", "mainContents")]);
+		for (b, rows) in [("news", 2), ("lecture", 1)].iter().cloned()
+		{
+			let box_ = el(&mut t, main, "div", &[("class", "homeNewsBox clearfix")]);
+			let h = el(&mut t, box_, "h2", &[]); text(&mut t, h, b);
+			let table = el(&mut t, box_, "table", &[]);
+			let header = el(&mut t, table, "tr", &[]);
+			let th = el(&mut t, header, "th", &[]); text(&mut t, th, "header");
+			for r in 0 .. rows
+			{
+				let tr = el(&mut t, table, "tr", &[("class", "pointer"), ("onclick", "javascript:openNews(1)")]);
+				for c in 0 .. 3
+				{
+					let td = el(&mut t, tr, "td", &[]); text(&mut t, td, &format!(" {}-{}-{} ", b, r, c));
+				}
+			}
+		}
+		t
+	}
+	/// 2x3 table with a header row: `#rates tr:not(:first-child) td`
+	fn rate_table() -> DomTree
+	{
+		let mut t = DomTree::new("https://example.com/rates");
+		let table = el(&mut t, 0, "table", &[("id", "rates")]);
+		for r in 0 .. 3
+		{
+			let tr = el(&mut t, table, "tr", &[("class", if r == 0 { "header" } else { "text-main" })]);
+			for c in 0 .. 2 { let td = el(&mut t, tr, "td", &[]); text(&mut t, td, &format!("{}{}", r, c)); }
+		}
+		t
+	}
+
+	#[test]
+	fn newsbox_rows()
+	{
+		let t = home_page();
+		let rows = t.select("#mainContents .homeNewsBox:nth-child(1) table:nth-child(2) tr.pointer").unwrap();
+		assert_eq!(rows.len(), 2);
+		assert_eq!(rows[0].attribute("onclick"), Some("javascript:openNews(1)"));
+		assert_eq!(texts(&rows[0].select("td").unwrap()), vec!["news-0-0", "news-0-1", "news-0-2"]);
+		let rows = t.select("#mainContents .homeNewsBox:nth-child(2) table:nth-child(2) tr.pointer").unwrap();
+		assert_eq!(rows.len(), 1);
+		assert_eq!(rows[0].select_first("td").unwrap().map(|n| n.text()), Some("lecture-0-0".to_owned()));
+		// the heading is the first child, so no table matches
+		assert!(t.select(".homeNewsBox table:nth-child(1)").unwrap().is_empty());
+	}
+	#[test]
+	fn not_first_child()
+	{
+		let t = rate_table();
+		assert_eq!(texts(&t.select("#rates tr:not(:first-child) td").unwrap()), vec!["10", "11", "20", "21"]);
+		assert_eq!(texts(&t.select("#rates tr.text-main td:not(:first-child)").unwrap()), vec!["11", "21"]);
+		assert_eq!(texts(&t.select("tr:last-child > td:last-child").unwrap()), vec!["21"]);
+	}
+	#[test]
+	fn nth_child_formulas()
+	{
+		let t = rate_table();
+		assert_eq!(texts(&t.select("#rates tr:nth-child(2n) td:nth-child(2n)").unwrap()), vec!["11"]);
+		assert_eq!(texts(&t.select("tr:nth-child(odd) td:nth-child(1)").unwrap()), vec!["00", "20"]);
+		assert_eq!(texts(&t.select("tr:nth-child(-n+2) td:nth-child(even)").unwrap()), vec!["01", "11"]);
+		assert_eq!(texts(&t.select("tr:nth-child(2n + 3) td:first-child").unwrap()), vec!["20"]);
+	}
+	#[test]
+	fn attributes_and_combinators()
+	{
+		let t = home_page();
+		assert_eq!(t.select("[onclick]").unwrap().len(), 3);
+		assert_eq!(t.select("tr[onclick^=\"javascript:\"]").unwrap().len(), 3);
+		assert_eq!(t.select("tr[onclick$='(1)']").unwrap().len(), 3);
+		assert_eq!(t.select("tr[onclick*=News]").unwrap().len(), 3);
+		assert_eq!(t.select("div[class~=clearfix]").unwrap().len(), 2);
+		assert_eq!(t.select("div[class=clearfix]").unwrap().len(), 0);
+		assert_eq!(t.select("#mainContents > .homeNewsBox > h2").unwrap().len(), 2);
+		assert!(t.select("body > .homeNewsBox").unwrap().is_empty());
+		assert_eq!(texts(&t.select("h2, th").unwrap()), vec!["news", "header", "lecture", "header"]);
+		assert_eq!(t.select("*").unwrap().len(), t.select("html, html *").unwrap().len());
+	}
+	#[test]
+	fn select_from_node()
+	{
+		let t = home_page();
+		let second = t.select(".homeNewsBox").unwrap()[1];
+		assert_eq!(texts(&second.select("h2").unwrap()), vec!["lecture"]);
+		// ancestors outside the node still take part in matching, as querySelectorAll does
+		assert_eq!(second.select("#mainContents td").unwrap().len(), 3);
+		assert_eq!(second.parent().and_then(|p| p.id()), Some("mainContents"));
+		assert_eq!(second.child_elements().len(), 2);
+	}
+	#[test]
+	fn invalid_selectors()
+	{
+		let t = home_page();
+		for s in &["", "tr >", "[onclick", "td:hover", "td:nth-child(x)", "td:not(.a", "a[b|=c]", "tr,"]
+		{
+			assert!(t.select(s).is_err(), "{:?} should be rejected", s);
+		}
+	}
+	#[test]
+	fn from_snapshot()
+	{
+		let snapshot: CaptureSnapshotResult = serde_json::from_str(r##"{
+			"strings": ["https://example.com/", "F1", "#document", "HTML", "BODY", "P", "class", "note", "#text", "hello", "INPUT", "value", "block", "none", "IFRAME", "about:blank", "F2"],
+			"documents": [{
+				"documentURL": 0, "title": -1, "frameId": 1,
+				"nodes": {
+					"parentIndex": [-1, 0, 1, 2, 3, 2, 2],
+					"nodeType": [9, 1, 1, 1, 3, 1, 1],
+					"nodeName": [2, 3, 4, 5, 8, 10, 14],
+					"nodeValue": [-1, -1, -1, -1, 9, -1, -1],
+					"attributes": [[], [], [], [6, 7], [], [], []],
+					"inputValue": { "index": [5], "value": [11] },
+					"inputChecked": { "index": [5] }
+				},
+				"layout": {
+					"nodeIndex": [1, 3, 6],
+					"styles": [[12, 12], [12, 12], [13, 12]],
+					"bounds": [[0, 0, 800, 600], [8, 8, 784, 18], [0, 0, 0, 0]],
+					"text": [-1, -1, -1]
+				}
+			}, {
+				"documentURL": 15, "title": -1, "frameId": 16,
+				"nodes": { "parentIndex": [-1], "nodeType": [9], "nodeName": [2], "nodeValue": [-1] },
+				"layout": {}
+			}]
+		}"##).unwrap();
+		assert_eq!(snapshot.document_of_frame("F2"), Some(1));
+		let t = DomTree::from_snapshot(&snapshot, 0, &["display", "visibility"]).unwrap();
+		assert_eq!(t.url(), "https://example.com/");
+		assert_eq!(t.frame_id(), Some("F1"));
+		assert_eq!(t.len(), 7);
+
+		let p = t.select_first("body > p.note").unwrap().unwrap();
+		assert_eq!(p.text(), "hello");
+		assert_eq!(p.bounds(), Some(Rect { x: 8.0, y: 8.0, width: 784.0, height: 18.0 }));
+		assert_eq!(p.style("display"), Some("block"));
+		assert!(p.is_rendered());
+		let input = t.select_first("input").unwrap().unwrap();
+		assert_eq!(input.data().input_value.as_ref().map(|s| s as &str), Some("value"));
+		assert!(input.data().input_checked);
+		assert!(!input.is_rendered());
+		assert_eq!(t.select_first("iframe").unwrap().and_then(|n| n.style("display")), Some("none"));
+		assert_eq!(t.document().text_content(), "hello");
+
+		let empty = DomTree::from_snapshot(&snapshot, 1, &[]).unwrap();
+		assert_eq!(empty.len(), 1);
+		assert!(DomTree::from_snapshot(&snapshot, 2, &[]).is_err());
+	}
+}
@@ -0,0 +1,356 @@
+//! Frame tree and execution context tracking
+
+use headless_chrome::{page, runtime, Event};
+use headless_chrome::runtime::{ExecutionContextID, ScriptID};
+use std::collections::HashMap;
+use serde_json::Value as JValue;
+use serde::Deserialize;
+use GenericResult;
+
+/// Frame specifier
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FrameQuery<'s>
+{
+	/// Frame ID
+	Id(&'s str),
+	/// Frame name(`name` attribute of the frame element). The most recently navigated one wins
+	Name(&'s str),
+	/// Exactly matching URL
+	Url(&'s str),
+	/// URL containing the fragment
+	UrlContains(&'s str),
+	/// Frame names from the top-level frame's children to the target(nested frames)
+	Path(&'s [&'s str])
+}
+
+/// Tracks the frame tree and the execution contexts(default and isolated worlds) of each frame.
+/// Fed by `Page.getFrameTree` and the `Page.frame*`/`Runtime.executionContext*` events
+pub struct FrameTracker
+{
+	/// known frames, ordered by last navigation(newest at the end)
+	frames: Vec<page::FrameOwned>,
+	/// frame id -> default execution context id
+	contexts: HashMap<String, ExecutionContextID>,
+	/// (frame id, world name) -> isolated world context id
+	worlds: HashMap<(String, String), ExecutionContextID>,
+	/// (context id, source) -> persisted script compiled in the context
+	scripts: HashMap<(ExecutionContextID, String), ScriptID>
+}
+/// Maximum number of compiled scripts kept over all contexts
+const SCRIPT_CACHE_CAPACITY: usize = 256;
+impl FrameTracker
+{
+	pub fn new() -> Self { FrameTracker { frames: Vec::new(), contexts: HashMap::new(), worlds: HashMap::new(), scripts: HashMap::new() } }
+
+	/// Replaces known frames with the result of `Page.getFrameTree`. Contexts of surviving frames are kept
+	pub fn reset(&mut self, tree: page::FrameTree)
+	{
+		fn flatten(tree: page::FrameTree, sink: &mut Vec<page::FrameOwned>)
+		{
+			sink.push(tree.frame);
+			for c in tree.child_frames.unwrap_or_default() { flatten(c, sink); }
+		}
+		self.frames.clear(); flatten(tree, &mut self.frames);
+		let frames = &self.frames;
+		self.contexts.retain(|fid, _| frames.iter().any(|f| &f.id == fid));
+		self.worlds.retain(|&(ref fid, _), _| frames.iter().any(|f| &f.id == fid));
+		self.forget_lost_scripts();
+	}
+
+	/// Forgets all execution contexts
+	pub fn clear_contexts(&mut self) { self.contexts.clear(); self.worlds.clear(); self.scripts.clear(); }
+
+	/// Updates the state with a protocol event. Events not related to frames are ignored
+	pub fn process_event(&mut self, name: &str, params: &JValue) -> GenericResult<()>
+	{
+		if name == page::FrameNavigated::METHOD_NAME
+		{
+			self.navigated(page::FrameNavigated::deserialize(params)?.frame.to_owned());
+		}
+		else if name == page::FrameDetached::METHOD_NAME
+		{
+			self.detached(page::FrameDetached::deserialize(params)?.frame_id);
+		}
+		else if name == runtime::ExecutionContextCreated::METHOD_NAME
+		{
+			self.context_created(&runtime::ExecutionContextCreated::deserialize(params)?.context);
+		}
+		else if name == runtime::ExecutionContextDestroyed::METHOD_NAME
+		{
+			self.context_destroyed(runtime::ExecutionContextDestroyed::deserialize(params)?.execution_context_id);
+		}
+		else if name == runtime::ExecutionContextsCleared::METHOD_NAME { self.clear_contexts(); }
+		Ok(())
+	}
+	/// Records a navigated frame(for the callers which already have `Page.frameNavigated` deserialized)
+	pub fn navigated(&mut self, frame: page::FrameOwned)
+	{
+		if let Some(index) = self.frames.iter().position(|f| f.id == frame.id) { self.frames.remove(index); }
+		self.frames.push(frame);
+	}
+	fn detached(&mut self, fid: &str)
+	{
+		let children: Vec<String> = self.children(fid).into_iter().map(|f| f.id.clone()).collect();
+		for c in children { self.detached(&c); }
+		self.frames.retain(|f| f.id != fid);
+		self.contexts.remove(fid);
+		self.worlds.retain(|&(ref f, _), _| f != fid);
+		self.forget_lost_scripts();
+	}
+	fn context_created(&mut self, ctx: &runtime::ExecutionContextDescription)
+	{
+		if let Some(ref aux) = ctx.aux_data
+		{
+			if let Some(fid) = aux.get("frameId").and_then(JValue::as_str)
+			{
+				// isolated worlds are not the default context of the frame
+				if aux.get("isDefault").and_then(JValue::as_bool) == Some(false)
+				{
+					self.worlds.insert((fid.to_owned(), ctx.name.clone()), ctx.id);
+				}
+				else { self.contexts.insert(fid.to_owned(), ctx.id); }
+			}
+		}
+	}
+	fn context_destroyed(&mut self, cid: ExecutionContextID)
+	{
+		self.contexts.retain(|_, &mut c| c != cid);
+		self.worlds.retain(|_, &mut c| c != cid);
+		self.scripts.retain(|&(c, _), _| c != cid);
+	}
+	/// Drops scripts of the contexts no longer known
+	fn forget_lost_scripts(&mut self)
+	{
+		let (contexts, worlds) = (&self.contexts, &self.worlds);
+		self.scripts.retain(|&(c, _), _| contexts.values().any(|&k| k == c) || worlds.values().any(|&k| k == c));
+	}
+}
+/// Compiled script cache(`Runtime.compileScript`)
+impl FrameTracker
+{
+	/// The script compiled from `source` in the context
+	pub fn compiled_script(&self, cid: ExecutionContextID, source: &str) -> Option<&ScriptID>
+	{
+		self.scripts.get(&(cid, source.to_owned()))
+	}
+	pub fn can_cache_script(&self) -> bool { self.scripts.len() < SCRIPT_CACHE_CAPACITY }
+	/// Records a persisted script. Ignored while the cache is full
+	pub fn script_compiled(&mut self, cid: ExecutionContextID, source: &str, script_id: ScriptID)
+	{
+		if self.can_cache_script() { self.scripts.insert((cid, source.to_owned()), script_id); }
+	}
+}
+/// Queries
+impl FrameTracker
+{
+	/// Top-level frame
+	pub fn main_frame(&self) -> Option<&page::FrameOwned>
+	{
+		self.frames.iter().rev().find(|f| f.parent_id.is_none())
+	}
+	/// Direct child frames
+	pub fn children(&self, fid: &str) -> Vec<&page::FrameOwned>
+	{
+		self.frames.iter().filter(|f| f.parent_id.as_ref().map(|p| p as &str) == Some(fid)).collect()
+	}
+	pub fn frames(&self) -> &[page::FrameOwned] { &self.frames }
+	pub fn find(&self, query: FrameQuery) -> Option<&page::FrameOwned>
+	{
+		match query
+		{
+			FrameQuery::Id(id) => self.frames.iter().find(|f| f.id == id),
+			FrameQuery::Name(n) => self.frames.iter().rev().find(|f| f.name.as_ref().map(|s| s as &str) == Some(n)),
+			FrameQuery::Url(u) => self.frames.iter().rev().find(|f| f.url == u),
+			FrameQuery::UrlContains(u) => self.frames.iter().rev().find(|f| f.url.contains(u)),
+			FrameQuery::Path(names) =>
+			{
+				let mut current = self.main_frame();
+				for &n in names
+				{
+					current = current.and_then(|p| self.children(&p.id).into_iter().rev()
+						.find(|f| f.name.as_ref().map(|s| s as &str) == Some(n)));
+				}
+				current
+			}
+		}
+	}
+	/// The default execution context of the frame
+	pub fn context_of(&self, query: FrameQuery) -> Option<ExecutionContextID>
+	{
+		self.find(query).and_then(|f| self.contexts.get(&f.id).cloned())
+	}
+	/// The context of the isolated world named `world_name` in the frame
+	pub fn world_context_of(&self, query: FrameQuery, world_name: &str) -> Option<ExecutionContextID>
+	{
+		self.find(query).and_then(|f| self.worlds.get(&(f.id.clone(), world_name.to_owned())).cloned())
+	}
+	/// The frame owning the default execution context
+	pub fn frame_of_context(&self, cid: ExecutionContextID) -> Option<&page::FrameOwned>
+	{
+		self.contexts.iter().find(|&(_, &c)| c == cid).and_then(|(fid, _)| self.find(FrameQuery::Id(fid)))
+	}
+}
+
+#[cfg(test)]
+mod tests
+{
+	use super::*;
+	use serde_json;
+
+	fn feed(t: &mut FrameTracker, name: &str, params: &str)
+	{
+		t.process_event(name, &serde_json::from_str(params).unwrap()).unwrap();
+	}
+	fn navigated(t: &mut FrameTracker, id: &str, parent: Option<&str>, name: Option<&str>, url: &str)
+	{
+		let frame = json_frame(id, parent, name, url);
+		feed(t, "Page.frameNavigated", &format!(r#"{{ "frame": {} }}"#, frame));
+	}
+	fn json_frame(id: &str, parent: Option<&str>, name: Option<&str>, url: &str) -> String
+	{
+		format!(r#"{{ "id": {:?}, "parentId": {}, "loaderId": "L", "name": {}, "url": {:?}, "securityOrigin": "", "mimeType": "text/html" }}"#,
+			id, parent.map(|p| format!("{:?}", p)).unwrap_or("null".to_owned()),
+			name.map(|n| format!("{:?}", n)).unwrap_or("null".to_owned()), url)
+	}
+	fn context_created(t: &mut FrameTracker, cid: u64, fid: &str, world: Option<&str>)
+	{
+		feed(t, "Runtime.executionContextCreated", &format!(
+			r#"{{ "context": {{ "id": {}, "origin": "", "name": {:?}, "auxData": {{ "frameId": {:?}, "isDefault": {} }} }} }}"#,
+			cid, world.unwrap_or(""), fid, world.is_none()));
+	}
+	/// main(M) > menu(A), main(B) > inner(C)
+	fn campus_plan() -> FrameTracker
+	{
+		let mut t = FrameTracker::new();
+		navigated(&mut t, "M", None, None, "https://example.com/campusHomepage");
+		navigated(&mut t, "A", Some("M"), Some("menu"), "https://example.com/menu");
+		navigated(&mut t, "B", Some("M"), Some("main"), "https://example.com/main");
+		navigated(&mut t, "C", Some("B"), Some("inner"), "https://example.com/inner?x=1");
+		context_created(&mut t, 1, "M", None);
+		context_created(&mut t, 2, "A", None);
+		context_created(&mut t, 3, "B", None);
+		context_created(&mut t, 4, "C", None);
+		t
+	}
+
+	#[test]
+	fn find_frames()
+	{
+		let t = campus_plan();
+		assert_eq!(t.main_frame().map(|f| &f.id as &str), Some("M"));
+		assert_eq!(t.find(FrameQuery::Name("menu")).map(|f| &f.id as &str), Some("A"));
+		assert_eq!(t.find(FrameQuery::Url("https://example.com/main")).map(|f| &f.id as &str), Some("B"));
+		assert_eq!(t.find(FrameQuery::UrlContains("inner?")).map(|f| &f.id as &str), Some("C"));
+		assert_eq!(t.children("M").len(), 2);
+	}
+	#[test]
+	fn find_by_path()
+	{
+		let t = campus_plan();
+		assert_eq!(t.find(FrameQuery::Path(&["main", "inner"])).map(|f| &f.id as &str), Some("C"));
+		assert_eq!(t.find(FrameQuery::Path(&["main"])).map(|f| &f.id as &str), Some("B"));
+		assert_eq!(t.find(FrameQuery::Path(&[])).map(|f| &f.id as &str), Some("M"));
+		// "inner" is not a direct child of the main frame
+		assert!(t.find(FrameQuery::Path(&["inner"])).is_none());
+		assert!(t.find(FrameQuery::Path(&["menu", "inner"])).is_none());
+	}
+	#[test]
+	fn renavigation_replaces_frame()
+	{
+		let mut t = campus_plan();
+		navigated(&mut t, "A", Some("M"), Some("main"), "https://example.com/other");
+		assert_eq!(t.frames().len(), 4);
+		// the most recently navigated frame wins
+		assert_eq!(t.find(FrameQuery::Name("main")).map(|f| &f.id as &str), Some("A"));
+		assert!(t.find(FrameQuery::Name("menu")).is_none());
+		assert_eq!(t.context_of(FrameQuery::Id("A")), Some(2));
+	}
+	#[test]
+	fn contexts_and_worlds()
+	{
+		let mut t = campus_plan();
+		context_created(&mut t, 10, "B", Some("dc_web"));
+		assert_eq!(t.context_of(FrameQuery::Name("main")), Some(3));
+		assert_eq!(t.world_context_of(FrameQuery::Name("main"), "dc_web"), Some(10));
+		assert_eq!(t.world_context_of(FrameQuery::Name("menu"), "dc_web"), None);
+		assert_eq!(t.frame_of_context(4).map(|f| &f.id as &str), Some("C"));
+		// isolated worlds do not own frames
+		assert!(t.frame_of_context(10).is_none());
+		// a new document replaces the default context
+		context_created(&mut t, 5, "B", None);
+		assert_eq!(t.context_of(FrameQuery::Id("B")), Some(5));
+	}
+	#[test]
+	fn context_destroyed()
+	{
+		let mut t = campus_plan();
+		context_created(&mut t, 10, "B", Some("dc_web"));
+		feed(&mut t, "Runtime.executionContextDestroyed", r#"{ "executionContextId": 3 }"#);
+		feed(&mut t, "Runtime.executionContextDestroyed", r#"{ "executionContextId": 10 }"#);
+		assert_eq!(t.context_of(FrameQuery::Id("B")), None);
+		assert_eq!(t.world_context_of(FrameQuery::Id("B"), "dc_web"), None);
+		assert_eq!(t.context_of(FrameQuery::Id("C")), Some(4));
+		// the frame itself is still there
+		assert!(t.find(FrameQuery::Id("B")).is_some());
+	}
+	#[test]
+	fn contexts_cleared()
+	{
+		let mut t = campus_plan();
+		context_created(&mut t, 10, "B", Some("dc_web"));
+		feed(&mut t, "Runtime.executionContextsCleared", "{}");
+		assert!(["M", "A", "B", "C"].iter().all(|&f| t.context_of(FrameQuery::Id(f)).is_none()));
+		assert_eq!(t.world_context_of(FrameQuery::Id("B"), "dc_web"), None);
+		assert_eq!(t.frames().len(), 4);
+	}
+	#[test]
+	fn script_cache()
+	{
+		let mut t = campus_plan();
+		context_created(&mut t, 10, "C", Some("dc_web"));
+		for &(cid, sid) in &[(3, "s3"), (4, "s4"), (10, "s10"), (2, "s2")] { t.script_compiled(cid, "q()", sid.to_owned()); }
+		assert_eq!(t.compiled_script(3, "q()").map(|s| s as &str), Some("s3"));
+		assert!(t.compiled_script(3, "r()").is_none());
+		feed(&mut t, "Runtime.executionContextDestroyed", r#"{ "executionContextId": 3 }"#);
+		assert!(t.compiled_script(3, "q()").is_none());
+		// with the frame, scripts in its isolated world go too
+		feed(&mut t, "Page.frameDetached", r#"{ "frameId": "C" }"#);
+		assert!(t.compiled_script(4, "q()").is_none() && t.compiled_script(10, "q()").is_none());
+		assert_eq!(t.compiled_script(2, "q()").map(|s| s as &str), Some("s2"));
+		feed(&mut t, "Runtime.executionContextsCleared", "{}");
+		assert!(t.compiled_script(2, "q()").is_none());
+	}
+	#[test]
+	fn detached_with_descendants()
+	{
+		let mut t = campus_plan();
+		context_created(&mut t, 10, "C", Some("dc_web"));
+		feed(&mut t, "Page.frameDetached", r#"{ "frameId": "B" }"#);
+		assert!(t.find(FrameQuery::Id("B")).is_none());
+		assert!(t.find(FrameQuery::Id("C")).is_none());
+		assert_eq!(t.context_of(FrameQuery::Name("inner")), None);
+		assert!(t.frame_of_context(4).is_none());
+		assert_eq!(t.frames().len(), 2);
+		assert_eq!(t.context_of(FrameQuery::Name("menu")), Some(2));
+	}
+	#[test]
+	fn reset_keeps_surviving_contexts()
+	{
+		let mut t = campus_plan();
+		let tree: page::FrameTree = serde_json::from_str(&format!(r#"{{ "frame": {}, "childFrames": [{{ "frame": {} }}] }}"#,
+			json_frame("M", None, None, "https://example.com/campusHomepage"),
+			json_frame("A", Some("M"), Some("menu"), "https://example.com/menu"))).unwrap();
+		t.reset(tree);
+		assert_eq!(t.frames().len(), 2);
+		assert_eq!(t.context_of(FrameQuery::Id("M")), Some(1));
+		assert_eq!(t.context_of(FrameQuery::Name("menu")), Some(2));
+		assert!(t.frame_of_context(3).is_none());
+	}
+	#[test]
+	fn unrelated_events_are_ignored()
+	{
+		let mut t = campus_plan();
+		feed(&mut t, "Page.loadEventFired", r#"{ "timestamp": 1.0 }"#);
+		assert_eq!(t.frames().len(), 4);
+	}
+}
@@ -3,20 +3,32 @@
 #![allow(dead_code)]
 
 use serde::Serialize;
-use hyper::client::{Client, Connect, FutureResponse};
-use websocket::WebSocketResult;
+use hyper::client::{Client, Connect, Response};
+use hyper::Error as HyperError;
+use futures::{future, Future};
+use websocket::{WebSocketResult, WebSocketError};
 use websocket::message::OwnedMessage;
 use websocket::sender::Writer as WebSocketWriter;
 use websocket::receiver::Reader as WebSocketReader;
 use websocket::client::ClientBuilder;
-use std::process::{Child, Command};
-use std::io::prelude::{Write, Read};
+use std::process::{Child, Command, ExitStatus};
+use std::io::prelude::{Write, Read, BufRead};
+use std::io::BufReader;
+#[cfg(unix)] use std::fs::File;
 use std::net::TcpStream;
-use std::io::{Result as IOResult, ErrorKind as IOErrorKind};
-use std::mem::transmute_copy;
+use std::io::{Result as IOResult, Error as IOError, ErrorKind as IOErrorKind};
 use serde_json::{Value as JValue}; use serde_json;
 use GenericResult;
 use serde::de::DeserializeOwned;
+use frame_tracker::{FrameTracker, FrameQuery};
+use remote_object::{RemoteObjectHandle, ReleaseQueue, ReleaseState};
+use logging;
+use logging::{Logger, Level, redact_message, redact_request_bodies};
+use std::collections::{HashSet, VecDeque};
+use std::time::Duration;
+use std::thread::sleep;
+use std::rc::Rc;
+use std::cell::RefCell;
 
 // primitives
 pub type RequestID = u64;
@@ -34,7 +46,10 @@ pub struct BrowserVersion<'s>
 	#[serde(rename = "User-Agent")]
 	pub user_agent: &'s str,
 	#[serde(rename = "V8-Version")]
-	pub v8_version: &'s str
+	pub v8_version: &'s str,
+	/// Browser target endpoint
+	#[serde(rename = "webSocketDebuggerUrl")]
+	pub web_socket_debugger_url: Option<&'s str>
 }
 
 /// `json` response(session list)
@@ -66,6 +81,79 @@ pub trait Event: Sized
 {
 	const METHOD_NAME: &'static str;
 }
+/// Typed protocol events received by a session
+#[derive(Debug, Clone)]
+pub enum SessionEvent
+{
+	FrameNavigated(page::FrameNavigatedOwned),
+	FrameStoppedLoading(page::FrameStoppedLoadingOwned),
+	LoadEventFired(page::LoadEventFired),
+	JavascriptDialogOpening(page::JavascriptDialogOpening),
+	JavascriptDialogClosed(page::JavascriptDialogClosed),
+	DocumentUpdated,
+	ExecutionContextCreated(runtime::ExecutionContextCreated),
+	ExecutionContextDestroyed(runtime::ExecutionContextDestroyed),
+	ExecutionContextsCleared,
+	TargetCrashed,
+	Detached(inspector::Detached),
+	SecurityStateChanged(security::SecurityStateChanged),
+	BindingCalled(runtime::BindingCalled),
+	/// Events without a dedicated variant
+	Other { method: String, params: JValue }
+}
+impl SessionEvent
+{
+	pub fn parse(method: &str, params: JValue) -> GenericResult<Self>
+	{
+		Ok(if method == page::FrameNavigatedOwned::METHOD_NAME { SessionEvent::FrameNavigated(serde_json::from_value(params)?) }
+		else if method == page::FrameStoppedLoadingOwned::METHOD_NAME { SessionEvent::FrameStoppedLoading(serde_json::from_value(params)?) }
+		else if method == page::LoadEventFired::METHOD_NAME { SessionEvent::LoadEventFired(serde_json::from_value(params)?) }
+		else if method == page::JavascriptDialogOpening::METHOD_NAME { SessionEvent::JavascriptDialogOpening(serde_json::from_value(params)?) }
+		else if method == page::JavascriptDialogClosed::METHOD_NAME { SessionEvent::JavascriptDialogClosed(serde_json::from_value(params)?) }
+		else if method == dom::DocumentUpdated::METHOD_NAME { SessionEvent::DocumentUpdated }
+		else if method == runtime::ExecutionContextCreated::METHOD_NAME { SessionEvent::ExecutionContextCreated(serde_json::from_value(params)?) }
+		else if method == runtime::ExecutionContextDestroyed::METHOD_NAME { SessionEvent::ExecutionContextDestroyed(serde_json::from_value(params)?) }
+		else if method == runtime::ExecutionContextsCleared::METHOD_NAME { SessionEvent::ExecutionContextsCleared }
+		else if method == inspector::TargetCrashed::METHOD_NAME { SessionEvent::TargetCrashed }
+		else if method == inspector::Detached::METHOD_NAME { SessionEvent::Detached(serde_json::from_value(params)?) }
+		else if method == security::SecurityStateChanged::METHOD_NAME { SessionEvent::SecurityStateChanged(serde_json::from_value(params)?) }
+		else if method == runtime::BindingCalled::METHOD_NAME { SessionEvent::BindingCalled(serde_json::from_value(params)?) }
+		else { SessionEvent::Other { method: method.to_owned(), params } })
+	}
+	/// Decodes an event without a dedicated variant
+	pub fn decode<E: Event + DeserializeOwned>(&self) -> Option<GenericResult<E>>
+	{
+		match *self
+		{
+			SessionEvent::Other { ref method, ref params } if method == E::METHOD_NAME =>
+				Some(serde_json::from_value(params.clone()).map_err(From::from)),
+			_ => None
+		}
+	}
+}
+/// Stream of the events received by a session.
+/// Ends after yielding a connection error that cannot be recovered(closed, lost or gone)
+pub struct Events<'s> { session: &'s mut Session, finished: bool }
+impl<'s> Iterator for Events<'s>
+{
+	type Item = GenericResult<SessionEvent>;
+	fn next(&mut self) -> Option<Self::Item>
+	{
+		if self.finished { return None; }
+		let r = self.session.next_event();
+		if let Err(ref e) = r
+		{
+			// the stream continues over a re-established connection
+			self.finished = match ConnectionError::from_error(&**e) { Some(&ConnectionError::Reconnected) | None => false, Some(_) => true };
+		}
+		Some(r)
+	}
+}
+impl<'s> Events<'s>
+{
+	/// The session delivering events(e.g. to look up frames while iterating)
+	pub fn session(&mut self) -> &mut Session { self.session }
+}
 
 #[derive(Deserialize)] #[serde(untagged)]
 pub enum SessionReceiveEvent<'s>
@@ -78,6 +166,15 @@ pub enum SessionReceiveEvent<'s>
 pub struct ErrorDescription<'s> { code: i64, message: &'s str }
 impl<'s> SessionReceiveEvent<'s>
 {
+	/// The request answered by this message(`None` for events)
+	pub fn request_id(&self) -> Option<RequestID>
+	{
+		match *self
+		{
+			SessionReceiveEvent::Result { id, .. } | SessionReceiveEvent::Error { id, .. } => Some(id),
+			SessionReceiveEvent::Method { .. } => None
+		}
+	}
 	pub fn error_text(&self) -> Option<String>
 	{
 		if let &SessionReceiveEvent::Error { id, ref error } = self
@@ -88,33 +185,226 @@ impl<'s> SessionReceiveEvent<'s>
 	}
 }
 
-pub struct Session<W: Write, R: Read>
+/// How to respond to JavaScript dialogs(`alert`/`confirm`/`prompt`/`beforeunload`)
+pub enum DialogPolicy
+{
+	/// Accept all dialogs(prompts are answered with their default text)
+	Accept,
+	/// Dismiss all dialogs
+	Dismiss,
+	/// Decide per dialog
+	Callback(Box<FnMut(&page::JavascriptDialogOpening) -> DialogAction>)
+}
+/// Response to a JavaScript dialog
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DialogAction { Accept(Option<String>), Dismiss }
+/// Request id used for the dialog handling commands issued by the session itself
+const DIALOG_HANDLER_REQUEST_ID: RequestID = ::std::u64::MAX;
+/// Request id used for releasing dropped remote objects(results are discarded)
+const OBJECT_RELEASER_REQUEST_ID: RequestID = ::std::u64::MAX - 1;
+
+/// Errors about the connection to the browser
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConnectionError
+{
+	/// The peer closed the connection
+	Closed,
+	/// The connection was broken(I/O error)
+	Lost(String),
+	/// The connection was lost and then re-established.
+	/// Requests in flight are lost, so the page state should be re-synchronized
+	Reconnected,
+	/// The browser or the target is no longer available
+	BrowserGone(GoneReason)
+}
+/// Reason why the browser or the target has gone
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GoneReason
+{
+	/// The renderer crashed(`Inspector.targetCrashed`)
+	Crashed,
+	/// The target has been detached(`Inspector.detached`), with the reason
+	Detached(String),
+	/// The browser process has exited, with the exit code if available
+	Exited(Option<i32>)
+}
+/// What to do when the browser or the target has gone
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CrashPolicy
 {
-	sender: WebSocketWriter<W>, receiver: WebSocketReader<R>,
-	frame_navigated_event_subscriber: Vec<*mut FrameNavigatedEventSubscriber>
+	/// Fail every pending and following wait with `ConnectionError::BrowserGone`
+	Fail,
+	/// Relaunch the browser(or reload the crashed page), redo the session setup and navigate back to the last location
+	RelaunchAndResume
 }
-impl Session<TcpStream, TcpStream>
+impl ::std::fmt::Display for ConnectionError
+{
+	fn fmt(&self, fmt: &mut ::std::fmt::Formatter) -> ::std::fmt::Result
+	{
+		match *self
+		{
+			ConnectionError::Closed => write!(fmt, "The DevTools connection has been closed"),
+			ConnectionError::Lost(ref e) => write!(fmt, "The DevTools connection has been lost: {}", e),
+			ConnectionError::Reconnected => write!(fmt, "The DevTools connection has been re-established"),
+			ConnectionError::BrowserGone(GoneReason::Crashed) => write!(fmt, "The target has crashed"),
+			ConnectionError::BrowserGone(GoneReason::Detached(ref r)) => write!(fmt, "The target has been detached: {}", r),
+			ConnectionError::BrowserGone(GoneReason::Exited(Some(c))) => write!(fmt, "The browser has exited with code {}", c),
+			ConnectionError::BrowserGone(GoneReason::Exited(None)) => write!(fmt, "The browser has exited")
+		}
+	}
+}
+impl ::std::error::Error for ConnectionError
+{
+	fn description(&self) -> &str { "DevTools connection error" }
+}
+impl ConnectionError
+{
+	/// Extracts the connection error from a generic error
+	pub fn from_error<'e>(e: &'e (::std::error::Error + 'static)) -> Option<&'e ConnectionError> { e.downcast_ref() }
+}
+/// Reconnection attempts of a session
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ReconnectPolicy { pub max_attempts: u32, pub interval: Duration }
+impl Default for ReconnectPolicy
+{
+	fn default() -> Self { ReconnectPolicy { max_attempts: 5, interval: Duration::from_millis(500) } }
+}
+/// Called after reconnection to restore the session configuration(domains, overrides, ...)
+pub type ReconnectHook = Box<FnMut(&mut Session) -> GenericResult<()>>;
+
+/// Message channel carrying protocol JSON texts
+pub trait Transport
+{
+	fn send_text(&mut self, text: String) -> GenericResult<()>;
+	fn recv_text(&mut self) -> GenericResult<String>;
+	/// Whether `reconnect` is supported. Sessions over the other transports start without a reconnection policy
+	fn can_reconnect(&self) -> bool { false }
+	/// Re-establishes the connection to the same target
+	fn reconnect(&mut self) -> GenericResult<()> { Err("Reconnection is not supported by the transport".into()) }
+}
+/// DevTools WebSocket connection
+pub struct WebSocketTransport { addr: String, sender: WebSocketWriter<TcpStream>, receiver: WebSocketReader<TcpStream> }
+impl WebSocketTransport
 {
 	pub fn connect(addr: &str) -> GenericResult<Self>
 	{
 		let ws_client = ClientBuilder::new(addr)?.connect_insecure()?;
-		let (recv, send) = ws_client.split()?;
-		Ok(Session
+		let (receiver, sender) = ws_client.split()?;
+		Ok(WebSocketTransport { addr: addr.to_owned(), sender, receiver })
+	}
+	pub fn wait_message(&mut self) -> WebSocketResult<OwnedMessage>
+	{
+		self.receiver.recv_message::<DummyIterator>()
+	}
+}
+fn connection_error(e: WebSocketError) -> Box<::std::error::Error>
+{
+	match e
+	{
+		WebSocketError::NoDataAvailable => ConnectionError::Closed.into(),
+		WebSocketError::IoError(e) => ConnectionError::Lost(e.to_string()).into(),
+		e => e.into()
+	}
+}
+impl Transport for WebSocketTransport
+{
+	fn send_text(&mut self, text: String) -> GenericResult<()>
+	{
+		self.sender.send_message(&OwnedMessage::Text(text)).map_err(connection_error)
+	}
+	fn recv_text(&mut self) -> GenericResult<String>
+	{
+		loop
 		{
-			sender: send, receiver: recv, frame_navigated_event_subscriber: Vec::new()
-		})
+			match self.wait_message().map_err(connection_error)?
+			{
+				OwnedMessage::Text(s) => return Ok(s),
+				OwnedMessage::Close(_) => return Err(ConnectionError::Closed.into()),
+				OwnedMessage::Ping(p) => self.sender.send_message(&OwnedMessage::Pong(p)).map_err(connection_error)?,
+				_ => ()
+			}
+		}
+	}
+	fn can_reconnect(&self) -> bool { true }
+	fn reconnect(&mut self) -> GenericResult<()>
+	{
+		let addr = self.addr.clone();
+		*self = WebSocketTransport::connect(&addr)?; Ok(())
+	}
+}
+/// DevTools connection over `--remote-debugging-pipe`: JSON messages terminated by NUL.
+/// Carries the browser target, so page sessions are attached through `browser::BrowserConnection`
+pub struct PipeTransport<W: Write, R: Read> { writer: W, reader: BufReader<R> }
+impl<W: Write, R: Read> PipeTransport<W, R>
+{
+	/// `writer` is the browser's input(fd 3), `reader` is its output(fd 4)
+	pub fn new(writer: W, reader: R) -> Self { PipeTransport { writer, reader: BufReader::new(reader) } }
+}
+impl<W: Write, R: Read> Transport for PipeTransport<W, R>
+{
+	fn send_text(&mut self, text: String) -> GenericResult<()>
+	{
+		let mut bytes = text.into_bytes(); bytes.push(0);
+		self.writer.write_all(&bytes).and_then(|_| self.writer.flush()).map_err(|e| ConnectionError::Lost(e.to_string()).into())
+	}
+	fn recv_text(&mut self) -> GenericResult<String>
+	{
+		let mut bytes = Vec::new();
+		self.reader.read_until(0, &mut bytes).map_err(|e| ConnectionError::Lost(e.to_string()))?;
+		// EOF before the terminator: the browser has closed its end
+		if bytes.pop() != Some(0) { return Err(ConnectionError::Closed.into()); }
+		String::from_utf8(bytes).map_err(From::from)
+	}
+}
+
+pub struct Session
+{
+	transport: Box<Transport>,
+	frame_navigated_event_subscriber: Vec<*mut FrameNavigatedEventSubscriber>,
+	frames: FrameTracker, dialog_policy: DialogPolicy, dialogs: Vec<page::JavascriptDialogOpening>,
+	logger: Box<Logger>, sensitive: bool, sensitive_requests: HashSet<RequestID>,
+	reconnect_policy: Option<ReconnectPolicy>, reconnect_hook: Option<ReconnectHook>, reconnecting: bool, reconnections: u32,
+	process: Option<Rc<RefCell<Process>>>, crash_policy: CrashPolicy, gone: Option<GoneReason>,
+	released_objects: ReleaseQueue,
+	security_state: Option<security::SecurityStateChanged>, security_issues: Vec<security::SecurityStateChanged>,
+	bindings: HashSet<String>, binding_calls: VecDeque<runtime::BindingCalled>
+}
+impl Session
+{
+	/// Connects to a page target with its `webSocketDebuggerUrl`
+	pub fn connect(addr: &str) -> GenericResult<Self>
+	{
+		WebSocketTransport::connect(addr).map(|t| Session::new(Box::new(t)))
+	}
+	pub fn new(transport: Box<Transport>) -> Self
+	{
+		// a pipe or a browser-level session cannot be re-established, its failure is final
+		let reconnect_policy = if transport.can_reconnect() { Some(ReconnectPolicy::default()) } else { None };
+		Session
+		{
+			transport, frame_navigated_event_subscriber: Vec::new(), frames: FrameTracker::new(),
+			dialog_policy: DialogPolicy::Dismiss, dialogs: Vec::new(),
+			logger: logging::default_logger(), sensitive: false, sensitive_requests: HashSet::new(),
+			reconnect_policy, reconnect_hook: None, reconnecting: false, reconnections: 0,
+			process: None, crash_policy: CrashPolicy::Fail, gone: None,
+			released_objects: Rc::new(RefCell::new(ReleaseState::default())),
+			security_state: None, security_issues: Vec::new(), bindings: HashSet::new(), binding_calls: VecDeque::new()
+		}
 	}
 }
 /// Session associated domains
-impl<W: Write, R: Read> Session<W, R>
+impl Session
 {
-	pub fn dom(&mut self) -> domain::DOM<W, R> { domain::DOM(self) }
-	pub fn input(&mut self) -> domain::Input<W, R> { domain::Input(self) }
-	pub fn network(&mut self) -> domain::Network<W, R> { domain::Network(self) }
-	pub fn page(&mut self) -> domain::Page<W, R> { domain::Page(self) }
-	pub fn runtime(&mut self) -> domain::Runtime<W, R> { domain::Runtime(self) }
+	pub fn dom(&mut self) -> domain::DOM { domain::DOM(self) }
+	pub fn dom_snapshot(&mut self) -> domain::DOMSnapshot { domain::DOMSnapshot(self) }
+	pub fn inspector(&mut self) -> domain::Inspector { domain::Inspector(self) }
+	pub fn input(&mut self) -> domain::Input { domain::Input(self) }
+	pub fn network(&mut self) -> domain::Network { domain::Network(self) }
+	pub fn page(&mut self) -> domain::Page { domain::Page(self) }
+	pub fn runtime(&mut self) -> domain::Runtime { domain::Runtime(self) }
+	pub fn security(&mut self) -> domain::Security { domain::Security(self) }
 }
-impl<W: Write, R: Read> Session<W, R>
+impl Session
 {
 	pub fn subscribe_session_event(&mut self, subscriber: &'static FrameNavigatedEventSubscriber)
 	{
@@ -127,88 +417,401 @@ impl<W: Write, R: Read> Session<W, R>
 			self.frame_navigated_event_subscriber.remove(index);
 		}
 	}
+
+	/// Frames and execution contexts observed in this session
+	pub fn frames(&self) -> &FrameTracker { &self.frames }
+	/// Rebuilds the frame tree with `Page.getFrameTree`
+	pub fn sync_frame_tree(&mut self, id: RequestID) -> GenericResult<()>
+	{
+		let tree = self.page().get_frame_tree_sync(id)?;
+		self.frames.reset(tree); Ok(())
+	}
+
+	/// The context of the isolated world named `world_name` in the frame, created if not exist(requires `Runtime.enable`)
+	pub fn isolated_world(&mut self, id: RequestID, frame_id: &str, world_name: &str) -> GenericResult<runtime::ExecutionContextID>
+	{
+		if let Some(cid) = self.frames.world_context_of(FrameQuery::Id(frame_id), world_name) { return Ok(cid); }
+		if let Some(cid) = self.page().create_isolated_world_sync(id, frame_id, Some(world_name))? { return Ok(cid); }
+		// stable Chrome returns nothing: wait for the context creation event
+		loop
+		{
+			if let Some(cid) = self.frames.world_context_of(FrameQuery::Id(frame_id), world_name) { return Ok(cid); }
+			if self.frames.find(FrameQuery::Id(frame_id)).is_none() { return Err(format!("Frame {} has been detached", frame_id).into()); }
+			self.next_event()?;
+		}
+	}
+	/// Evaluates the expression by value(awaiting a promise result), compiling it once per context.
+	/// Falls back to `Runtime.evaluate` when the script cache of the context is full
+	pub fn run_cached_script(&mut self, id: RequestID, context_id: u64, expression: &str) -> GenericResult<runtime::EvaluateResult>
+	{
+		let script_id = match self.frames.compiled_script(context_id, expression).cloned()
+		{
+			Some(sid) => sid,
+			None =>
+			{
+				if !self.frames.can_cache_script() { return self.runtime().evaluate_awaited_sync(id, Some(context_id), expression); }
+				let r = self.runtime().compile_script_sync(id, context_id, expression, true)?;
+				if let Some(e) = r.exception_details { return Err(format!("Error in compiling script: {:?}", e).into()); }
+				let sid = r.script_id.ok_or("Runtime.compileScript returned no script id")?;
+				self.frames.script_compiled(context_id, expression, sid.clone());
+				sid
+			}
+		};
+		self.runtime().run_script_sync(id, context_id, &script_id, true, true)
+	}
+
+	/// Sets how JavaScript dialogs are handled. Defaults to `DialogPolicy::Dismiss`
+	pub fn set_dialog_policy(&mut self, policy: DialogPolicy) { self.dialog_policy = policy; }
+	/// Dialogs opened in this session so far
+	pub fn dialogs(&self) -> &[page::JavascriptDialogOpening] { &self.dialogs }
+	/// Takes the recorded dialogs out
+	pub fn take_dialogs(&mut self) -> Vec<page::JavascriptDialogOpening> { ::std::mem::replace(&mut self.dialogs, Vec::new()) }
+
+	/// The last reported security state of the page(requires `Security.enable`)
+	pub fn security_state(&self) -> Option<&security::SecurityStateChanged> { self.security_state.as_ref() }
+	/// Insecure or mixed-content states reported so far
+	pub fn security_issues(&self) -> &[security::SecurityStateChanged] { &self.security_issues }
+	/// Takes the recorded security issues out
+	pub fn take_security_issues(&mut self) -> Vec<security::SecurityStateChanged> { ::std::mem::replace(&mut self.security_issues, Vec::new()) }
+
+	/// Takes the oldest unhandled call of the binding
+	pub fn take_binding_call(&mut self, name: &str) -> Option<runtime::BindingCalled>
+	{
+		let index = self.binding_calls.iter().position(|c| c.name == name);
+		index.and_then(|i| self.binding_calls.remove(i))
+	}
+	/// Waits for a call of the binding. Calls received while waiting for something else are kept until taken
+	pub fn wait_binding_call(&mut self, name: &str) -> GenericResult<runtime::BindingCalled>
+	{
+		loop
+		{
+			if let Some(c) = self.take_binding_call(name) { return Ok(c); }
+			if !self.bindings.contains(name) { return Err(format!("Binding {} is not registered", name).into()); }
+			self.next_event()?;
+		}
+	}
+
+	/// Replaces the protocol traffic logger
+	pub fn set_logger(&mut self, logger: Box<Logger>) { self.logger = logger; }
+	/// While set, sent messages and their results are logged with parameters masked. Returns the previous state
+	pub fn set_sensitive(&mut self, sensitive: bool) -> bool { ::std::mem::replace(&mut self.sensitive, sensitive) }
+
+	/// Sets the reconnection attempts on a dropped connection(`None` disables reconnection)
+	pub fn set_reconnect_policy(&mut self, policy: Option<ReconnectPolicy>) { self.reconnect_policy = policy; }
+	/// Sets the procedure restoring the session configuration after reconnection
+	pub fn set_reconnect_hook(&mut self, hook: ReconnectHook) { self.reconnect_hook = Some(hook); }
+	/// Number of reconnections happened in this session
+	pub fn reconnections(&self) -> u32 { self.reconnections }
+
+	/// Watches the browser process: its exit is reported as `GoneReason::Exited`, and it is relaunched by `CrashPolicy::RelaunchAndResume`
+	pub fn watch_process(&mut self, process: Rc<RefCell<Process>>) { self.process = Some(process); }
+	/// Sets the behavior on crashes and lost targets. Defaults to `CrashPolicy::Fail`
+	pub fn set_crash_policy(&mut self, policy: CrashPolicy) { self.crash_policy = policy; }
+	/// Why the browser or the target has gone, if it has
+	pub fn gone(&self) -> Option<&GoneReason> { self.gone.as_ref() }
+
+	/// Wraps a remote object returned in this session. The object is released when the handle is dropped
+	pub fn wrap_object(&self, object: runtime::RemoteObject, group: Option<&str>) -> RemoteObjectHandle
+	{
+		RemoteObjectHandle::new(object, group.map(ToOwned::to_owned), self.released_objects.clone())
+	}
+	/// Sends `Runtime.releaseObject` for the handles dropped since the last request
+	fn flush_released_objects(&mut self) -> GenericResult<()>
+	{
+		let ids = self.released_objects.borrow_mut().take_pending();
+		for oid in ids { self.runtime().release_object(OBJECT_RELEASER_REQUEST_ID, &oid)?; }
+		Ok(())
+	}
 }
-#[cfg(feature = "verbose")] use colored::*;
-impl<W: Write, R: Read> Session<W, R>
+/// Reconnection
+impl Session
 {
-	pub fn wait_message(&mut self) -> WebSocketResult<OwnedMessage>
+	/// Tries to recover a connection error. Returns `ConnectionError::Reconnected` on success, the original error otherwise
+	fn recover(&mut self, e: Box<::std::error::Error>) -> Box<::std::error::Error>
 	{
-		self.receiver.recv_message::<DummyIterator>()
+		match ConnectionError::from_error(&*e).cloned()
+		{
+			Some(ConnectionError::BrowserGone(r)) => return self.on_gone(r),
+			Some(ConnectionError::Closed) | Some(ConnectionError::Lost(_)) => (),
+			_ => return e
+		}
+		// a dead browser does not accept connections anymore
+		if let Some(r) = self.probe_process() { return self.on_gone(r); }
+		let policy = match self.reconnect_policy
+		{
+			Some(p) if !self.reconnecting => p,
+			_ => return e
+		};
+		self.logger.log(Level::Warn, "session", &format!("{}. Reconnecting...", e));
+		self.reconnecting = true;
+		let r = self.reconnect(policy);
+		self.reconnecting = false;
+		match r
+		{
+			Ok(()) => { self.reconnections += 1; ConnectionError::Reconnected.into() },
+			Err(re) =>
+			{
+				self.logger.log(Level::Error, "session", &format!("Reconnection failed: {}", re));
+				match self.probe_process() { Some(r) => self.on_gone(r), None => e }
+			}
+		}
 	}
-	pub fn wait_text(&mut self) -> WebSocketResult<String>
+	/// Exit status of the watched browser process
+	fn probe_process(&mut self) -> Option<GoneReason>
 	{
+		self.process.as_ref().and_then(|p| p.borrow_mut().exit_status().ok().and_then(|s| s)).map(|s| GoneReason::Exited(s.code()))
+	}
+	/// Marks the session as gone, then relaunches by the policy.
+	/// Returns `ConnectionError::Reconnected` if resumed, `ConnectionError::BrowserGone` otherwise
+	fn on_gone(&mut self, reason: GoneReason) -> Box<::std::error::Error>
+	{
+		self.logger.log(Level::Error, "session", &ConnectionError::BrowserGone(reason.clone()).to_string());
+		self.gone = Some(reason.clone());
+		if self.crash_policy == CrashPolicy::RelaunchAndResume && !self.reconnecting
+		{
+			let last_url = self.frames.main_frame().map(|f| f.url.clone());
+			self.gone = None; self.reconnecting = true;
+			let r = self.relaunch(&reason, last_url);
+			self.reconnecting = false;
+			match r
+			{
+				Ok(()) => { self.reconnections += 1; return ConnectionError::Reconnected.into(); },
+				Err(e) => { self.logger.log(Level::Error, "session", &format!("Relaunch failed: {}", e)); self.gone = Some(reason.clone()); }
+			}
+		}
+		ConnectionError::BrowserGone(reason).into()
+	}
+	fn relaunch(&mut self, reason: &GoneReason, last_url: Option<String>) -> GenericResult<()>
+	{
+		// a crashed renderer is restarted by the next navigation in the same target
+		if *reason != GoneReason::Crashed
+		{
+			let process = self.process.clone().ok_or("No browser process to relaunch")?;
+			let addr = { let mut p = process.borrow_mut(); p.relaunch()?; p.page_target_url()? };
+			self.transport = Box::new(WebSocketTransport::connect(&addr)?);
+		}
+		self.frames = FrameTracker::new();
+		self.sensitive_requests.clear();
+		self.released_objects.borrow_mut().invalidate_all();
+		if let Some(mut hook) = self.reconnect_hook.take()
+		{
+			let r = hook(self);
+			self.reconnect_hook = Some(hook); r?;
+		}
+		if let Some(url) = last_url { self.page().navigate_sync(0, &url)?; }
+		Ok(())
+	}
+	fn reconnect(&mut self, policy: ReconnectPolicy) -> GenericResult<()>
+	{
+		let mut attempts = 0;
 		loop
 		{
-			match self.wait_message()?
+			attempts += 1;
+			match self.transport.reconnect()
 			{
-				OwnedMessage::Text(s) => return Ok(s),
-				_ => ()
+				Ok(()) => break,
+				Err(e) => if attempts >= policy.max_attempts { return Err(e); }
+			}
+			sleep(policy.interval);
+		}
+		// the contexts may have been replaced(e.g. restarted renderer)
+		self.frames.clear_contexts();
+		self.sensitive_requests.clear();
+		self.released_objects.borrow_mut().invalidate_all();
+		if let Some(mut hook) = self.reconnect_hook.take()
+		{
+			let r = hook(self);
+			self.reconnect_hook = Some(hook); r?;
+		}
+		Ok(())
+	}
+}
+impl Session
+{
+	pub fn wait_text(&mut self) -> GenericResult<String>
+	{
+		if let Some(ref r) = self.gone { return Err(ConnectionError::BrowserGone(r.clone()).into()); }
+		let s = match self.transport.recv_text()
+		{
+			Ok(s) => s, Err(e) => return Err(self.recover(e))
+		};
+		if self.logger.enabled(Level::Debug)
+		{
+			let id = serde_json::from_str::<JValue>(&s).ok().and_then(|v| v["id"].as_u64());
+			if id.map(|id| self.sensitive_requests.remove(&id)).unwrap_or(false)
+			{
+				self.logger.log(Level::Debug, "recv", &redact_message(&s));
 			}
+			else if let Some(r) = redact_request_bodies(&s) { self.logger.log(Level::Debug, "recv", &r); }
+			else { self.logger.log(Level::Debug, "recv", &s); }
 		}
+		Ok(s)
 	}
 	pub fn dispatch_frame_navigated(&self, e: &page::FrameNavigated)
 	{
 		for &call in &self.frame_navigated_event_subscriber { unsafe { &mut *call }.on_event(e); }
 	}
-	pub fn wait_event<E: Event + DeserializeOwned>(&mut self) -> GenericResult<E>
+	fn frame_navigated(&mut self, e: &page::FrameNavigated)
+	{
+		self.frames.navigated(e.frame.to_owned());
+		self.dispatch_frame_navigated(e);
+	}
+	/// Common processing for every received event(frame tracking, dialog handling and subscriber dispatching)
+	pub fn process_event(&mut self, name: &str, params: &JValue) -> GenericResult<()>
+	{
+		use serde::Deserialize;
+
+		if name == page::FrameNavigated::METHOD_NAME
+		{
+			// deserialized once for both the tracker and the subscribers
+			self.frame_navigated(&page::FrameNavigated::deserialize(params)?);
+			return Ok(());
+		}
+		self.frames.process_event(name, params)?;
+		if name == page::JavascriptDialogOpening::METHOD_NAME
+		{
+			// an unhandled dialog blocks every following evaluation
+			let e = page::JavascriptDialogOpening::deserialize(params)?;
+			let action = match self.dialog_policy
+			{
+				DialogPolicy::Accept => DialogAction::Accept(None),
+				DialogPolicy::Dismiss => DialogAction::Dismiss,
+				DialogPolicy::Callback(ref mut f) => f(&e)
+			};
+			match action
+			{
+				DialogAction::Accept(ref text) =>
+					self.page().handle_javascript_dialog(DIALOG_HANDLER_REQUEST_ID, true, text.as_ref().map(|s| s as &str))?,
+				DialogAction::Dismiss => self.page().handle_javascript_dialog(DIALOG_HANDLER_REQUEST_ID, false, None)?
+			}
+			self.dialogs.push(e);
+		}
+		else if name == runtime::ExecutionContextsCleared::METHOD_NAME { self.released_objects.borrow_mut().invalidate_all(); }
+		else if name == runtime::BindingCalled::METHOD_NAME
+		{
+			// calls of the bindings already removed(or not added through this session) are not kept
+			let e = runtime::BindingCalled::deserialize(params)?;
+			if self.bindings.contains(&e.name) { self.binding_calls.push_back(e); }
+		}
+		else if name == security::SecurityStateChanged::METHOD_NAME
+		{
+			let e = security::SecurityStateChanged::deserialize(params)?;
+			if e.is_issue() { self.security_issues.push(e.clone()); }
+			self.security_state = Some(e);
+		}
+		else if name == inspector::TargetCrashed::METHOD_NAME { return Err(self.on_gone(GoneReason::Crashed)); }
+		else if name == inspector::Detached::METHOD_NAME
+		{
+			let e = inspector::Detached::deserialize(params)?;
+			return Err(self.on_gone(GoneReason::Detached(e.reason)));
+		}
+		Ok(())
+	}
+	/// Waits for the next event. Results and errors of requests are discarded
+	pub fn next_event(&mut self) -> GenericResult<SessionEvent>
 	{
 		loop
 		{
 			let s = self.wait_text()?;
-			#[cfg(feature = "verbose")] println!("{}", format!("<<-- [wait_event]Received: {}", s).blue().bold());
 			let obj: SessionReceiveEvent = serde_json::from_str(&s)?;
 			match obj
 			{
-				e@SessionReceiveEvent::Error { .. } => return Err(e.error_text().unwrap().into()),
+				e@SessionReceiveEvent::Error { .. } => self.discard_error(&e),
 				SessionReceiveEvent::Method { method: name, params } =>
 				{
-					if name == page::FrameNavigated::METHOD_NAME
+					if name == page::FrameNavigatedOwned::METHOD_NAME
 					{
+						// the delivered event is also the one fed to the tracker and the subscribers
 						let e: page::FrameNavigatedOwned = serde_json::from_value(params)?;
-						self.dispatch_frame_navigated(&e.borrow());
-						if name == E::METHOD_NAME { return Ok(unsafe { transmute_copy(&e) }); }
+						self.frame_navigated(&e.borrow());
+						return Ok(SessionEvent::FrameNavigated(e));
 					}
-					else if name == E::METHOD_NAME { return serde_json::from_value(params).map_err(From::from); }
+					self.process_event(name, &params)?;
+					return SessionEvent::parse(name, params);
 				}
 				_ => ()
 			}
 		}
 	}
-	pub fn wait_result(&mut self, id: RequestID) -> GenericResult<JValue>
+	/// Stream of the events. Frames and dialogs are processed by the session before being delivered
+	pub fn events(&mut self) -> Events { Events { session: self, finished: false } }
+	pub fn wait_event<E: Event + DeserializeOwned>(&mut self) -> GenericResult<E>
 	{
 		loop
 		{
 			let s = self.wait_text()?;
-			#[cfg(feature = "verbose")] println!("{}", format!("<<-- [wait_result]Received: {}", s).blue().bold());
 			let obj: SessionReceiveEvent = serde_json::from_str(&s)?;
 			match obj
 			{
-				e@SessionReceiveEvent::Error { .. } => return Err(e.error_text().unwrap().into()),
+				e@SessionReceiveEvent::Error { .. } => self.discard_error(&e),
 				SessionReceiveEvent::Method { method: name, params } =>
 				{
-					if name == page::FrameNavigated::METHOD_NAME
-					{
-						self.dispatch_frame_navigated(&serde_json::from_value::<page::FrameNavigatedOwned>(params)?.borrow());
-					}
+					self.process_event(name, &params)?;
+					if name == E::METHOD_NAME { return serde_json::from_value(params).map_err(From::from); }
+				}
+				_ => ()
+			}
+		}
+	}
+	pub fn wait_result(&mut self, id: RequestID) -> GenericResult<JValue>
+	{
+		loop
+		{
+			let s = self.wait_text()?;
+			let obj: SessionReceiveEvent = serde_json::from_str(&s)?;
+			match obj
+			{
+				e@SessionReceiveEvent::Error { .. } =>
+				{
+					if e.request_id() == Some(id) { return Err(e.error_text().unwrap().into()); }
+					self.discard_error(&e);
 				},
+				SessionReceiveEvent::Method { method: name, params } => self.process_event(name, &params)?,
 				SessionReceiveEvent::Result { id: rid, result } => if rid == id { return Ok(result); },
 			}
 		}
 	}
-	fn send_text(&mut self, text: String) -> WebSocketResult<()>
+	/// Errors of the requests nobody is waiting for(e.g. dialog handling issued by the session itself) must not fail
+	/// the unrelated wait in progress, so they are only logged
+	fn discard_error(&self, e: &SessionReceiveEvent)
 	{
-		// println!("Sending {}", text);
-		#[cfg(feature = "verbose")] println!("{}", format!("-->> [send]Sending: {}", text).green().bold());
-		self.sender.send_message(&OwnedMessage::Text(text))
+		let text = e.error_text().unwrap();
+		match e.request_id()
+		{
+			Some(DIALOG_HANDLER_REQUEST_ID) => self.logger.log(Level::Warn, "session", &format!("Failed to handle a dialog: {}", text)),
+			// the object may have gone with its context(e.g. navigation)
+			Some(OBJECT_RELEASER_REQUEST_ID) => self.logger.log(Level::Debug, "session", &format!("Failed to release an object: {}", text)),
+			_ => self.logger.log(Level::Warn, "session", &format!("{} (not awaited, ignored)", text))
+		}
 	}
-	fn send<T: Serialize>(&mut self, payload: &T) -> WebSocketResult<()>
+	fn send_text(&mut self, text: String) -> GenericResult<()>
 	{
+		if self.logger.enabled(Level::Debug)
+		{
+			if self.sensitive
+			{
+				// results of sensitive requests may echo the secret back
+				if let Some(id) = serde_json::from_str::<JValue>(&text).ok().and_then(|v| v["id"].as_u64())
+				{
+					self.sensitive_requests.insert(id);
+				}
+				self.logger.log(Level::Debug, "send", &redact_message(&text));
+			}
+			else { self.logger.log(Level::Debug, "send", &text); }
+		}
+		match self.transport.send_text(text)
+		{
+			Ok(()) => Ok(()), Err(e) => Err(self.recover(e))
+		}
+	}
+	fn send<T: Serialize>(&mut self, payload: &T) -> GenericResult<()>
+	{
+		if !self.released_objects.borrow().is_empty() { self.flush_released_objects()?; }
 		self.send_text(::serde_json::to_string(payload).unwrap())
 	}
 }
 pub mod dom
 {
-	use std::io::prelude::*;
 	use serde_json::{Value as JValue};
 
 	#[derive(Deserialize)]
@@ -218,14 +821,14 @@ pub mod dom
 		const METHOD_NAME: &'static str = "DOM.documentUpdated";
 	}
 
-	pub struct Node<'s, 'c: 's, W: Write + 'c, R: Read + 'c> { pub domain: &'s mut super::domain::DOM<'c, W, R>, pub id: isize }
-	impl<'s, 'c: 's, W: Write + 'c, R: Read + 'c> Node<'s, 'c, W, R>
+	pub struct Node<'s, 'c: 's> { pub domain: &'s mut super::domain::DOM<'c>, pub id: isize }
+	impl<'s, 'c: 's> Node<'s, 'c>
 	{
-		pub fn query_selector<'ss: 's>(&'ss mut self, selector: &str) -> super::GenericResult<Node<'s, 'c, W, R>>
+		pub fn query_selector<'ss: 's>(&'ss mut self, selector: &str) -> super::GenericResult<Node<'s, 'c>>
 		{
 			self.domain.query_selector_sync(1000, self.id, selector).map(move |nid| Node { domain: self.domain, id: nid })
 		}
-		pub fn query_selector_nth<'ss: 's>(&'ss mut self, selector: &str, index: usize) -> super::GenericResult<Node<'s, 'c, W, R>>
+		pub fn query_selector_nth<'ss: 's>(&'ss mut self, selector: &str, index: usize) -> super::GenericResult<Node<'s, 'c>>
 		{
 			let ref nid = self.domain.query_selector_all_sync(1000, self.id, selector)?[index];
 			Ok(Node { domain: self.domain, id: nid.as_i64().unwrap() as _ })
@@ -255,7 +858,7 @@ pub mod dom
 #[allow(dead_code)]
 pub mod page
 {
-	#[derive(Deserialize, Clone, Copy)] #[serde(rename_all = "camelCase")]
+	#[derive(Deserialize, Debug, Clone, Copy)] #[serde(rename_all = "camelCase")]
 	pub struct LoadEventFired { timestamp: f64 }
 	impl super::Event for LoadEventFired
 	{
@@ -263,7 +866,7 @@ pub mod page
 	}
 	#[derive(Deserialize)] #[serde(rename_all = "camelCase")]
 	pub struct FrameStoppedLoading<'d> { pub frame_id: &'d str }
-	#[derive(Deserialize)] #[serde(rename_all = "camelCase")]
+	#[derive(Deserialize, Debug, Clone)] #[serde(rename_all = "camelCase")]
 	pub struct FrameStoppedLoadingOwned { pub frame_id: String }
 	impl<'d> super::Event for FrameStoppedLoading<'d>
 	{
@@ -280,7 +883,7 @@ pub mod page
 	}
 	#[derive(Deserialize)] #[serde(rename_all = "camelCase")]
 	pub struct FrameNavigated<'d> { #[serde(borrow = "'d")] pub frame: Frame<'d> }
-	#[derive(Deserialize)] #[serde(rename_all = "camelCase")]
+	#[derive(Deserialize, Debug, Clone)] #[serde(rename_all = "camelCase")]
 	pub struct FrameNavigatedOwned { pub frame: FrameOwned }
 	impl<'d> super::Event for FrameNavigated<'d>  { const METHOD_NAME: &'static str = "Page.frameNavigated"; }
 	impl     super::Event for FrameNavigatedOwned { const METHOD_NAME: &'static str = "Page.frameNavigated"; }
@@ -293,6 +896,28 @@ pub mod page
 		pub fn to_owned(&self) -> FrameNavigatedOwned { FrameNavigatedOwned { frame: self.frame.to_owned() } }
 	}
 
+	#[derive(Deserialize)] #[serde(rename_all = "camelCase")]
+	pub struct FrameDetached<'d> { pub frame_id: &'d str }
+	impl<'d> super::Event for FrameDetached<'d> { const METHOD_NAME: &'static str = "Page.frameDetached"; }
+
+	#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)] #[serde(rename_all = "camelCase")]
+	pub enum DialogType { Alert, Confirm, Prompt, Beforeunload }
+	#[derive(Deserialize, Debug, Clone, PartialEq, Eq)] #[serde(rename_all = "camelCase")]
+	pub struct JavascriptDialogOpening
+	{
+		pub url: Option<String>, pub message: String,
+		#[serde(rename = "type")] pub type_: DialogType,
+		pub default_prompt: Option<String>
+	}
+	impl super::Event for JavascriptDialogOpening { const METHOD_NAME: &'static str = "Page.javascriptDialogOpening"; }
+	#[derive(Deserialize, Debug, Clone, PartialEq, Eq)] #[serde(rename_all = "camelCase")]
+	pub struct JavascriptDialogClosed { pub result: bool, pub user_input: Option<String> }
+	impl super::Event for JavascriptDialogClosed { const METHOD_NAME: &'static str = "Page.javascriptDialogClosed"; }
+
+	/// `Page.getFrameTree` result
+	#[derive(Deserialize, Debug, Clone)] #[serde(rename_all = "camelCase")]
+	pub struct FrameTree { pub frame: FrameOwned, pub child_frames: Option<Vec<FrameTree>> }
+
 	#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq)] #[serde(rename_all = "camelCase")]
 	pub struct FrameOwned
 	{
@@ -333,6 +958,176 @@ pub mod page
 		}
 	}
 }
+pub mod inspector
+{
+	#[derive(Deserialize, Debug, Clone)]
+	pub struct TargetCrashed {}
+	impl super::Event for TargetCrashed { const METHOD_NAME: &'static str = "Inspector.targetCrashed"; }
+	#[derive(Deserialize, Debug, Clone)]
+	pub struct Detached { pub reason: String }
+	impl super::Event for Detached { const METHOD_NAME: &'static str = "Inspector.detached"; }
+}
+pub mod dom_snapshot
+{
+	/// Index into `CaptureSnapshotResult::strings`(-1 means none)
+	pub type StringIndex = i64;
+
+	/// Values for the nodes listed in `index`
+	#[derive(Deserialize, Debug, Clone, Default)] #[serde(rename_all = "camelCase")]
+	pub struct RareStringData { pub index: Vec<usize>, pub value: Vec<StringIndex> }
+	#[derive(Deserialize, Debug, Clone, Default)] #[serde(rename_all = "camelCase")]
+	pub struct RareBooleanData { pub index: Vec<usize> }
+	#[derive(Deserialize, Debug, Clone, Default)] #[serde(rename_all = "camelCase")]
+	pub struct RareIntegerData { pub index: Vec<usize>, pub value: Vec<i64> }
+	/// Nodes in pre-order. Each field is indexed by node
+	#[derive(Deserialize, Debug, Clone, Default)] #[serde(rename_all = "camelCase", default)]
+	pub struct NodeTreeSnapshot
+	{
+		/// -1 for the document node
+		pub parent_index: Vec<i64>, pub node_type: Vec<u32>, pub node_name: Vec<StringIndex>, pub node_value: Vec<StringIndex>,
+		pub backend_node_id: Vec<i64>,
+		/// name and value pairs flattened
+		pub attributes: Vec<Vec<StringIndex>>,
+		pub text_value: RareStringData, pub input_value: RareStringData, pub input_checked: RareBooleanData,
+		pub content_document_index: RareIntegerData
+	}
+	/// Rendered nodes. Each field is indexed by layout object
+	#[derive(Deserialize, Debug, Clone, Default)] #[serde(rename_all = "camelCase", default)]
+	pub struct LayoutTreeSnapshot
+	{
+		pub node_index: Vec<usize>,
+		/// values of the requested computed styles
+		pub styles: Vec<Vec<StringIndex>>,
+		/// x, y, width, height
+		pub bounds: Vec<Vec<f64>>,
+		pub text: Vec<StringIndex>
+	}
+	#[derive(Deserialize, Debug, Clone)] #[serde(rename_all = "camelCase")]
+	pub struct DocumentSnapshot
+	{
+		#[serde(rename = "documentURL")] pub document_url: StringIndex, pub title: StringIndex, pub frame_id: StringIndex,
+		pub nodes: NodeTreeSnapshot, pub layout: LayoutTreeSnapshot
+	}
+	#[derive(Deserialize, Debug, Clone)] #[serde(rename_all = "camelCase")]
+	pub struct CaptureSnapshotResult { pub documents: Vec<DocumentSnapshot>, pub strings: Vec<String> }
+	impl CaptureSnapshotResult
+	{
+		pub fn string(&self, index: StringIndex) -> Option<&str>
+		{
+			if index < 0 { None } else { self.strings.get(index as usize).map(|s| s as &str) }
+		}
+		/// The document of the frame
+		pub fn document_of_frame(&self, frame_id: &str) -> Option<usize>
+		{
+			self.documents.iter().position(|d| self.string(d.frame_id) == Some(frame_id))
+		}
+	}
+}
+pub mod network
+{
+	#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)] #[serde(rename_all = "camelCase")]
+	pub enum ConnectionType { None, Cellular2g, Cellular3g, Cellular4g, Bluetooth, Ethernet, Wifi, Wimax, Other }
+	/// Parameters of `Network.emulateNetworkConditions`. Throughputs are in bytes/sec, -1 disables throttling
+	#[derive(Serialize, Debug, Clone, PartialEq)] #[serde(rename_all = "camelCase")]
+	pub struct NetworkConditions
+	{
+		pub offline: bool,
+		/// Minimum latency from request sent to response headers received(ms)
+		pub latency: f64,
+		pub download_throughput: f64, pub upload_throughput: f64,
+		#[serde(skip_serializing_if = "Option::is_none")] pub connection_type: Option<ConnectionType>
+	}
+	/// Presets(same values as DevTools)
+	impl NetworkConditions
+	{
+		pub fn no_throttling() -> Self
+		{
+			NetworkConditions { offline: false, latency: 0.0, download_throughput: -1.0, upload_throughput: -1.0, connection_type: None }
+		}
+		pub fn offline() -> Self
+		{
+			NetworkConditions { offline: true, latency: 0.0, download_throughput: 0.0, upload_throughput: 0.0, connection_type: Some(ConnectionType::None) }
+		}
+		pub fn slow_3g() -> Self
+		{
+			NetworkConditions
+			{
+				offline: false, latency: 2000.0, download_throughput: 500.0 * 1024.0 / 8.0 * 0.8, upload_throughput: 500.0 * 1024.0 / 8.0 * 0.8,
+				connection_type: Some(ConnectionType::Cellular3g)
+			}
+		}
+		pub fn fast_3g() -> Self
+		{
+			NetworkConditions
+			{
+				offline: false, latency: 562.5, download_throughput: 1.6 * 1024.0 * 1024.0 / 8.0 * 0.9, upload_throughput: 750.0 * 1024.0 / 8.0 * 0.9,
+				connection_type: Some(ConnectionType::Cellular3g)
+			}
+		}
+	}
+}
+pub mod security
+{
+	#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)] #[serde(rename_all = "kebab-case")]
+	pub enum SecurityState { Unknown, Neutral, Insecure, Secure, Info, InsecureBroken }
+	impl SecurityState
+	{
+		pub fn is_insecure(self) -> bool { self == SecurityState::Insecure || self == SecurityState::InsecureBroken }
+	}
+	#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)] #[serde(rename_all = "kebab-case")]
+	pub enum MixedContentType { Blockable, OptionallyBlockable, None }
+	#[derive(Deserialize, Debug, Clone)] #[serde(rename_all = "camelCase")]
+	pub struct SecurityStateExplanation
+	{
+		pub security_state: SecurityState, pub title: Option<String>, pub summary: String, pub description: String,
+		pub mixed_content_type: Option<MixedContentType>
+	}
+	/// Mixed and certificate-error content found in the page
+	#[derive(Deserialize, Debug, Clone, Default)] #[serde(rename_all = "camelCase")]
+	pub struct InsecureContentStatus
+	{
+		pub ran_mixed_content: bool, pub displayed_mixed_content: bool, pub contained_mixed_form: bool,
+		pub ran_content_with_cert_errors: bool, pub displayed_content_with_cert_errors: bool
+	}
+	#[derive(Deserialize, Debug, Clone)] #[serde(rename_all = "camelCase")]
+	pub struct SecurityStateChanged
+	{
+		pub security_state: SecurityState,
+		#[serde(default)] pub explanations: Vec<SecurityStateExplanation>,
+		pub insecure_content_status: Option<InsecureContentStatus>,
+		pub summary: Option<String>
+	}
+	impl super::Event for SecurityStateChanged { const METHOD_NAME: &'static str = "Security.securityStateChanged"; }
+	impl SecurityStateChanged
+	{
+		/// Whether the page has loaded(or ran) some content over plain HTTP
+		pub fn has_mixed_content(&self) -> bool
+		{
+			self.explanations.iter().any(|e| e.mixed_content_type.map(|t| t != MixedContentType::None).unwrap_or(false))
+				|| self.insecure_content_status.as_ref().map(|c| c.ran_mixed_content || c.displayed_mixed_content || c.contained_mixed_form)
+					.unwrap_or(false)
+		}
+		/// Whether the state is worth reporting(insecure or mixed)
+		pub fn is_issue(&self) -> bool { self.security_state.is_insecure() || self.has_mixed_content() }
+	}
+}
+pub mod target
+{
+	/// Unique target identifier
+	pub type TargetID = String;
+	/// Unique identifier of an attached debugging session
+	pub type SessionID = String;
+
+	#[derive(Deserialize, Debug, Clone, PartialEq, Eq)] #[serde(rename_all = "camelCase")]
+	pub struct TargetInfo
+	{
+		pub target_id: TargetID, #[serde(rename = "type")] pub type_: String,
+		pub title: String, pub url: String, pub attached: bool
+	}
+	#[derive(Deserialize, Debug, Clone)] #[serde(rename_all = "camelCase")]
+	pub struct DetachedFromTarget { pub session_id: SessionID }
+	impl super::Event for DetachedFromTarget { const METHOD_NAME: &'static str = "Target.detachedFromTarget"; }
+}
 #[allow(dead_code)]
 pub mod input
 {
@@ -343,13 +1138,13 @@ pub mod runtime
 {
 	use serde_json::{Value as JValue, Map as JMap};
 
-	#[derive(Deserialize, Clone)] #[serde(rename_all = "camelCase")]
+	#[derive(Deserialize, Debug, Clone)] #[serde(rename_all = "camelCase")]
 	pub struct ExecutionContextCreated { pub context: ExecutionContextDescription }
 	impl super::Event for ExecutionContextCreated
 	{
 		const METHOD_NAME: &'static str = "Runtime.executionContextCreated";
 	}
-	#[derive(Deserialize, Clone, Copy)] #[serde(rename_all = "camelCase")]
+	#[derive(Deserialize, Debug, Clone, Copy)] #[serde(rename_all = "camelCase")]
 	pub struct ExecutionContextDestroyed { pub execution_context_id: ExecutionContextID }
 	impl super::Event for ExecutionContextDestroyed
 	{
@@ -361,6 +1156,10 @@ pub mod runtime
 	{
 		const METHOD_NAME: &'static str = "Runtime.executionContextsCleared";
 	}
+	/// A page script called a function installed by `Runtime.addBinding`
+	#[derive(Deserialize, Debug, Clone)] #[serde(rename_all = "camelCase")]
+	pub struct BindingCalled { pub name: String, pub payload: String, pub execution_context_id: ExecutionContextID }
+	impl super::Event for BindingCalled { const METHOD_NAME: &'static str = "Runtime.bindingCalled"; }
 
 	/// Unique script identifier
 	pub type ScriptID = String;
@@ -420,6 +1219,31 @@ pub mod runtime
 	}
 	#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)] #[serde(rename_all = "camelCase")]
 	pub struct EvaluateResult { pub result: RemoteObject, pub exception_details: Option<ExceptionDetails> }
+	#[derive(Deserialize, Debug, Clone)] #[serde(rename_all = "camelCase")]
+	pub struct CompileScriptResult { pub script_id: Option<ScriptID>, pub exception_details: Option<ExceptionDetails> }
+	/// Object property descriptor(`Runtime.getProperties`)
+	#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)] #[serde(rename_all = "camelCase")]
+	pub struct PropertyDescriptor
+	{
+		pub name: String, pub value: Option<RemoteObject>, pub writable: Option<bool>,
+		pub get: Option<RemoteObject>, pub set: Option<RemoteObject>,
+		pub configurable: bool, pub enumerable: bool, pub was_thrown: Option<bool>, pub is_own: Option<bool>
+	}
+	#[derive(Deserialize, Debug, PartialEq, Clone)] #[serde(rename_all = "camelCase")]
+	pub struct GetPropertiesResult { pub result: Vec<PropertyDescriptor>, pub exception_details: Option<ExceptionDetails> }
+	/// Argument of `Runtime.callFunctionOn`. Specify one of the fields(nothing means `undefined`)
+	#[derive(Serialize, Debug, PartialEq, Clone, Default)] #[serde(rename_all = "camelCase")]
+	pub struct CallArgument
+	{
+		#[serde(skip_serializing_if = "Option::is_none")] pub value: Option<JValue>,
+		#[serde(skip_serializing_if = "Option::is_none")] pub unserializable_value: Option<UnserializableValue>,
+		#[serde(skip_serializing_if = "Option::is_none")] pub object_id: Option<RemoteObjectID>
+	}
+	impl CallArgument
+	{
+		pub fn value(v: JValue) -> Self { CallArgument { value: Some(v), .. Default::default() } }
+		pub fn object(object_id: &str) -> Self { CallArgument { object_id: Some(object_id.to_owned()), .. Default::default() } }
+	}
 
 	/// Typing Helpers
 	impl RemoteObject
@@ -447,54 +1271,59 @@ pub mod runtime
 }
 pub mod domain
 {
-	use super::{Session, RequestID};
-	use std::io::prelude::*;
-	use websocket::WebSocketResult;
+	use super::{Session, RequestID, GenericResult};
 	use serde_json::Value as JValue;
 
-	pub struct DOM<'c, W: Write + 'c, R: Read + 'c>(pub &'c mut Session<W, R>);
-	impl<'c, W: Write + 'c, R: Read + 'c> DOM<'c, W, R>
+	pub struct DOM<'c>(pub &'c mut Session);
+	impl<'c> DOM<'c>
 	{
-		pub fn enable(&mut self, id: RequestID) -> WebSocketResult<()>
+		pub fn enable(&mut self, id: RequestID) -> GenericResult<()>
 		{
 			#[derive(Serialize)] struct Payload { method: &'static str, id: RequestID }
 			self.0.send(&Payload { method: "DOM.enable", id })
 		}
-		pub fn get_document(&mut self, id: RequestID) -> WebSocketResult<()>
+		pub fn get_document(&mut self, id: RequestID) -> GenericResult<()>
 		{
 			#[derive(Serialize)] struct Payload { method: &'static str, id: RequestID }
 			self.0.send(&Payload { method: "DOM.getDocument", id })
 		}
-		pub fn query_selector(&mut self, id: RequestID, node_id: isize, selector: &str) -> WebSocketResult<()>
+		pub fn query_selector(&mut self, id: RequestID, node_id: isize, selector: &str) -> GenericResult<()>
 		{
 			#[derive(Serialize)] struct Payload<'s> { method: &'static str, id: RequestID, params: Params<'s> }
 			#[derive(Serialize)] #[serde(rename_all = "camelCase")] struct Params<'s> { node_id: isize, selector: &'s str }
 			self.0.send(&Payload { method: "DOM.querySelector", id, params: Params { node_id, selector } })
 		}
-		pub fn query_selector_all(&mut self, id: RequestID, node_id: isize, selector: &str) -> WebSocketResult<()>
+		pub fn query_selector_all(&mut self, id: RequestID, node_id: isize, selector: &str) -> GenericResult<()>
 		{
 			#[derive(Serialize)] struct Payload<'s> { method: &'static str, id: RequestID, params: Params<'s> }
 			#[derive(Serialize)] #[serde(rename_all = "camelCase")] struct Params<'s> { node_id: isize, selector: &'s str }
 			self.0.send(&Payload { method: "DOM.querySelectorAll", id, params: Params { node_id, selector } })
 		}
-		pub fn focus(&mut self, id: RequestID, node_id: isize) -> WebSocketResult<()>
+		pub fn focus(&mut self, id: RequestID, node_id: isize) -> GenericResult<()>
 		{
 			#[derive(Serialize)] struct Payload { method: &'static str, id: RequestID, params: Params }
 			#[derive(Serialize)] #[serde(rename_all = "camelCase")] struct Params { node_id: isize }
 			self.0.send(&Payload { method: "DOM.focus", id, params: Params { node_id } })
 		}
-		pub fn get_attributes(&mut self, id: RequestID, node_id: isize) -> WebSocketResult<()>
+		pub fn get_attributes(&mut self, id: RequestID, node_id: isize) -> GenericResult<()>
 		{
 			#[derive(Serialize)] struct Payload { method: &'static str, id: RequestID, params: Params }
 			#[derive(Serialize)] #[serde(rename_all = "camelCase")] struct Params { node_id: isize }
 			self.0.send(&Payload { method: "DOM.getAttributes", id, params: Params { node_id } })
 		}
+		/// Pushes the node of the remote object to the front-end(the document must have been requested)
+		pub fn request_node(&mut self, id: RequestID, object_id: &str) -> GenericResult<()>
+		{
+			#[derive(Serialize)] struct Payload<'s> { method: &'static str, id: RequestID, params: Params<'s> }
+			#[derive(Serialize)] #[serde(rename_all = "camelCase")] struct Params<'s> { object_id: &'s str }
+			self.0.send(&Payload { method: "DOM.requestNode", id, params: Params { object_id } })
+		}
 
 		pub fn get_document_sync(&mut self, id: RequestID) -> super::GenericResult<JValue>
 		{
 			self.get_document(id).map_err(From::from).and_then(|_| self.0.wait_result(id))
 		}
-		pub fn get_root_node_sync<'s>(&'s mut self, id: RequestID) -> super::GenericResult<super::dom::Node<'s, 'c, W, R>>
+		pub fn get_root_node_sync<'s>(&'s mut self, id: RequestID) -> super::GenericResult<super::dom::Node<'s, 'c>>
 		{
 			self.get_document_sync(id).map(move |id| super::dom::Node { domain: self, id: id["root"]["nodeId"].as_i64().unwrap() as isize })
 		}
@@ -523,16 +1352,31 @@ pub mod domain
 		{
 			self.get_attributes(id, node_id).map_err(From::from).and_then(|_| self.0.wait_result(id))
 		}
+		pub fn request_node_sync(&mut self, id: RequestID, object_id: &str) -> super::GenericResult<isize>
+		{
+			self.request_node(id, object_id).and_then(|_| self.0.wait_result(id))
+				.map(|o| o["nodeId"].as_i64().unwrap_or_else(|| api_corruption!(value_type)) as isize)
+		}
 
-		pub fn node_from<'s>(&'s mut self, id: isize) -> super::dom::Node<'s, 'c, W, R>
+		pub fn node_from<'s>(&'s mut self, id: isize) -> super::dom::Node<'s, 'c>
 		{
 			super::dom::Node { domain: self, id }
 		}
 	}
-	pub struct Input<'c, W: Write + 'c, R: Read + 'c>(pub &'c mut Session<W, R>);
-	impl<'c, W: Write + 'c, R: Read + 'c> Input<'c, W, R>
+	pub struct Inspector<'c>(pub &'c mut Session);
+	impl<'c> Inspector<'c>
 	{
-		pub fn dispatch_key_event(&mut self, id: RequestID, etype: super::input::KeyEvent, text: Option<&str>) -> WebSocketResult<()>
+		/// Enables reporting of `targetCrashed` and `detached` events
+		pub fn enable(&mut self, id: RequestID) -> GenericResult<()>
+		{
+			#[derive(Serialize)] struct Payload { method: &'static str, id: RequestID }
+			self.0.send(&Payload { method: "Inspector.enable", id })
+		}
+	}
+	pub struct Input<'c>(pub &'c mut Session);
+	impl<'c> Input<'c>
+	{
+		pub fn dispatch_key_event(&mut self, id: RequestID, etype: super::input::KeyEvent, text: Option<&str>) -> GenericResult<()>
 		{
 			#[derive(Serialize)] struct Payload<'s> { method: &'static str, id: RequestID, params: Params<'s> }
 			#[derive(Serialize)] struct Params<'s> { #[serde(rename = "type")] etype: super::input::KeyEvent, text: Option<&'s str> }
@@ -544,42 +1388,135 @@ pub mod domain
 			self.dispatch_key_event(id, etype, text).map_err(From::from).and_then(|_| self.0.wait_result(id)).map(|_| ())
 		}
 	}
-	pub struct Network<'c, W: Write + 'c, R: Read + 'c>(pub &'c mut Session<W, R>);
-	impl<'c, W: Write + 'c, R: Read + 'c> Network<'c, W, R>
+	pub struct Network<'c>(pub &'c mut Session);
+	impl<'c> Network<'c>
 	{
-		pub fn set_user_agent_override(&mut self, id: RequestID, ua: &str) -> WebSocketResult<()>
+		pub fn set_user_agent_override(&mut self, id: RequestID, ua: &str) -> GenericResult<()>
 		{
 			#[derive(Serialize)] struct Payload<'s> { method: &'static str, id: RequestID, params: Params<'s> }
 			#[derive(Serialize)] #[serde(rename_all = "camelCase")] struct Params<'s> { user_agent: &'s str }
 			self.0.send(&Payload { method: "Network.setUserAgentOverride", id, params: Params { user_agent: ua } })
 		}
+		/// Enables network tracking(needed by the emulation)
+		pub fn enable(&mut self, id: RequestID) -> GenericResult<()>
+		{
+			#[derive(Serialize)] struct Payload { method: &'static str, id: RequestID }
+			self.0.send(&Payload { method: "Network.enable", id })
+		}
+		pub fn disable(&mut self, id: RequestID) -> GenericResult<()>
+		{
+			#[derive(Serialize)] struct Payload { method: &'static str, id: RequestID }
+			self.0.send(&Payload { method: "Network.disable", id })
+		}
+		pub fn emulate_network_conditions(&mut self, id: RequestID, conditions: &super::network::NetworkConditions) -> GenericResult<()>
+		{
+			#[derive(Serialize)] struct Payload<'s> { method: &'static str, id: RequestID, params: &'s super::network::NetworkConditions }
+			self.0.send(&Payload { method: "Network.emulateNetworkConditions", id, params: conditions })
+		}
+		/// Toggles ignoring of cache for each request
+		pub fn set_cache_disabled(&mut self, id: RequestID, cache_disabled: bool) -> GenericResult<()>
+		{
+			#[derive(Serialize)] struct Payload { method: &'static str, id: RequestID, params: Params }
+			#[derive(Serialize)] #[serde(rename_all = "camelCase")] struct Params { cache_disabled: bool }
+			self.0.send(&Payload { method: "Network.setCacheDisabled", id, params: Params { cache_disabled } })
+		}
+	}
+	pub struct DOMSnapshot<'c>(pub &'c mut Session);
+	impl<'c> DOMSnapshot<'c>
+	{
+		/// Captures the documents of the page(including iframes) with layout and the computed styles listed
+		pub fn capture_snapshot(&mut self, id: RequestID, computed_styles: &[&str]) -> GenericResult<()>
+		{
+			#[derive(Serialize)] struct Payload<'s> { method: &'static str, id: RequestID, params: Params<'s> }
+			#[derive(Serialize)] #[serde(rename_all = "camelCase")] struct Params<'s> { computed_styles: &'s [&'s str] }
+			self.0.send(&Payload { method: "DOMSnapshot.captureSnapshot", id, params: Params { computed_styles } })
+		}
+		pub fn capture_snapshot_sync(&mut self, id: RequestID, computed_styles: &[&str]) -> GenericResult<super::dom_snapshot::CaptureSnapshotResult>
+		{
+			self.capture_snapshot(id, computed_styles).and_then(|_| self.0.wait_result(id))
+				.and_then(|x| ::serde_json::from_value(x).map_err(From::from))
+		}
+	}
+	pub struct Security<'c>(pub &'c mut Session);
+	impl<'c> Security<'c>
+	{
+		/// Enables reporting of `securityStateChanged` events
+		pub fn enable(&mut self, id: RequestID) -> GenericResult<()>
+		{
+			#[derive(Serialize)] struct Payload { method: &'static str, id: RequestID }
+			self.0.send(&Payload { method: "Security.enable", id })
+		}
+		pub fn disable(&mut self, id: RequestID) -> GenericResult<()>
+		{
+			#[derive(Serialize)] struct Payload { method: &'static str, id: RequestID }
+			self.0.send(&Payload { method: "Security.disable", id })
+		}
+		/// Makes the page load despite invalid certificates(e.g. self-signed test servers)
+		pub fn set_ignore_certificate_errors(&mut self, id: RequestID, ignore: bool) -> GenericResult<()>
+		{
+			#[derive(Serialize)] struct Payload { method: &'static str, id: RequestID, params: Params }
+			#[derive(Serialize)] struct Params { ignore: bool }
+			self.0.send(&Payload { method: "Security.setIgnoreCertificateErrors", id, params: Params { ignore } })
+		}
 	}
-	pub struct Page<'c, W: Write + 'c, R: Read + 'c>(pub &'c mut Session<W, R>);
-	impl<'c, W: Write + 'c, R: Read + 'c> Page<'c, W, R>
+	pub struct Page<'c>(pub &'c mut Session);
+	impl<'c> Page<'c>
 	{
-		pub fn enable(&mut self, id: RequestID) -> WebSocketResult<()>
+		pub fn enable(&mut self, id: RequestID) -> GenericResult<()>
 		{
 			#[derive(Serialize)] struct Payload { method: &'static str, id: RequestID }
 			self.0.send(&Payload { method: "Page.enable", id })
 		}
-		pub fn navigate(&mut self, id: RequestID, url: &str) -> WebSocketResult<()>
+		pub fn navigate(&mut self, id: RequestID, url: &str) -> GenericResult<()>
 		{
 			#[derive(Serialize)] struct Payload<'s> { method: &'static str, id: RequestID, params: Params<'s> }
 			#[derive(Serialize)] struct Params<'s> { url: &'s str }
 			self.0.send(&Payload { method: "Page.navigate", id, params: Params { url } })
 		}
-		pub fn get_resource_tree(&mut self, id: RequestID) -> WebSocketResult<()>
+		pub fn get_resource_tree(&mut self, id: RequestID) -> GenericResult<()>
 		{
 			#[derive(Serialize)] struct Payload { method: &'static str, id: RequestID }
 			self.0.send(&Payload { method: "Page.getResourceTree", id })
 		}
-		/// Experimental(stable版Chromeだと返り値がない)
-		#[allow(dead_code)]
-		pub fn create_isolated_world(&mut self, id: RequestID, frame_id: &str) -> WebSocketResult<()>
+		pub fn get_frame_tree(&mut self, id: RequestID) -> GenericResult<()>
+		{
+			#[derive(Serialize)] struct Payload { method: &'static str, id: RequestID }
+			self.0.send(&Payload { method: "Page.getFrameTree", id })
+		}
+		pub fn handle_javascript_dialog(&mut self, id: RequestID, accept: bool, prompt_text: Option<&str>) -> GenericResult<()>
+		{
+			#[derive(Serialize)] struct Payload<'s> { method: &'static str, id: RequestID, params: Params<'s> }
+			#[derive(Serialize)] #[serde(rename_all = "camelCase")] struct Params<'s>
+			{
+				accept: bool, #[serde(skip_serializing_if = "Option::is_none")] prompt_text: Option<&'s str>
+			}
+			self.0.send(&Payload { method: "Page.handleJavaScriptDialog", id, params: Params { accept, prompt_text } })
+		}
+		/// Evaluates `source` in every new document(in the isolated world `world_name` if specified) before its own scripts
+		pub fn add_script_to_evaluate_on_new_document(&mut self, id: RequestID, source: &str, world_name: Option<&str>) -> GenericResult<()>
+		{
+			#[derive(Serialize)] struct Payload<'s> { method: &'static str, id: RequestID, params: Params<'s> }
+			#[derive(Serialize)] #[serde(rename_all = "camelCase")] struct Params<'s>
+			{
+				source: &'s str, #[serde(skip_serializing_if = "Option::is_none")] world_name: Option<&'s str>
+			}
+			self.0.send(&Payload { method: "Page.addScriptToEvaluateOnNewDocument", id, params: Params { source, world_name } })
+		}
+		pub fn remove_script_to_evaluate_on_new_document(&mut self, id: RequestID, identifier: &str) -> GenericResult<()>
 		{
 			#[derive(Serialize)] struct Payload<'s> { method: &'static str, id: RequestID, params: Params<'s> }
-			#[derive(Serialize)] #[serde(rename_all = "camelCase")] struct Params<'s> { frame_id: &'s str }
-			self.0.send(&Payload { method: "Page.createIsolatedWorld", id, params: Params { frame_id } })
+			#[derive(Serialize)] struct Params<'s> { identifier: &'s str }
+			self.0.send(&Payload { method: "Page.removeScriptToEvaluateOnNewDocument", id, params: Params { identifier } })
+		}
+		/// Experimental(stable版Chromeだと返り値がない: the context is announced only by `Runtime.executionContextCreated`)
+		pub fn create_isolated_world(&mut self, id: RequestID, frame_id: &str, world_name: Option<&str>) -> GenericResult<()>
+		{
+			#[derive(Serialize)] struct Payload<'s> { method: &'static str, id: RequestID, params: Params<'s> }
+			#[derive(Serialize)] #[serde(rename_all = "camelCase")] struct Params<'s>
+			{
+				frame_id: &'s str, #[serde(skip_serializing_if = "Option::is_none")] world_name: Option<&'s str>
+			}
+			self.0.send(&Payload { method: "Page.createIsolatedWorld", id, params: Params { frame_id, world_name } })
 		}
 
 		pub fn navigate_sync(&mut self, id: RequestID, url: &str) -> super::GenericResult<()>
@@ -590,41 +1527,64 @@ pub mod domain
 		{
 			self.get_resource_tree(id).map_err(From::from).and_then(|_| self.0.wait_result(id))
 		}
-		#[allow(dead_code)]
-		pub fn create_isolated_world_sync(&mut self, id: RequestID, frame_id: &str) -> super::GenericResult<i64>
+		/// Returns the identifier of the script
+		pub fn add_script_to_evaluate_on_new_document_sync(&mut self, id: RequestID, source: &str, world_name: Option<&str>) -> GenericResult<String>
+		{
+			self.add_script_to_evaluate_on_new_document(id, source, world_name).and_then(|_| self.0.wait_result(id))
+				.map(|v| v["identifier"].as_str().unwrap_or_else(|| api_corruption!(value_type)).to_owned())
+		}
+		pub fn get_frame_tree_sync(&mut self, id: RequestID) -> super::GenericResult<super::page::FrameTree>
+		{
+			self.get_frame_tree(id).map_err(From::from).and_then(|_| self.0.wait_result(id))
+				.and_then(|mut v| ::serde_json::from_value(v["frameTree"].take()).map_err(From::from))
+		}
+		/// The id of the created context, if returned(not by stable Chrome)
+		pub fn create_isolated_world_sync(&mut self, id: RequestID, frame_id: &str, world_name: Option<&str>) -> super::GenericResult<Option<u64>>
 		{
-			self.create_isolated_world(id, frame_id).map_err(From::from).and_then(|_| self.0.wait_result(id)).map(|v| v.as_i64().unwrap())
+			self.create_isolated_world(id, frame_id, world_name).and_then(|_| self.0.wait_result(id))
+				.map(|v| v["executionContextId"].as_u64())
 		}
 	}
-	pub struct Runtime<'c, W: Write + 'c, R: Read + 'c>(pub &'c mut Session<W, R>);
-	impl<'c, W: Write + 'c, R: Read + 'c> Runtime<'c, W, R>
+	pub struct Runtime<'c>(pub &'c mut Session);
+	impl<'c> Runtime<'c>
 	{
-		pub fn evaluate(&mut self, id: RequestID, expression: &str) -> WebSocketResult<()>
+		pub fn evaluate(&mut self, id: RequestID, expression: &str) -> GenericResult<()>
 		{
 			#[derive(Serialize)] struct Payload<'s> { method: &'static str, id: RequestID, params: Params<'s> }
 			#[derive(Serialize)] struct Params<'s> { expression: &'s str }
 			self.0.send(&Payload { method: "Runtime.evaluate", id, params: Params { expression } })
 		}
 		#[allow(dead_code)]
-		pub fn evaluate_in(&mut self, id: RequestID, context_id: u64, expression: &str) -> WebSocketResult<()>
+		pub fn evaluate_in(&mut self, id: RequestID, context_id: u64, expression: &str) -> GenericResult<()>
 		{
 			#[derive(Serialize)] struct Payload<'s> { method: &'static str, id: RequestID, params: Params<'s> }
 			#[derive(Serialize)] #[serde(rename_all = "camelCase")] struct Params<'s> { expression: &'s str, context_id: u64 }
 			self.0.send(&Payload { method: "Runtime.evaluate", id, params: Params { expression, context_id } })
 		}
-		pub fn evaluate_value(&mut self, id: RequestID, expression: &str) -> WebSocketResult<()>
+		pub fn evaluate_value(&mut self, id: RequestID, expression: &str) -> GenericResult<()>
 		{
 			#[derive(Serialize)] struct Payload<'s> { method: &'static str, id: RequestID, params: Params<'s> }
 			#[derive(Serialize)] #[serde(rename_all = "camelCase")] struct Params<'s> { expression: &'s str, return_by_value: bool }
 			self.0.send(&Payload { method: "Runtime.evaluate", id, params: Params { expression, return_by_value: true } })
 		}
-		pub fn evaluate_value_in(&mut self, id: RequestID, context_id: u64, expression: &str) -> WebSocketResult<()>
+		pub fn evaluate_value_in(&mut self, id: RequestID, context_id: u64, expression: &str) -> GenericResult<()>
 		{
 			#[derive(Serialize)] struct Payload<'s> { method: &'static str, id: RequestID, params: Params<'s> }
 			#[derive(Serialize)] #[serde(rename_all = "camelCase")] struct Params<'s> { expression: &'s str, return_by_value: bool, context_id: u64 }
 			self.0.send(&Payload { method: "Runtime.evaluate", id, params: Params { expression, return_by_value: true, context_id } })
 		}
-		pub fn get_properties(&mut self, id: RequestID, object_id: &str) -> WebSocketResult<()>
+		/// Evaluates by value, waiting for the resolution if the result is a promise(a rejection is reported as an exception)
+		pub fn evaluate_awaited(&mut self, id: RequestID, context_id: Option<u64>, expression: &str) -> GenericResult<()>
+		{
+			#[derive(Serialize)] struct Payload<'s> { method: &'static str, id: RequestID, params: Params<'s> }
+			#[derive(Serialize)] #[serde(rename_all = "camelCase")] struct Params<'s>
+			{
+				expression: &'s str, return_by_value: bool, await_promise: bool,
+				#[serde(skip_serializing_if = "Option::is_none")] context_id: Option<u64>
+			}
+			self.0.send(&Payload { method: "Runtime.evaluate", id, params: Params { expression, return_by_value: true, await_promise: true, context_id } })
+		}
+		pub fn get_properties(&mut self, id: RequestID, object_id: &str) -> GenericResult<()>
 		{
 			#[derive(Serialize)] struct Payload<'s> { method: &'static str, id: RequestID, params: Params<'s> }
 			#[derive(Serialize)] #[serde(rename_all = "camelCase")] struct Params<'s> { object_id: &'s str }
@@ -652,49 +1612,320 @@ pub mod domain
 			self.evaluate_value_in(id, context_id, expression).map_err(From::from).and_then(|_| self.0.wait_result(id))
 				.and_then(|x| ::serde_json::from_value(x).map_err(From::from))
 		}
+		pub fn evaluate_awaited_sync(&mut self, id: RequestID, context_id: Option<u64>, expression: &str) -> super::GenericResult<super::runtime::EvaluateResult>
+		{
+			self.evaluate_awaited(id, context_id, expression).map_err(From::from).and_then(|_| self.0.wait_result(id))
+				.and_then(|x| ::serde_json::from_value(x).map_err(From::from))
+		}
 		pub fn get_properties_sync(&mut self, id: RequestID, object_id: &str) -> super::GenericResult<JValue>
 		{
 			self.get_properties(id, object_id).map_err(From::from).and_then(|_| self.0.wait_result(id))
 		}
 	}
 
+	/// Remote objects
+	impl<'c> Runtime<'c>
+	{
+		/// Evaluates without serializing the result. Returned objects belong to `object_group` if specified
+		pub fn evaluate_object(&mut self, id: RequestID, context_id: Option<u64>, expression: &str, object_group: Option<&str>) -> GenericResult<()>
+		{
+			#[derive(Serialize)] struct Payload<'s> { method: &'static str, id: RequestID, params: Params<'s> }
+			#[derive(Serialize)] #[serde(rename_all = "camelCase")] struct Params<'s>
+			{
+				expression: &'s str,
+				#[serde(skip_serializing_if = "Option::is_none")] context_id: Option<u64>,
+				#[serde(skip_serializing_if = "Option::is_none")] object_group: Option<&'s str>
+			}
+			self.0.send(&Payload { method: "Runtime.evaluate", id, params: Params { expression, context_id, object_group } })
+		}
+		/// Calls a function with the object as `this`
+		pub fn call_function_on(&mut self, id: RequestID, object_id: &str, function_declaration: &str,
+			arguments: &[super::runtime::CallArgument], return_by_value: bool, object_group: Option<&str>) -> GenericResult<()>
+		{
+			#[derive(Serialize)] struct Payload<'s> { method: &'static str, id: RequestID, params: Params<'s> }
+			#[derive(Serialize)] #[serde(rename_all = "camelCase")] struct Params<'s>
+			{
+				object_id: &'s str, function_declaration: &'s str, arguments: &'s [super::runtime::CallArgument], return_by_value: bool,
+				#[serde(skip_serializing_if = "Option::is_none")] object_group: Option<&'s str>
+			}
+			self.0.send(&Payload
+			{
+				method: "Runtime.callFunctionOn", id,
+				params: Params { object_id, function_declaration, arguments, return_by_value, object_group }
+			})
+		}
+		/// Own properties of the object(accessors are not invoked)
+		pub fn get_own_properties(&mut self, id: RequestID, object_id: &str) -> GenericResult<()>
+		{
+			#[derive(Serialize)] struct Payload<'s> { method: &'static str, id: RequestID, params: Params<'s> }
+			#[derive(Serialize)] #[serde(rename_all = "camelCase")] struct Params<'s> { object_id: &'s str, own_properties: bool }
+			self.0.send(&Payload { method: "Runtime.getProperties", id, params: Params { object_id, own_properties: true } })
+		}
+		/// Installs a global function `name(payload: string)` reporting `bindingCalled`.
+		/// Exposed only to the contexts named `execution_context_name`(e.g. an isolated world) if specified
+		pub fn add_binding(&mut self, id: RequestID, name: &str, execution_context_name: Option<&str>) -> GenericResult<()>
+		{
+			#[derive(Serialize)] struct Payload<'s> { method: &'static str, id: RequestID, params: Params<'s> }
+			#[derive(Serialize)] #[serde(rename_all = "camelCase")] struct Params<'s>
+			{
+				name: &'s str, #[serde(skip_serializing_if = "Option::is_none")] execution_context_name: Option<&'s str>
+			}
+			self.0.bindings.insert(name.to_owned());
+			self.0.send(&Payload { method: "Runtime.addBinding", id, params: Params { name, execution_context_name } })
+		}
+		/// The function stays in the existing contexts but no longer reports calls
+		pub fn remove_binding(&mut self, id: RequestID, name: &str) -> GenericResult<()>
+		{
+			#[derive(Serialize)] struct Payload<'s> { method: &'static str, id: RequestID, params: Params<'s> }
+			#[derive(Serialize)] struct Params<'s> { name: &'s str }
+			self.0.bindings.remove(name);
+			self.0.binding_calls.retain(|c| c.name != name);
+			self.0.send(&Payload { method: "Runtime.removeBinding", id, params: Params { name } })
+		}
+		pub fn release_object(&mut self, id: RequestID, object_id: &str) -> GenericResult<()>
+		{
+			#[derive(Serialize)] struct Payload<'s> { method: &'static str, id: RequestID, params: Params<'s> }
+			#[derive(Serialize)] #[serde(rename_all = "camelCase")] struct Params<'s> { object_id: &'s str }
+			self.0.send(&Payload { method: "Runtime.releaseObject", id, params: Params { object_id } })
+		}
+		/// Releases all objects in the group
+		pub fn release_object_group(&mut self, id: RequestID, object_group: &str) -> GenericResult<()>
+		{
+			#[derive(Serialize)] struct Payload<'s> { method: &'static str, id: RequestID, params: Params<'s> }
+			#[derive(Serialize)] #[serde(rename_all = "camelCase")] struct Params<'s> { object_group: &'s str }
+			self.0.released_objects.borrow_mut().invalidate_group(object_group);
+			self.0.send(&Payload { method: "Runtime.releaseObjectGroup", id, params: Params { object_group } })
+		}
+
+		pub fn evaluate_object_sync(&mut self, id: RequestID, context_id: Option<u64>, expression: &str, object_group: Option<&str>)
+			-> GenericResult<super::runtime::EvaluateResult>
+		{
+			self.evaluate_object(id, context_id, expression, object_group).and_then(|_| self.0.wait_result(id))
+				.and_then(|x| ::serde_json::from_value(x).map_err(From::from))
+		}
+		pub fn call_function_on_sync(&mut self, id: RequestID, object_id: &str, function_declaration: &str,
+			arguments: &[super::runtime::CallArgument], return_by_value: bool, object_group: Option<&str>) -> GenericResult<super::runtime::EvaluateResult>
+		{
+			self.call_function_on(id, object_id, function_declaration, arguments, return_by_value, object_group)
+				.and_then(|_| self.0.wait_result(id)).and_then(|x| ::serde_json::from_value(x).map_err(From::from))
+		}
+		pub fn get_own_properties_sync(&mut self, id: RequestID, object_id: &str) -> GenericResult<super::runtime::GetPropertiesResult>
+		{
+			self.get_own_properties(id, object_id).and_then(|_| self.0.wait_result(id))
+				.and_then(|x| ::serde_json::from_value(x).map_err(From::from))
+		}
+		pub fn release_object_group_sync(&mut self, id: RequestID, object_group: &str) -> GenericResult<()>
+		{
+			self.release_object_group(id, object_group).and_then(|_| self.0.wait_result(id)).map(drop)
+		}
+	}
+
+	/// Compiled scripts
+	impl<'c> Runtime<'c>
+	{
+		/// Compiles the expression in the context. Persisted scripts live until the context is destroyed
+		pub fn compile_script(&mut self, id: RequestID, context_id: u64, expression: &str, persist_script: bool) -> GenericResult<()>
+		{
+			#[derive(Serialize)] struct Payload<'s> { method: &'static str, id: RequestID, params: Params<'s> }
+			#[derive(Serialize)] #[serde(rename_all = "camelCase")] struct Params<'s>
+			{
+				expression: &'s str, #[serde(rename = "sourceURL")] source_url: &'s str, persist_script: bool, execution_context_id: u64
+			}
+			self.0.send(&Payload
+			{
+				method: "Runtime.compileScript", id,
+				params: Params { expression, source_url: "", persist_script, execution_context_id: context_id }
+			})
+		}
+		/// Runs a persisted script in the context it was compiled in. A promise result is waited for if `await_promise`
+		pub fn run_script(&mut self, id: RequestID, context_id: u64, script_id: &str, return_by_value: bool, await_promise: bool) -> GenericResult<()>
+		{
+			#[derive(Serialize)] struct Payload<'s> { method: &'static str, id: RequestID, params: Params<'s> }
+			#[derive(Serialize)] #[serde(rename_all = "camelCase")] struct Params<'s>
+			{
+				script_id: &'s str, execution_context_id: u64, return_by_value: bool, await_promise: bool
+			}
+			self.0.send(&Payload
+			{
+				method: "Runtime.runScript", id, params: Params { script_id, execution_context_id: context_id, return_by_value, await_promise }
+			})
+		}
+
+		pub fn compile_script_sync(&mut self, id: RequestID, context_id: u64, expression: &str, persist_script: bool)
+			-> GenericResult<super::runtime::CompileScriptResult>
+		{
+			self.compile_script(id, context_id, expression, persist_script).and_then(|_| self.0.wait_result(id))
+				.and_then(|x| ::serde_json::from_value(x).map_err(From::from))
+		}
+		pub fn run_script_sync(&mut self, id: RequestID, context_id: u64, script_id: &str, return_by_value: bool, await_promise: bool)
+			-> GenericResult<super::runtime::EvaluateResult>
+		{
+			self.run_script(id, context_id, script_id, return_by_value, await_promise).and_then(|_| self.0.wait_result(id))
+				.and_then(|x| ::serde_json::from_value(x).map_err(From::from))
+		}
+	}
+
 	/// Event Handlable
-	impl<'c, W: Write + 'c, R: Read + 'c> Runtime<'c, W, R>
+	impl<'c> Runtime<'c>
 	{
 		/// Enables reporting of execution contexts creation by means of `executionContextCreated` event.
 		/// When the reporting gets enabled the event will be sent immediately for each existing execution context.
-		pub fn enable(&mut self, id: RequestID) -> WebSocketResult<()>
+		pub fn enable(&mut self, id: RequestID) -> GenericResult<()>
 		{
 			#[derive(Serialize)] struct Payload { method: &'static str, id: RequestID }
 			self.0.send(&Payload { method: "Runtime.enable", id })
 		}
 		/// Disables reporting of execution contexts creation
-		pub fn disable(&mut self, id: RequestID) -> WebSocketResult<()>
+		pub fn disable(&mut self, id: RequestID) -> GenericResult<()>
 		{
 			#[derive(Serialize)] struct Payload { method: &'static str, id: RequestID }
 			self.0.send(&Payload { method: "Runtime.disable", id })
 		}
 	}
 }
-pub struct Process { process: Child, port: u16 }
+/// Pending response of the HTTP endpoints of the browser
+pub type AsyncResponse = Box<Future<Item = Response, Error = HyperError>>;
+/// Extra launch settings of the browser
+#[derive(Debug, Clone, Default)]
+pub struct LaunchOptions
+{
+	/// Loads pages served with invalid certificates(`--ignore-certificate-errors`)
+	pub ignore_certificate_errors: bool
+}
+impl LaunchOptions
+{
+	fn args(&self) -> Vec<String>
+	{
+		let mut args = vec!["--headless".to_owned(), "--disable-gpu".to_owned()];
+		if self.ignore_certificate_errors { args.push("--ignore-certificate-errors".to_owned()); }
+		args
+	}
+}
+/// Browser process. `port` is `None` when launched with `--remote-debugging-pipe`
+pub struct Process { process: Child, port: Option<u16>, bin: String, args: Vec<String> }
 impl Process
 {
 	pub fn run(port: u16, initial_url: &str) -> IOResult<Self>
+	{
+		Self::run_with_options(port, initial_url, &LaunchOptions::default())
+	}
+	pub fn run_with_options(port: u16, initial_url: &str, options: &LaunchOptions) -> IOResult<Self>
+	{
+		let chrome_bin = Self::chrome_bin();
+		let mut args = options.args();
+		args.push(format!("--remote-debugging-port={}", port)); args.push(initial_url.to_owned());
+		let process = Self::spawn(&chrome_bin, &args, port)?;
+		Ok(Process { process, port: Some(port), bin: chrome_bin, args })
+	}
+	/// Launches with `--remote-debugging-pipe`. No TCP port is opened;
+	/// the returned transport talks to the browser target(wrap it with `browser::BrowserConnection`)
+	#[cfg(unix)]
+	pub fn run_with_pipe(initial_url: &str, options: &LaunchOptions) -> IOResult<(Self, PipeTransport<File, File>)>
+	{
+		use std::os::unix::process::CommandExt;
+		use std::os::unix::io::{FromRawFd, AsRawFd};
+		use libc;
+
+		// keep the ends away from the children other than the browser.
+		// close-on-exec is set atomically, so a concurrent fork cannot inherit them
+		#[cfg(not(target_os = "macos"))]
+		fn pipe() -> IOResult<(File, File)>
+		{
+			let mut fds = [0; 2];
+			if unsafe { libc::pipe2(fds.as_mut_ptr(), libc::O_CLOEXEC) } != 0 { return Err(IOError::last_os_error()); }
+			Ok(unsafe { (File::from_raw_fd(fds[0]), File::from_raw_fd(fds[1])) })
+		}
+		// no pipe2 on macOS
+		#[cfg(target_os = "macos")]
+		fn pipe() -> IOResult<(File, File)>
+		{
+			let mut fds = [0; 2];
+			if unsafe { libc::pipe(fds.as_mut_ptr()) } != 0 { return Err(IOError::last_os_error()); }
+			for &fd in &fds { unsafe { libc::fcntl(fd, libc::F_SETFD, libc::FD_CLOEXEC); } }
+			Ok(unsafe { (File::from_raw_fd(fds[0]), File::from_raw_fd(fds[1])) })
+		}
+		let (browser_in, to_browser) = pipe()?;
+		let (from_browser, browser_out) = pipe()?;
+
+		let chrome_bin = Self::chrome_bin();
+		let mut args = options.args();
+		args.push("--remote-debugging-pipe".to_owned()); args.push(initial_url.to_owned());
+		let mut cmd = Command::new(&chrome_bin);
+		cmd.args(&args);
+		let (in_fd, out_fd) = (browser_in.as_raw_fd(), browser_out.as_raw_fd());
+		unsafe
+		{
+			cmd.pre_exec(move ||
+			{
+				// the browser reads from fd 3 and writes to fd 4. move the output end first if it sits on fd 3
+				let out_fd = if out_fd == 3 { libc::dup(out_fd) } else { out_fd };
+				if out_fd < 0 || libc::dup2(in_fd, 3) < 0 || libc::dup2(out_fd, 4) < 0 { return Err(IOError::last_os_error()); }
+				// dup2 onto the same fd does not clear close-on-exec
+				libc::fcntl(3, libc::F_SETFD, 0); libc::fcntl(4, libc::F_SETFD, 0);
+				Ok(())
+			});
+		}
+		println!("[Headless Chrome]Launching {:?}...", cmd);
+		let process = cmd.spawn()?;
+		// the browser owns its ends now
+		drop(browser_in); drop(browser_out);
+		Ok((Process { process, port: None, bin: chrome_bin, args }, PipeTransport::new(to_browser, from_browser)))
+	}
+	fn chrome_bin() -> String
 	{
 		#[cfg(windows)] const CHROME_DEFAULT_BIN: &'static str = r"C:\Program Files (x86)\Google\Chrome\Application\chrome.exe";
 		#[cfg(unix)]    const CHROME_DEFAULT_BIN: &'static str = "google-chrome-stable";
 
-		let chrome_bin = ::std::env::var("CHROME_BIN").unwrap_or_else(|_|
+		::std::env::var("CHROME_BIN").unwrap_or_else(|_|
 		{
 			println!("Warning: $CHROME_BIN is not set, defaulting to \"{}\"", CHROME_DEFAULT_BIN);
 			CHROME_DEFAULT_BIN.into()
-		});
-		let mut cmd = Command::new(&chrome_bin);
-		cmd.args(&["--headless", "--disable-gpu", &format!("--remote-debugging-port={}", port), initial_url]);
+		})
+	}
+	fn debugging_port(&self) -> IOResult<u16>
+	{
+		self.port.ok_or_else(|| IOError::new(IOErrorKind::Other, "The browser has been launched without a debugging port"))
+	}
+	fn spawn(bin: &str, args: &[String], port: u16) -> IOResult<Child>
+	{
+		let mut cmd = Command::new(bin);
+		cmd.args(args);
 		println!("[Headless Chrome]Launching {:?}...", cmd);
 		let process = cmd.spawn()?;
 		Self::wait_port_open(port)?;
-		Ok(Process { process, port })
+		Ok(process)
+	}
+	/// Kills the current process(if still running) and launches a new one with the same arguments
+	pub fn relaunch(&mut self) -> IOResult<()>
+	{
+		// new pipes could not be handed to the existing transport
+		let port = self.debugging_port()?;
+		self.terminate();
+		self.process = Self::spawn(&self.bin, &self.args, port)?;
+		Ok(())
+	}
+	/// Exit status of the process, `None` while running
+	pub fn exit_status(&mut self) -> IOResult<Option<ExitStatus>> { self.process.try_wait() }
+	fn terminate(&mut self)
+	{
+		// the process may have already exited
+		if let Ok(None) = self.process.try_wait() { self.process.kill().ok(); }
+		self.process.wait().ok();
+	}
+	/// `webSocketDebuggerUrl` of the first page target(blocking)
+	pub fn page_target_url(&self) -> GenericResult<String>
+	{
+		let body = self.get_json_sync("/json")?;
+		let sessions: Vec<SessionInfo> = serde_json::from_str(&body)?;
+		sessions.into_iter().find(|s| s._type == "page").and_then(|s| s.web_socket_debugger_url).map(ToOwned::to_owned)
+			.ok_or_else(|| "No page target found in the browser".into())
+	}
+	fn get_json_sync(&self, path: &str) -> IOResult<String>
+	{
+		let mut stream = TcpStream::connect(format!("127.0.0.1:{}", self.debugging_port()?))?;
+		write!(stream, "GET {} HTTP/1.0\r\nHost: localhost\r\n\r\n", path)?;
+		let mut response = String::new(); stream.read_to_string(&mut response)?;
+		Ok(response.find("\r\n\r\n").map(|p| response[p + 4..].to_owned()).unwrap_or_default())
 	}
 	fn wait_port_open(port: u16) -> IOResult<()>
 	{
@@ -711,16 +1942,20 @@ impl Process
 			sleep(Duration::from_millis(100));
 		}
 	}
-	pub fn get_sessions_async<C: Connect>(&self, client: &Client<C>) -> FutureResponse
+	/// Fails with `hyper::Error::Io` if the browser has been launched without a debugging port
+	pub fn get_sessions_async<C: Connect>(&self, client: &Client<C>) -> AsyncResponse { self.get_json_async(client, "/json") }
+	/// Fails with `hyper::Error::Io` if the browser has been launched without a debugging port
+	pub fn get_version_async<C: Connect>(&self, client: &Client<C>) -> AsyncResponse { self.get_json_async(client, "/json/version") }
+	fn get_json_async<C: Connect>(&self, client: &Client<C>, path: &str) -> AsyncResponse
 	{
-		client.get(format!("http://localhost:{}/json", self.port).parse().expect("Failed to parse URL"))
-	}
-	pub fn get_version_async<C: Connect>(&self, client: &Client<C>) -> FutureResponse
-	{
-		client.get(format!("http://localhost:{}/json/version", self.port).parse().expect("Failed to parse URL"))
+		match self.debugging_port()
+		{
+			Ok(port) => Box::new(client.get(format!("http://localhost:{}{}", port, path).parse().expect("Failed to parse URL"))),
+			Err(e) => Box::new(future::err(HyperError::Io(e)))
+		}
 	}
 }
 impl Drop for Process
 {
-	fn drop(&mut self) { self.process.kill().expect("Failed to kill the Headless Chrome"); }
+	fn drop(&mut self) { self.terminate(); }
 }
\ No newline at end of file
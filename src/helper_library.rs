@@ -0,0 +1,229 @@
+//! Helper functions shared by the scraping scripts, installed once per document under a namespaced global
+
+use std::fmt::{Display, Result as FmtResult, Formatter};
+use std::marker::PhantomData;
+use jsquery as jsq;
+use offline::{self, Environment};
+use GenericResult;
+
+/// Global object holding the helpers
+pub const NAMESPACE: &'static str = "dcWeb";
+/// Bump when the helpers change: documents holding an older copy are detected as stale and reinstalled
+pub const VERSION: u32 = 3;
+/// Message thrown by the guard when the library is missing or stale
+pub const MISSING_MARKER: &'static str = "dcWeb: helper library is not installed";
+
+/// Script defining the library(for `Page.addScriptToEvaluateOnNewDocument` and direct installation)
+pub fn script() -> String
+{
+	format!(r#"(function() {{
+		var lib = {{
+			version: {version},
+			translateNotificationState: function(s) {{
+				switch(s) {{
+				case "未読": return "Unread"; case "既読": return "Read";
+				case "未回答": return "Unanswered"; case "回答済": return "Answered";
+				case "未提出": return "Unsubmitted"; case "提出済": return "Submitted";
+				default: console.assert(0);
+				}}
+			}},
+			toPeriod: function(s) {{
+				switch(s) {{
+				case "1 Q": return "FirstQuarter"; case "2 Q": return "SecondQuarter";
+				case "3 Q": return "ThirdQuarter";  case "4 Q": return "FourthQuarter";
+				case "前期": return "FirstStage"; case "後期": return "LateStage";
+				case "通年": return "WholeYear"; default: console.assert(false);
+				}}
+			}},
+			toWeekName: function(s) {{
+				switch(s) {{
+				case "月曜日": return "Monday"; case "火曜日": return "Tuesday"; case "水曜日": return "Wednesday";
+				case "木曜日": return "Thursday"; case "金曜日": return "Friday"; case "土曜日": return "Saturday";
+				default: console.assert(false);
+				}}
+			}},
+			// 全角数字を半角に
+			toHalfWidthDigits: function(s) {{ return s.replace(/[０-９]/g, x => String.fromCharCode(x.charCodeAt(0) - 65248)); }},
+			// 出欠セル("mm/dd 出席"など)を[月, 日, 状態]に
+			toAttendanceState: function(s) {{
+				if(!s) return [0, 0, "NoData"];
+				var date = s.match(/(\d+)\/(\d+)/);
+				var state = s.includes("公認欠席") ? "Authorized" : s.includes("欠席") ? "Absence" : s.includes("出席") ? "Presence" : "NoData";
+				// 日付のないセルは0月0日扱い
+				return date ? [parseInt(date[1]), parseInt(date[2]), state] : [0, 0, state];
+			}}
+		}};
+		// configurable so that a newer version can replace it
+		Object.defineProperty(window, "{ns}", {{ value: Object.freeze(lib), configurable: true, writable: false }});
+	}})();"#, version = VERSION, ns = NAMESPACE)
+}
+/// Statement throwing `MISSING_MARKER` unless the current version of the library is installed
+pub fn guard() -> String
+{
+	format!(r#"if(typeof {ns} === "undefined" || {ns}.version !== {version}) throw new Error({marker});"#,
+		ns = NAMESPACE, version = VERSION, marker = jsq::Literal(MISSING_MARKER))
+}
+
+/// Typed call of a helper(`dcWeb.<name>(arg)`)
+pub struct HelperCall<ArgTy: jsq::QueryCombinator, T>(&'static str, ArgTy, PhantomData<T>);
+impl<ArgTy: jsq::QueryCombinator, T> jsq::QueryCombinator for HelperCall<ArgTy, T> { type ValueTy = T; }
+impl<ArgTy: jsq::QueryCombinator, T> Display for HelperCall<ArgTy, T>
+{
+	fn fmt(&self, fmt: &mut Formatter) -> FmtResult { write!(fmt, "{}.{}({})", NAMESPACE, self.0, self.1) }
+}
+impl<ArgTy: jsq::QueryCombinator, T> jsq::Evaluate for HelperCall<ArgTy, T>
+{
+	fn evaluate(&self, env: &mut Environment) -> GenericResult<offline::Value>
+	{
+		let arg = self.1.evaluate(env)?;
+		native(self.0, arg)
+	}
+}
+/// `translateNotificationState`: "未読"などを`NotificationState`の名前に
+pub fn translate_notification_state<ArgTy>(arg: ArgTy) -> HelperCall<ArgTy, jsq::types::String>
+	where ArgTy: jsq::QueryCombinator<ValueTy = jsq::types::String>
+{
+	HelperCall("translateNotificationState", arg, PhantomData)
+}
+/// `toPeriod`: "1 Q"/"前期"などを`Period`の名前に
+pub fn to_period<ArgTy>(arg: ArgTy) -> HelperCall<ArgTy, jsq::types::String>
+	where ArgTy: jsq::QueryCombinator<ValueTy = jsq::types::String>
+{
+	HelperCall("toPeriod", arg, PhantomData)
+}
+/// `toWeekName`: "月曜日"などを`Week`の名前に
+pub fn to_week_name<ArgTy>(arg: ArgTy) -> HelperCall<ArgTy, jsq::types::String>
+	where ArgTy: jsq::QueryCombinator<ValueTy = jsq::types::String>
+{
+	HelperCall("toWeekName", arg, PhantomData)
+}
+/// `toHalfWidthDigits`: 全角数字を半角に
+pub fn to_half_width_digits<ArgTy>(arg: ArgTy) -> HelperCall<ArgTy, jsq::types::String>
+	where ArgTy: jsq::QueryCombinator<ValueTy = jsq::types::String>
+{
+	HelperCall("toHalfWidthDigits", arg, PhantomData)
+}
+/// `toAttendanceState`: 出欠セルを`(月, 日, AttendanceState)`に
+pub fn to_attendance_state<ArgTy>(arg: ArgTy) -> HelperCall<ArgTy, jsq::types::Tuple<(jsq::types::Number, jsq::types::Number, jsq::types::String)>>
+	where ArgTy: jsq::QueryCombinator<ValueTy = jsq::types::String>
+{
+	HelperCall("toAttendanceState", arg, PhantomData)
+}
+
+/// Rust port of the helpers(for the offline evaluation). `console.assert` failures are undefined(null)
+fn native(name: &str, arg: offline::Value) -> GenericResult<offline::Value>
+{
+	let s = match arg
+	{
+		offline::Value::String(s) => s,
+		// switch falls to the default, and `!s` holds
+		offline::Value::Null if name != "toHalfWidthDigits" => String::new(),
+		v => return Err(offline::type_error(name, &v))
+	};
+	let translated = match name
+	{
+		"translateNotificationState" => match &s as &str
+		{
+			"未読" => "Unread", "既読" => "Read", "未回答" => "Unanswered", "回答済" => "Answered",
+			"未提出" => "Unsubmitted", "提出済" => "Submitted", _ => return Ok(offline::Value::Null)
+		},
+		"toPeriod" => match &s as &str
+		{
+			"1 Q" => "FirstQuarter", "2 Q" => "SecondQuarter", "3 Q" => "ThirdQuarter", "4 Q" => "FourthQuarter",
+			"前期" => "FirstStage", "後期" => "LateStage", "通年" => "WholeYear", _ => return Ok(offline::Value::Null)
+		},
+		"toWeekName" => match &s as &str
+		{
+			"月曜日" => "Monday", "火曜日" => "Tuesday", "水曜日" => "Wednesday",
+			"木曜日" => "Thursday", "金曜日" => "Friday", "土曜日" => "Saturday", _ => return Ok(offline::Value::Null)
+		},
+		"toHalfWidthDigits" => return Ok(offline::Value::String(s.chars()
+			.map(|c| if c >= '０' && c <= '９' { ::std::char::from_u32(c as u32 - 65248).unwrap() } else { c }).collect())),
+		"toAttendanceState" =>
+		{
+			if s.is_empty() { return Ok(attendance_state(0.0, 0.0, "NoData")); }
+			let date = ::regex::Regex::new(r"([0-9]+)/([0-9]+)").unwrap().captures(&s).map(|c| (offline::parse_int(&c[1]), offline::parse_int(&c[2])))
+				.unwrap_or((0.0, 0.0));
+			let state = if s.contains("公認欠席") { "Authorized" } else if s.contains("欠席") { "Absence" }
+				else if s.contains("出席") { "Presence" } else { "NoData" };
+			return Ok(attendance_state(date.0, date.1, state));
+		},
+		_ => return Err(format!("TypeError: {}.{} is not a function", NAMESPACE, name).into())
+	};
+	Ok(offline::Value::String(translated.to_owned()))
+}
+fn attendance_state(month: f64, day: f64, state: &str) -> offline::Value
+{
+	offline::Value::Array(vec![offline::Value::Number(month), offline::Value::Number(day), offline::Value::String(state.to_owned())])
+}
+
+#[cfg(test)]
+mod tests
+{
+	use super::*;
+	use std::process::Command;
+	use serde_json::{self, Value as JValue};
+	use js_syntax::validate;
+
+	/// (helper, argument, result) in JSON: `None` is a thrown exception
+	const CASES: &'static [(&'static str, &'static str, Option<&'static str>)] = &[
+		("translateNotificationState", r#""未読""#, Some(r#""Unread""#)),
+		("translateNotificationState", r#""提出済""#, Some(r#""Submitted""#)),
+		("translateNotificationState", r#""?""#, Some("null")),
+		("translateNotificationState", "null", Some("null")),
+		("toPeriod", r#""3 Q""#, Some(r#""ThirdQuarter""#)),
+		("toPeriod", r#""通年""#, Some(r#""WholeYear""#)),
+		("toPeriod", r#""""#, Some("null")),
+		("toWeekName", r#""土曜日""#, Some(r#""Saturday""#)),
+		("toWeekName", r#""日曜日""#, Some("null")),
+		("toHalfWidthDigits", r#""第１２回 3""#, Some(r#""第12回 3""#)),
+		("toHalfWidthDigits", "null", None),
+		("toAttendanceState", r#""04/09 出席""#, Some(r#"[4, 9, "Presence"]"#)),
+		("toAttendanceState", r#""4/16\n公認欠席""#, Some(r#"[4, 16, "Authorized"]"#)),
+		("toAttendanceState", r#""05/07 欠席""#, Some(r#"[5, 7, "Absence"]"#)),
+		("toAttendanceState", r#""06/11""#, Some(r#"[6, 11, "NoData"]"#)),
+		("toAttendanceState", r#""欠席""#, Some(r#"[0, 0, "Absence"]"#)),
+		("toAttendanceState", r#""-""#, Some(r#"[0, 0, "NoData"]"#)),
+		("toAttendanceState", r#""""#, Some(r#"[0, 0, "NoData"]"#)),
+		("toAttendanceState", "null", Some(r#"[0, 0, "NoData"]"#))
+	];
+	fn json(s: &str) -> JValue { serde_json::from_str(s).unwrap() }
+
+	#[test]
+	fn native_helpers()
+	{
+		for &(name, arg, expected) in CASES
+		{
+			let r = native(name, offline::Value::from_json(json(arg))).ok().map(offline::Value::into_json);
+			assert_eq!(r, expected.map(json), "{}({})", name, arg);
+		}
+	}
+	/// The same cases through the JavaScript library(skipped without node.js)
+	#[test]
+	fn script_helpers()
+	{
+		let calls = CASES.iter().map(|&(name, arg, _)|
+			format!("(function() {{ try {{ return [{}.{}({})]; }} catch(e) {{ return null; }} }})()", NAMESPACE, name, arg))
+			.collect::<Vec<_>>().join(",\n");
+		// `console.assert` of the defaults only reports
+		let program = format!("var window = this; console.assert = function() {{}};\n{}\nconsole.log(JSON.stringify([{}]));", script(), calls);
+		let output = match Command::new("node").arg("-e").arg(&program).output()
+		{
+			Ok(o) => o, Err(_) => { println!("node.js is not available: skipped"); return; }
+		};
+		assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+		let results: Vec<Option<(JValue,)>> = serde_json::from_slice(&output.stdout).unwrap();
+		assert_eq!(results.len(), CASES.len());
+		for (&(name, arg, expected), r) in CASES.iter().zip(results)
+		{
+			assert_eq!(r.map(|(v,)| v), expected.map(json), "{}({})", name, arg);
+		}
+	}
+	#[test]
+	fn scripts()
+	{
+		validate(&script()).unwrap();
+		// as `RemoteCampus::query_value_with_helpers` sends it
+		validate(&format!("{}\n{}", guard(), translate_notification_state(jsq::Literal("未読")))).unwrap();
+	}
+}
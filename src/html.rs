@@ -0,0 +1,244 @@
+//! Lenient HTML parser building a `DomTree`(for saved or downloaded pages)
+//!
+//! Covers what the DigitalCampus pages use: void and raw-text elements, character references, implied end tags
+//! (`p`, `li`, table parts, `option`) and the implied `tbody` of tables. `html`/`head`/`body` are not synthesized
+
+use dom_tree::{DomTree, NodeData};
+
+const VOID_ELEMENTS: &'static [&'static str] =
+	&["area", "base", "br", "col", "embed", "hr", "img", "input", "link", "meta", "param", "source", "track", "wbr"];
+const RAW_TEXT_ELEMENTS: &'static [&'static str] = &["script", "style", "textarea", "title"];
+/// Elements closing an open `p`
+const BLOCK_ELEMENTS: &'static [&'static str] = &[
+	"address", "article", "aside", "blockquote", "div", "dl", "fieldset", "footer", "form", "h1", "h2", "h3", "h4", "h5", "h6",
+	"header", "hr", "main", "nav", "ol", "p", "pre", "section", "table", "ul"
+];
+
+/// Parses a document. Never fails: broken markup is recovered like browsers roughly do
+pub fn parse(url: &str, html: &str) -> DomTree
+{
+	let mut b = TreeBuilder { tree: DomTree::new(url), open: vec![(0, String::new())] };
+	let mut rest = html;
+	while !rest.is_empty()
+	{
+		if rest.starts_with("<!--")
+		{
+			rest = rest[4..].find("-->").map(|e| &rest[4 + e + 3..]).unwrap_or("");
+		}
+		else if rest.starts_with("<!") || rest.starts_with("<?")
+		{
+			// doctype and processing instructions
+			rest = rest.find('>').map(|e| &rest[e + 1..]).unwrap_or("");
+		}
+		else if rest.starts_with("</") && rest[2..].starts_with(|c: char| c.is_ascii_alphabetic())
+		{
+			let end = rest.find('>').unwrap_or(rest.len());
+			let name = rest[2..end].trim().to_ascii_lowercase();
+			b.close(&name);
+			rest = if end < rest.len() { &rest[end + 1..] } else { "" };
+		}
+		else if rest.starts_with('<') && rest[1..].starts_with(|c: char| c.is_ascii_alphabetic())
+		{
+			let (name, attributes, self_closing, after) = start_tag(&rest[1..]);
+			rest = after;
+			if RAW_TEXT_ELEMENTS.contains(&&name[..])
+			{
+				let index = b.open_element(&name, attributes);
+				let close = format!("</{}", name);
+				let end = find_ignore_case(rest, &close).unwrap_or(rest.len());
+				let text = if name == "textarea" || name == "title" { decode_entities(&rest[..end]) } else { rest[..end].to_owned() };
+				if !text.is_empty() { b.tree.append(index, NodeData::text(&text)); }
+				b.close(&name);
+				rest = &rest[end..];
+				rest = rest.find('>').map(|e| &rest[e + 1..]).unwrap_or("");
+			}
+			else
+			{
+				b.open_element(&name, attributes);
+				if self_closing || VOID_ELEMENTS.contains(&&name[..]) { b.open.pop(); }
+			}
+		}
+		else
+		{
+			// a stray '<' is a text
+			let skip = rest.chars().next().map_or(0, char::len_utf8);
+			let end = rest[skip..].find('<').map(|e| e + skip).unwrap_or(rest.len());
+			b.text(&decode_entities(&rest[..end]));
+			rest = &rest[end..];
+		}
+	}
+	b.tree
+}
+
+struct TreeBuilder { tree: DomTree, open: Vec<(usize, String)> }
+impl TreeBuilder
+{
+	fn current(&self) -> &str { &self.open.last().unwrap().1 }
+	fn is_open(&self, name: &str) -> bool { self.open.iter().any(|&(_, ref n)| n == name) }
+	/// Closes the innermost `name` unless one of `boundaries` is nearer
+	fn close_within(&mut self, name: &str, boundaries: &[&str])
+	{
+		for i in (1 .. self.open.len()).rev()
+		{
+			if self.open[i].1 == name { self.open.truncate(i); return; }
+			if boundaries.contains(&&self.open[i].1[..]) { return; }
+		}
+	}
+	fn open_element(&mut self, name: &str, attributes: Vec<(String, String)>) -> usize
+	{
+		match name
+		{
+			"li" => self.close_within("li", &["ul", "ol"]),
+			"dt" | "dd" => { self.close_within("dt", &["dl"]); self.close_within("dd", &["dl"]); },
+			"option" => self.close_within("option", &["select"]),
+			"td" | "th" => { self.close_within("td", &["tr", "table"]); self.close_within("th", &["tr", "table"]); },
+			"tr" => { self.close_within("tr", &["table", "tbody", "thead", "tfoot"]); },
+			"tbody" | "thead" | "tfoot" =>
+			{
+				for n in &["tbody", "thead", "tfoot"] { self.close_within(n, &["table"]); }
+			},
+			_ => ()
+		}
+		if BLOCK_ELEMENTS.contains(&name) && self.is_open("p") { self.close_within("p", &["div", "td", "th", "li", "table"]); }
+		// browsers put rows of a table into an implied tbody
+		if name == "tr" && self.current() == "table" { self.open_element("tbody", Vec::new()); }
+		let parent = self.open.last().unwrap().0;
+		let index = self.tree.append(parent, NodeData::element(name, attributes));
+		self.open.push((index, name.to_owned()));
+		index
+	}
+	fn close(&mut self, name: &str)
+	{
+		// closing a table also closes its implied parts
+		if let Some(i) = self.open.iter().rposition(|&(_, ref n)| n == name)
+		{
+			if i > 0 { self.open.truncate(i); }
+		}
+	}
+	fn text(&mut self, text: &str)
+	{
+		let parent = self.open.last().unwrap().0;
+		self.tree.append(parent, NodeData::text(text));
+	}
+}
+
+/// Parses a start tag after `<`: (name, attributes, self-closing, rest after `>`)
+fn start_tag(s: &str) -> (String, Vec<(String, String)>, bool, &str)
+{
+	let name_end = s.find(|c: char| c.is_whitespace() || c == '>' || c == '/').unwrap_or(s.len());
+	let name = s[..name_end].to_ascii_lowercase();
+	let mut rest = &s[name_end..];
+	let mut attributes: Vec<(String, String)> = Vec::new();
+	let mut self_closing = false;
+	loop
+	{
+		rest = rest.trim_left();
+		if rest.is_empty() { return (name, attributes, self_closing, rest); }
+		if rest.starts_with('>') { return (name, attributes, self_closing, &rest[1..]); }
+		if rest.starts_with('/') { self_closing = true; rest = &rest[1..]; continue; }
+		self_closing = false;
+		let key_end = rest.find(|c: char| c.is_whitespace() || c == '=' || c == '>' || c == '/').unwrap_or(rest.len()).max(1);
+		let key = rest[..key_end].to_ascii_lowercase();
+		rest = rest[key_end..].trim_left();
+		let value = if rest.starts_with('=')
+		{
+			rest = rest[1..].trim_left();
+			if rest.starts_with('"') || rest.starts_with('\'')
+			{
+				let q = &rest[..1];
+				let end = rest[1..].find(q).map(|e| e + 1).unwrap_or(rest.len());
+				let v = decode_entities(&rest[1..end]);
+				rest = if end < rest.len() { &rest[end + 1..] } else { "" };
+				v
+			}
+			else
+			{
+				let end = rest.find(|c: char| c.is_whitespace() || c == '>').unwrap_or(rest.len());
+				let v = decode_entities(&rest[..end]);
+				rest = &rest[end..];
+				v
+			}
+		}
+		else { String::new() };
+		// the first one wins on duplicates
+		if !attributes.iter().any(|&(ref k, _)| *k == key) { attributes.push((key, value)); }
+	}
+}
+
+fn find_ignore_case(haystack: &str, needle: &str) -> Option<usize>
+{
+	let lower = haystack.to_ascii_lowercase();
+	lower.find(&needle.to_ascii_lowercase())
+}
+
+/// Decodes character references(numeric and the common named ones). Unknown references are left as is
+pub fn decode_entities(s: &str) -> String
+{
+	if !s.contains('&') { return s.to_owned(); }
+	let mut out = String::with_capacity(s.len());
+	let mut rest = s;
+	while let Some(p) = rest.find('&')
+	{
+		out.push_str(&rest[..p]);
+		rest = &rest[p..];
+		let end = match rest.find(';') { Some(e) if e <= 10 => e, _ => { out.push('&'); rest = &rest[1..]; continue; } };
+		let name = &rest[1..end];
+		let c = if name.starts_with("#x") || name.starts_with("#X") { u32::from_str_radix(&name[2..], 16).ok().and_then(::std::char::from_u32) }
+			else if name.starts_with('#') { name[1..].parse().ok().and_then(::std::char::from_u32) }
+			else
+			{
+				match name
+				{
+					"amp" => Some('&'), "lt" => Some('<'), "gt" => Some('>'), "quot" => Some('"'), "apos" => Some('\''),
+					"nbsp" => Some('\u{a0}'), "copy" => Some('©'), "reg" => Some('®'), "yen" => Some('¥'), "times" => Some('×'),
+					_ => None
+				}
+			};
+		match c
+		{
+			Some(c) => { out.push(c); rest = &rest[end + 1..]; },
+			None => { out.push('&'); rest = &rest[1..]; }
+		}
+	}
+	out.push_str(rest);
+	out
+}
+
+#[cfg(test)]
+mod tests
+{
+	use super::*;
+	use dom_tree::DomNode;
+
+	fn texts(v: &[DomNode]) -> Vec<String> { v.iter().map(DomNode::text).collect() }
+
+	#[test]
+	fn elements_and_attributes()
+	{
+		let t = parse("about:blank", r#"<!DOCTYPE html><div id=main class="a b"><p>x<br>y<img src='i.png'/></div><!-- <p>no</p> -->"#);
+		let main = t.select_first("#main.b").unwrap().unwrap();
+		assert_eq!(main.attribute("class"), Some("a b"));
+		assert_eq!(main.text_content(), "xy");
+		assert_eq!(t.select("p > img[src=\"i.png\"]").unwrap().len(), 1);
+		assert!(t.select("p p").unwrap().is_empty());
+	}
+	#[test]
+	fn implied_end_tags()
+	{
+		let t = parse("about:blank", "<ul><li>a<li>b</ul><p>1<p>2<div>3</div><table><tr><td>x<td>y<tr><td>z</table>");
+		assert_eq!(texts(&t.select("li").unwrap()), vec!["a", "b"]);
+		assert_eq!(texts(&t.select("p").unwrap()), vec!["1", "2"]);
+		assert_eq!(t.select("table > tbody > tr").unwrap().len(), 2);
+		assert_eq!(texts(&t.select("tr:nth-child(1) td:nth-child(2)").unwrap()), vec!["y"]);
+		assert_eq!(texts(&t.select("tr:not(:first-child) td").unwrap()), vec!["z"]);
+	}
+	#[test]
+	fn raw_text_and_references()
+	{
+		let t = parse("about:blank", r#"<script>if(a < b && c) { x("</div>"); }</script><a onclick="javascript:go(&quot;1&quot;)">&lt;&#x41;&#66;&nbsp;&unknown; & ok</a>"#);
+		assert_eq!(t.select_first("script").unwrap().unwrap().text_content(), r#"if(a < b && c) { x("</div>"); }"#);
+		let a = t.select_first("a").unwrap().unwrap();
+		assert_eq!(a.attribute("onclick"), Some(r#"javascript:go("1")"#));
+		assert_eq!(a.text_content(), "<AB\u{a0}&unknown; & ok");
+	}
+}
@@ -0,0 +1,859 @@
+//! JavaScript syntax validation(for the generated scripts, without a browser)
+//!
+//! Parses ES2017 scripts as far as the generated code and the templates use them: statements, functions, arrows,
+//! object/array/regular expression literals and all the operators. Destructuring, classes, generators and async functions
+//! are reported as errors, and template literals are only checked to be terminated
+//!
+//! A slash after `}` is read as a regular expression when the brace closes a block and as a division when it closes
+//! an object literal. Function expressions count as blocks, so `function() {} / 2` is rejected
+
+use std::fmt::{Display, Result as FmtResult, Formatter};
+use std::error::Error;
+use jsquery::{QueryCombinator, Executor};
+use jsquery::types::Decode;
+use GenericResult;
+
+/// Syntax error with its position and the fragment of the source around it
+#[derive(Debug, Clone, PartialEq)]
+pub struct SyntaxError
+{
+	pub message: String,
+	/// 1-based position
+	pub line: usize, pub column: usize,
+	/// The line containing the error(clipped around the position)
+	pub fragment: String,
+	/// Position of the error in `fragment`(in chars)
+	pub fragment_column: usize
+}
+impl Display for SyntaxError
+{
+	fn fmt(&self, fmt: &mut Formatter) -> FmtResult
+	{
+		write!(fmt, "SyntaxError: {} at line {}, column {}\n    {}\n    {}^", self.message, self.line, self.column,
+			self.fragment, " ".repeat(self.fragment_column))
+	}
+}
+impl Error for SyntaxError { fn description(&self) -> &str { &self.message } }
+
+/// Parses the script and reports the first syntax error
+pub fn validate(source: &str) -> Result<(), SyntaxError>
+{
+	let tokens = tokenize(source)?;
+	Parser { source, tokens, pos: 0, no_in: false }.program()
+}
+/// Validates the script of the query
+pub fn validate_query<Q: QueryCombinator>(query: &Q) -> Result<(), SyntaxError> { validate(&query.to_string()) }
+
+/// Executor validating every script before running it on the inner one(e.g. `Validated(offline::StaticPage)` in tests)
+pub struct Validated<E: Executor>(pub E);
+impl<E: Executor> Executor for Validated<E>
+{
+	fn execute<Q: QueryCombinator, T>(&mut self, context: Option<u64>, query: Q) -> GenericResult<T> where Q::ValueTy: Decode<T>
+	{
+		validate_query(&query)?;
+		self.0.execute(context, query)
+	}
+}
+
+const CONTEXT_CHARS: usize = 40;
+fn error(source: &str, offset: usize, message: &str) -> SyntaxError
+{
+	let line_start = source[..offset].rfind('\n').map(|i| i + 1).unwrap_or(0);
+	let line_end = source[offset..].find('\n').map(|i| offset + i).unwrap_or(source.len());
+	let before: Vec<char> = source[line_start..offset].chars().map(|c| if c == '\t' { ' ' } else { c }).collect();
+	let skip = before.len().saturating_sub(CONTEXT_CHARS);
+	let after = source[offset..line_end].trim_right_matches('\r');
+	let mut fragment = if skip > 0 { "...".to_owned() } else { String::new() };
+	let fragment_column = fragment.len() + before.len() - skip;
+	fragment.extend(before[skip..].iter());
+	fragment.extend(after.chars().take(CONTEXT_CHARS).map(|c| if c == '\t' { ' ' } else { c }));
+	if after.chars().count() > CONTEXT_CHARS { fragment.push_str("..."); }
+	SyntaxError
+	{
+		message: message.to_owned(), line: source[..offset].matches('\n').count() + 1, column: before.len() + 1,
+		fragment, fragment_column
+	}
+}
+
+// tokenizer //
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum TokenKind { Name, Number, String, Template, Regex, Punct, End }
+#[derive(Clone, Copy, Debug)]
+struct Token<'s> { kind: TokenKind, text: &'s str, offset: usize, newline_before: bool }
+
+const PUNCTUATORS: &'static [&'static str] = &[
+	">>>=", "...", "===", "!==", "**=", "<<=", ">>=", ">>>",
+	"=>", "==", "!=", "<=", ">=", "&&", "||", "??", "?.", "++", "--", "+=", "-=", "*=", "/=", "%=", "&=", "|=", "^=", "<<", ">>", "**",
+	"{", "}", "(", ")", "[", "]", ";", ",", "<", ">", "+", "-", "*", "/", "%", "&", "|", "^", "!", "~", "?", ":", "=", "."
+];
+const ASSIGNMENT_OPERATORS: &'static [&'static str] = &["=", "+=", "-=", "*=", "/=", "%=", "**=", "<<=", ">>=", ">>>=", "&=", "|=", "^="];
+const RESERVED_WORDS: &'static [&'static str] = &[
+	"break", "case", "catch", "class", "const", "continue", "debugger", "default", "delete", "do", "else", "export", "extends",
+	"finally", "for", "function", "if", "import", "in", "instanceof", "new", "return", "super", "switch", "this", "throw", "try",
+	"typeof", "var", "void", "while", "with", "null", "true", "false"
+];
+fn is_reserved(name: &str) -> bool { RESERVED_WORDS.contains(&name) }
+fn is_line_terminator(c: char) -> bool { c == '\n' || c == '\r' || c == '\u{2028}' || c == '\u{2029}' }
+fn is_id_start(c: char) -> bool { c == '$' || c == '_' || c.is_alphabetic() }
+fn is_id_part(c: char) -> bool { is_id_start(c) || c.is_numeric() || c == '\u{200c}' || c == '\u{200d}' }
+/// Length of the leading chars satisfying `pred`(in bytes)
+fn span<F: Fn(char) -> bool>(s: &str, pred: F) -> usize { s.find(|c| !pred(c)).unwrap_or(s.len()) }
+
+fn tokenize(source: &str) -> Result<Vec<Token>, SyntaxError>
+{
+	let mut tokens: Vec<Token> = Vec::new();
+	let (mut pos, mut newline) = (0, false);
+	// whether each open brace started an object literal, and whether the last token closed a block
+	let (mut braces, mut closed_block) = (Vec::new(), false);
+	while pos < source.len()
+	{
+		let rest = &source[pos..];
+		let c = rest.chars().next().unwrap();
+		if is_line_terminator(c) { newline = true; pos += c.len_utf8(); continue; }
+		if c.is_whitespace() || c == '\u{feff}' { pos += c.len_utf8(); continue; }
+		if rest.starts_with("//") { pos += span(rest, |c| !is_line_terminator(c)); continue; }
+		if rest.starts_with("/*")
+		{
+			let end = rest[2..].find("*/").ok_or_else(|| error(source, pos, "Invalid or unexpected token"))?;
+			if rest[2 .. 2 + end].contains(is_line_terminator) { newline = true; }
+			pos += 2 + end + 2; continue;
+		}
+		let (kind, len) = if is_id_start(c) { (TokenKind::Name, span(rest, is_id_part)) }
+			else if c.is_ascii_digit() || (c == '.' && rest[1..].starts_with(|d: char| d.is_ascii_digit())) { (TokenKind::Number, number_length(source, pos)?) }
+			else if c == '"' || c == '\'' { (TokenKind::String, string_length(source, pos)?) }
+			else if c == '`' { (TokenKind::Template, template_length(source, pos)?) }
+			else if c == '/' && (closed_block || regex_allowed(tokens.last())) { (TokenKind::Regex, regex_length(source, pos)?) }
+			else
+			{
+				match PUNCTUATORS.iter().find(|p| rest.starts_with(*p))
+				{
+					Some(p) => (TokenKind::Punct, p.len()),
+					None => return Err(error(source, pos, "Invalid or unexpected token"))
+				}
+			};
+		let token = Token { kind, text: &source[pos .. pos + len], offset: pos, newline_before: newline };
+		closed_block = false;
+		if kind == TokenKind::Punct && token.text == "{" { braces.push(opens_object_literal(tokens.last())); }
+		if kind == TokenKind::Punct && token.text == "}" { closed_block = !braces.pop().unwrap_or(false); }
+		tokens.push(token);
+		pos += len; newline = false;
+	}
+	tokens.push(Token { kind: TokenKind::End, text: "", offset: source.len(), newline_before: true });
+	Ok(tokens)
+}
+/// A slash starts a regular expression unless it follows an operand(a closing brace is an operand here: see `tokenize`)
+fn regex_allowed(last: Option<&Token>) -> bool
+{
+	match last
+	{
+		None => true,
+		Some(t) => match t.kind
+		{
+			TokenKind::Name => ["return", "typeof", "instanceof", "in", "of", "new", "delete", "void", "throw", "case", "do", "else"].contains(&t.text),
+			TokenKind::Punct => !(t.text == ")" || t.text == "]" || t.text == "}"),
+			_ => false
+		}
+	}
+}
+/// Whether a `{` after `last` starts an object literal rather than a block(a `{` after `:` is taken as an object)
+fn opens_object_literal(last: Option<&Token>) -> bool
+{
+	match last
+	{
+		None => false,
+		Some(t) => match t.kind
+		{
+			TokenKind::Name => ["return", "typeof", "instanceof", "in", "of", "new", "delete", "void", "throw", "case"].contains(&t.text),
+			TokenKind::Punct => !["{", "}", ")", "]", ";", "=>"].contains(&t.text),
+			_ => false
+		}
+	}
+}
+fn number_length(source: &str, pos: usize) -> Result<usize, SyntaxError>
+{
+	let rest = &source[pos..];
+	let radix = if rest.starts_with('0')
+	{
+		match rest[1..].chars().next() { Some('x') | Some('X') => 16, Some('b') | Some('B') => 2, Some('o') | Some('O') => 8, _ => 10 }
+	}
+	else { 10 };
+	let len = if radix != 10
+	{
+		let digits = span(&rest[2..], |c| c.is_digit(radix));
+		if digits == 0 { return Err(error(source, pos, "Invalid or unexpected token")); }
+		2 + digits
+	}
+	else
+	{
+		let mut len = span(rest, |c| c.is_ascii_digit());
+		if rest[len..].starts_with('.') { len += 1 + span(&rest[len + 1..], |c| c.is_ascii_digit()); }
+		if rest[len..].starts_with(|c| c == 'e' || c == 'E')
+		{
+			let sign = if rest[len + 1..].starts_with(|c| c == '+' || c == '-') { 1 } else { 0 };
+			let digits = span(&rest[len + 1 + sign..], |c| c.is_ascii_digit());
+			if digits == 0 { return Err(error(source, pos, "Invalid or unexpected token")); }
+			len += 1 + sign + digits;
+		}
+		len
+	};
+	// `3in` and `1.2.3` are errors
+	if rest[len..].starts_with(|c: char| is_id_start(c) || c.is_ascii_digit()) { return Err(error(source, pos + len, "Invalid or unexpected token")); }
+	Ok(len)
+}
+fn string_length(source: &str, pos: usize) -> Result<usize, SyntaxError>
+{
+	let rest = &source[pos..];
+	let quote = rest.chars().next().unwrap();
+	let mut chars = rest.char_indices().skip(1).peekable();
+	while let Some((i, c)) = chars.next()
+	{
+		if c == '\\'
+		{
+			// a line continuation of CR LF is a unit
+			if let Some((_, '\r')) = chars.next() { if let Some(&(_, '\n')) = chars.peek() { chars.next(); } }
+		}
+		else if c == quote { return Ok(i + 1); }
+		else if c == '\n' || c == '\r' { break; }
+	}
+	Err(error(source, pos, "Invalid or unexpected token"))
+}
+fn template_length(source: &str, pos: usize) -> Result<usize, SyntaxError>
+{
+	let rest = &source[pos..];
+	let mut i = 1;
+	while i < rest.len()
+	{
+		let c = rest[i..].chars().next().unwrap();
+		if c == '\\' { i += 1 + rest[i + 1..].chars().next().map_or(0, char::len_utf8); continue; }
+		if c == '`' { return Ok(i + 1); }
+		if rest[i..].starts_with("${")
+		{
+			// skips the substitution(nested strings and templates included)
+			let (mut depth, mut j) = (1, i + 2);
+			while depth > 0 && j < rest.len()
+			{
+				let d = rest[j..].chars().next().unwrap();
+				j += match d
+				{
+					'"' | '\'' => string_length(source, pos + j)?,
+					'`' => template_length(source, pos + j)?,
+					'{' => { depth += 1; 1 },
+					'}' => { depth -= 1; 1 },
+					_ => d.len_utf8()
+				};
+			}
+			i = j; continue;
+		}
+		i += c.len_utf8();
+	}
+	Err(error(source, pos, "Unterminated template literal"))
+}
+fn regex_length(source: &str, pos: usize) -> Result<usize, SyntaxError>
+{
+	let rest = &source[pos..];
+	let (mut chars, mut in_class) = (rest.char_indices().skip(1), false);
+	while let Some((i, c)) = chars.next()
+	{
+		match c
+		{
+			'\\' => { chars.next(); },
+			'[' => in_class = true,
+			']' => in_class = false,
+			'/' if !in_class =>
+			{
+				let flags = &rest[i + 1 .. i + 1 + span(&rest[i + 1..], is_id_part)];
+				let mut seen = String::new();
+				for f in flags.chars()
+				{
+					if !"gimsuy".contains(f) || seen.contains(f) { return Err(error(source, pos, "Invalid regular expression flags")); }
+					seen.push(f);
+				}
+				return Ok(i + 1 + flags.len());
+			},
+			c if is_line_terminator(c) => break,
+			_ => ()
+		}
+	}
+	Err(error(source, pos, "Invalid regular expression: missing /"))
+}
+
+// parser //
+/// Whether an expression can be assigned to
+#[derive(Clone, Copy, PartialEq)]
+enum Expr { Target, Other }
+type PResult<T> = Result<T, SyntaxError>;
+
+struct Parser<'s> { source: &'s str, tokens: Vec<Token<'s>>, pos: usize, no_in: bool }
+impl<'s> Parser<'s>
+{
+	fn peek(&self) -> Token<'s> { self.tokens[self.pos] }
+	fn peek_at(&self, n: usize) -> Token<'s> { self.tokens[(self.pos + n).min(self.tokens.len() - 1)] }
+	fn next(&mut self) -> Token<'s>
+	{
+		let t = self.peek();
+		if t.kind != TokenKind::End { self.pos += 1; }
+		t
+	}
+	fn is(&self, text: &str) -> bool { Self::token_is(self.peek(), text) }
+	fn token_is(t: Token, text: &str) -> bool { (t.kind == TokenKind::Punct || t.kind == TokenKind::Name) && t.text == text }
+	fn eat(&mut self, text: &str) -> bool { if self.is(text) { self.next(); true } else { false } }
+	fn expect(&mut self, text: &str) -> PResult<()> { if self.eat(text) { Ok(()) } else { Err(self.unexpected()) } }
+	fn unexpected(&self) -> SyntaxError { self.unexpected_token(self.peek()) }
+	fn unexpected_token(&self, t: Token) -> SyntaxError
+	{
+		let message = match t.kind
+		{
+			TokenKind::End => "Unexpected end of input".to_owned(),
+			TokenKind::Number => "Unexpected number".to_owned(),
+			TokenKind::String => "Unexpected string".to_owned(),
+			TokenKind::Template => "Unexpected template string".to_owned(),
+			TokenKind::Regex => "Unexpected regular expression".to_owned(),
+			TokenKind::Name if !is_reserved(t.text) => "Unexpected identifier".to_owned(),
+			_ => format!("Unexpected token '{}'", t.text)
+		};
+		error(self.source, t.offset, &message)
+	}
+	/// Semicolon or its automatic insertion(before `}`, a line break or the end)
+	fn consume_semicolon(&mut self) -> PResult<()>
+	{
+		if self.eat(";") { return Ok(()); }
+		let t = self.peek();
+		if t.newline_before || Self::token_is(t, "}") { Ok(()) } else { Err(self.unexpected()) }
+	}
+	/// Runs `f` with the `in` operator allowed(inside brackets)
+	fn allowing_in<T, F: FnOnce(&mut Self) -> PResult<T>>(&mut self, f: F) -> PResult<T>
+	{
+		let saved = ::std::mem::replace(&mut self.no_in, false);
+		let r = f(self);
+		self.no_in = saved;
+		r
+	}
+
+	// statements //
+	fn program(&mut self) -> PResult<()>
+	{
+		while self.peek().kind != TokenKind::End { self.statement()?; }
+		Ok(())
+	}
+	fn statement(&mut self) -> PResult<()>
+	{
+		let t = self.peek();
+		if Self::token_is(t, "{") { return self.block(); }
+		if Self::token_is(t, ";") { self.next(); return Ok(()); }
+		if t.kind == TokenKind::Name
+		{
+			match t.text
+			{
+				"var" | "const" => { self.next(); self.declarations(t.text == "const")?; return self.consume_semicolon(); },
+				"let" if { let n = self.peek_at(1); (n.kind == TokenKind::Name && !is_reserved(n.text)) || Self::token_is(n, "[") || Self::token_is(n, "{") } =>
+				{
+					self.next(); self.declarations(false)?; return self.consume_semicolon();
+				},
+				"function" => { self.next(); self.binding_identifier()?; return self.function_rest(); },
+				"if" =>
+				{
+					self.next(); self.paren_expression()?; self.statement()?;
+					if self.eat("else") { self.statement()?; }
+					return Ok(());
+				},
+				"for" => return self.for_statement(),
+				"while" => { self.next(); self.paren_expression()?; return self.statement(); },
+				"do" =>
+				{
+					self.next(); self.statement()?; self.expect("while")?; self.paren_expression()?;
+					self.eat(";"); return Ok(());
+				},
+				"return" | "throw" =>
+				{
+					self.next();
+					let n = self.peek();
+					if t.text == "throw" && n.newline_before { return Err(error(self.source, n.offset, "Illegal newline after throw")); }
+					if !(n.newline_before || Self::token_is(n, ";") || Self::token_is(n, "}")) { self.expression()?; }
+					return self.consume_semicolon();
+				},
+				"break" | "continue" =>
+				{
+					self.next();
+					let n = self.peek();
+					if n.kind == TokenKind::Name && !n.newline_before && !is_reserved(n.text) { self.next(); }
+					return self.consume_semicolon();
+				},
+				"switch" => return self.switch_statement(),
+				"try" =>
+				{
+					self.next(); self.block()?;
+					let mut handled = false;
+					if self.eat("catch")
+					{
+						if self.eat("(") { self.binding_identifier()?; self.expect(")")?; }
+						self.block()?; handled = true;
+					}
+					if self.eat("finally") { self.block()?; handled = true; }
+					return if handled { Ok(()) } else { Err(error(self.source, self.peek().offset, "Missing catch or finally after try")) };
+				},
+				"debugger" => { self.next(); return self.consume_semicolon(); },
+				// labeled statement
+				name if !is_reserved(name) && Self::token_is(self.peek_at(1), ":") => { self.next(); self.next(); return self.statement(); },
+				_ => ()
+			}
+		}
+		self.expression()?;
+		self.consume_semicolon()
+	}
+	fn block(&mut self) -> PResult<()>
+	{
+		self.expect("{")?;
+		while !self.eat("}")
+		{
+			if self.peek().kind == TokenKind::End { return Err(self.unexpected()); }
+			self.statement()?;
+		}
+		Ok(())
+	}
+	fn paren_expression(&mut self) -> PResult<()>
+	{
+		self.expect("(")?;
+		self.allowing_in(|p| p.expression())?;
+		self.expect(")")
+	}
+	fn binding_identifier(&mut self) -> PResult<()>
+	{
+		let t = self.peek();
+		if t.kind == TokenKind::Name && !is_reserved(t.text) { self.next(); Ok(()) } else { Err(self.unexpected()) }
+	}
+	fn declarations(&mut self, constant: bool) -> PResult<()>
+	{
+		loop
+		{
+			self.binding_identifier()?;
+			if self.eat("=") { self.assignment()?; }
+			else if constant { return Err(error(self.source, self.peek().offset, "Missing initializer in const declaration")); }
+			if !self.eat(",") { return Ok(()); }
+		}
+	}
+	fn for_statement(&mut self) -> PResult<()>
+	{
+		self.next(); self.expect("(")?;
+		let t = self.peek();
+		if ["var", "let", "const"].iter().any(|k| Self::token_is(t, k))
+		{
+			self.next(); self.binding_identifier()?;
+			if self.eat("of") || self.eat("in") { return self.for_in_rest(); }
+			self.no_in = true;
+			let r = (|| -> PResult<()>
+			{
+				if self.eat("=") { self.assignment()?; }
+				while self.eat(",") { self.binding_identifier()?; if self.eat("=") { self.assignment()?; } }
+				Ok(())
+			})();
+			self.no_in = false; r?;
+		}
+		else if !self.is(";")
+		{
+			let start = self.peek();
+			self.no_in = true;
+			let k = self.expression();
+			self.no_in = false;
+			if self.is("of") || self.is("in")
+			{
+				if k? != Expr::Target { return Err(error(self.source, start.offset, "Invalid left-hand side in for-loop")); }
+				self.next(); return self.for_in_rest();
+			}
+			k?;
+		}
+		self.expect(";")?;
+		if !self.is(";") { self.expression()?; }
+		self.expect(";")?;
+		if !self.is(")") { self.expression()?; }
+		self.expect(")")?;
+		self.statement()
+	}
+	fn for_in_rest(&mut self) -> PResult<()>
+	{
+		self.expression()?; self.expect(")")?;
+		self.statement()
+	}
+	fn switch_statement(&mut self) -> PResult<()>
+	{
+		self.next(); self.paren_expression()?; self.expect("{")?;
+		while !self.eat("}")
+		{
+			if self.eat("case") { self.expression()?; }
+			else if !self.eat("default") { return Err(self.unexpected()); }
+			self.expect(":")?;
+			while !(self.is("case") || self.is("default") || self.is("}"))
+			{
+				if self.peek().kind == TokenKind::End { return Err(self.unexpected()); }
+				self.statement()?;
+			}
+		}
+		Ok(())
+	}
+	/// Parameters and the body of a function
+	fn function_rest(&mut self) -> PResult<()>
+	{
+		self.parameters()?;
+		self.function_body()
+	}
+	fn parameters(&mut self) -> PResult<()>
+	{
+		self.expect("(")?;
+		while !self.eat(")")
+		{
+			let rest = self.eat("...");
+			self.binding_identifier()?;
+			if !rest && self.eat("=") { self.allowing_in(|p| p.assignment())?; }
+			if !self.eat(",") { return self.expect(")"); }
+		}
+		Ok(())
+	}
+	fn function_body(&mut self) -> PResult<()>
+	{
+		let saved = ::std::mem::replace(&mut self.no_in, false);
+		let r = self.block();
+		self.no_in = saved;
+		r
+	}
+
+	// expressions //
+	fn expression(&mut self) -> PResult<Expr>
+	{
+		let mut k = self.assignment()?;
+		while self.eat(",") { self.assignment()?; k = Expr::Other; }
+		Ok(k)
+	}
+	fn assignment(&mut self) -> PResult<Expr>
+	{
+		if self.arrow_ahead() { return self.arrow_function().map(|_| Expr::Other); }
+		let start = self.peek();
+		let k = self.conditional()?;
+		let op = self.peek();
+		if op.kind == TokenKind::Punct && ASSIGNMENT_OPERATORS.contains(&op.text)
+		{
+			if k != Expr::Target { return Err(error(self.source, start.offset, "Invalid left-hand side in assignment")); }
+			self.next(); self.assignment()?;
+			return Ok(Expr::Other);
+		}
+		Ok(k)
+	}
+	/// `x =>` or `(...) =>`
+	fn arrow_ahead(&self) -> bool
+	{
+		let t = self.peek();
+		if t.kind == TokenKind::Name && !is_reserved(t.text)
+		{
+			let n = self.peek_at(1);
+			return Self::token_is(n, "=>") && !n.newline_before;
+		}
+		if !Self::token_is(t, "(") { return false; }
+		let mut depth = 0;
+		for (i, t) in self.tokens[self.pos..].iter().enumerate()
+		{
+			if t.kind != TokenKind::Punct { continue; }
+			match t.text
+			{
+				"(" | "[" | "{" => depth += 1,
+				")" | "]" | "}" =>
+				{
+					depth -= 1;
+					if depth == 0 { return Self::token_is(self.peek_at(i + 1), "=>"); }
+				},
+				_ => ()
+			}
+		}
+		false
+	}
+	fn arrow_function(&mut self) -> PResult<()>
+	{
+		if self.is("(") { self.parameters()?; } else { self.next(); }
+		if self.peek().newline_before { return Err(self.unexpected()); }
+		self.expect("=>")?;
+		if self.is("{") { self.function_body() } else { self.assignment().map(|_| ()) }
+	}
+	fn conditional(&mut self) -> PResult<Expr>
+	{
+		let k = self.binary(0)?;
+		if !self.eat("?") { return Ok(k); }
+		self.allowing_in(|p| p.assignment())?;
+		self.expect(":")?;
+		self.assignment()?;
+		Ok(Expr::Other)
+	}
+	fn binary_precedence(&self, t: Token) -> Option<u32>
+	{
+		match (t.kind, t.text)
+		{
+			(TokenKind::Punct, "??") | (TokenKind::Punct, "||") => Some(1),
+			(TokenKind::Punct, "&&") => Some(2),
+			(TokenKind::Punct, "|") => Some(3),
+			(TokenKind::Punct, "^") => Some(4),
+			(TokenKind::Punct, "&") => Some(5),
+			(TokenKind::Punct, "==") | (TokenKind::Punct, "!=") | (TokenKind::Punct, "===") | (TokenKind::Punct, "!==") => Some(6),
+			(TokenKind::Punct, "<") | (TokenKind::Punct, ">") | (TokenKind::Punct, "<=") | (TokenKind::Punct, ">=") |
+				(TokenKind::Name, "instanceof") => Some(7),
+			(TokenKind::Name, "in") if !self.no_in => Some(7),
+			(TokenKind::Punct, "<<") | (TokenKind::Punct, ">>") | (TokenKind::Punct, ">>>") => Some(8),
+			(TokenKind::Punct, "+") | (TokenKind::Punct, "-") => Some(9),
+			(TokenKind::Punct, "*") | (TokenKind::Punct, "/") | (TokenKind::Punct, "%") => Some(10),
+			(TokenKind::Punct, "**") => Some(11),
+			_ => None
+		}
+	}
+	/// Binary operators binding tighter than `min_precedence`
+	fn binary(&mut self, min_precedence: u32) -> PResult<Expr>
+	{
+		let mut k = self.unary()?;
+		loop
+		{
+			let t = self.peek();
+			let precedence = match self.binary_precedence(t) { Some(p) if p > min_precedence => p, _ => return Ok(k) };
+			self.next();
+			// `**` is right-associative
+			self.binary(if t.text == "**" { precedence - 1 } else { precedence })?;
+			k = Expr::Other;
+		}
+	}
+	fn unary(&mut self) -> PResult<Expr>
+	{
+		let t = self.peek();
+		if (t.kind == TokenKind::Punct && ["!", "~", "+", "-"].contains(&t.text)) || (t.kind == TokenKind::Name && ["typeof", "void", "delete"].contains(&t.text))
+		{
+			self.next(); self.unary()?;
+			return Ok(Expr::Other);
+		}
+		if Self::token_is(t, "++") || Self::token_is(t, "--")
+		{
+			self.next();
+			let start = self.peek();
+			if self.unary()? != Expr::Target { return Err(error(self.source, start.offset, "Invalid left-hand side expression in prefix operation")); }
+			return Ok(Expr::Other);
+		}
+		let k = self.left_hand_side()?;
+		let t = self.peek();
+		if (Self::token_is(t, "++") || Self::token_is(t, "--")) && !t.newline_before
+		{
+			if k != Expr::Target { return Err(error(self.source, t.offset, "Invalid left-hand side expression in postfix operation")); }
+			self.next();
+			return Ok(Expr::Other);
+		}
+		Ok(k)
+	}
+	fn left_hand_side(&mut self) -> PResult<Expr>
+	{
+		let mut k = if self.is("new") { self.new_expression()? } else { self.primary()? };
+		loop
+		{
+			let t = self.peek();
+			if t.kind == TokenKind::Template { self.next(); k = Expr::Other; continue; }
+			if t.kind != TokenKind::Punct { return Ok(k); }
+			match t.text
+			{
+				"." => { self.next(); self.property_name_after_dot()?; k = Expr::Target; },
+				"?." =>
+				{
+					self.next();
+					if self.is("(") { self.arguments()?; }
+					else if self.eat("[") { self.allowing_in(|p| p.expression())?; self.expect("]")?; }
+					else { self.property_name_after_dot()?; }
+					k = Expr::Other;
+				},
+				"[" => { self.next(); self.allowing_in(|p| p.expression())?; self.expect("]")?; k = Expr::Target; },
+				"(" => { self.arguments()?; k = Expr::Other; },
+				_ => return Ok(k)
+			}
+		}
+	}
+	/// `new Callee(args)`(arguments are optional)
+	fn new_expression(&mut self) -> PResult<Expr>
+	{
+		self.next();
+		if self.is("new") { self.new_expression()?; } else { self.primary()?; }
+		loop
+		{
+			if self.eat(".") { self.property_name_after_dot()?; }
+			else if self.eat("[") { self.allowing_in(|p| p.expression())?; self.expect("]")?; }
+			else { break; }
+		}
+		if self.is("(") { self.arguments()?; }
+		Ok(Expr::Other)
+	}
+	fn property_name_after_dot(&mut self) -> PResult<()>
+	{
+		if self.peek().kind == TokenKind::Name { self.next(); Ok(()) } else { Err(self.unexpected()) }
+	}
+	fn arguments(&mut self) -> PResult<()>
+	{
+		self.expect("(")?;
+		self.allowing_in(|p|
+		{
+			while !p.eat(")")
+			{
+				p.eat("...");
+				p.assignment()?;
+				if !p.eat(",")
+				{
+					if p.eat(")") { break; }
+					return Err(error(p.source, p.peek().offset, "missing ) after argument list"));
+				}
+			}
+			Ok(())
+		})
+	}
+	fn primary(&mut self) -> PResult<Expr>
+	{
+		let t = self.next();
+		match t.kind
+		{
+			TokenKind::Number | TokenKind::String | TokenKind::Template | TokenKind::Regex => Ok(Expr::Other),
+			TokenKind::Name => match t.text
+			{
+				"function" =>
+				{
+					if self.peek().kind == TokenKind::Name && !self.is("(") { self.binding_identifier()?; }
+					self.function_rest().map(|_| Expr::Other)
+				},
+				"this" | "null" | "true" | "false" => Ok(Expr::Other),
+				name if is_reserved(name) => Err(self.unexpected_token(t)),
+				_ => Ok(Expr::Target)
+			},
+			TokenKind::Punct => match t.text
+			{
+				"(" =>
+				{
+					let k = self.allowing_in(|p| p.expression())?;
+					self.expect(")")?;
+					Ok(k)
+				},
+				"[" => self.allowing_in(|p| p.array_rest()).map(|_| Expr::Other),
+				"{" => self.allowing_in(|p| p.object_rest()).map(|_| Expr::Other),
+				_ => Err(self.unexpected_token(t))
+			},
+			TokenKind::End => Err(self.unexpected_token(t))
+		}
+	}
+	/// Array literal after `[`
+	fn array_rest(&mut self) -> PResult<()>
+	{
+		while !self.eat("]")
+		{
+			// holes
+			if self.eat(",") { continue; }
+			self.eat("...");
+			self.assignment()?;
+			if !self.eat(",") { return self.expect("]"); }
+		}
+		Ok(())
+	}
+	/// Object literal after `{`
+	fn object_rest(&mut self) -> PResult<()>
+	{
+		while !self.eat("}")
+		{
+			if self.eat("...") { self.assignment()?; }
+			else
+			{
+				let key = self.peek();
+				self.property_key()?;
+				let accessor = key.kind == TokenKind::Name && (key.text == "get" || key.text == "set") &&
+					!(self.is(":") || self.is("(") || self.is(",") || self.is("}"));
+				if accessor { self.property_key()?; self.function_rest()?; }
+				else if self.eat(":") { self.assignment()?; }
+				else if self.is("(") { self.function_rest()?; }
+				else if key.kind == TokenKind::Name && (self.is(",") || self.is("}"))
+				{
+					// shorthand
+					if is_reserved(key.text) { return Err(self.unexpected_token(key)); }
+				}
+				else { return Err(self.unexpected()); }
+			}
+			if !self.eat(",") { return self.expect("}"); }
+		}
+		Ok(())
+	}
+	fn property_key(&mut self) -> PResult<()>
+	{
+		let t = self.next();
+		match t.kind
+		{
+			TokenKind::Name | TokenKind::String | TokenKind::Number => Ok(()),
+			TokenKind::Punct if t.text == "[" => { self.assignment()?; self.expect("]") },
+			_ => Err(self.unexpected_token(t))
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests
+{
+	use super::*;
+
+	fn error_of(source: &str) -> SyntaxError { validate(source).unwrap_err() }
+
+	#[test]
+	fn valid_scripts()
+	{
+		let scripts = [
+			"var a = 1, b; let c = a + b * 2 ** 3; const d = { a, b: [1, , 2], 'c': c => c, [a]: 0, f(x) { return x; }, get g() { return 1; } };",
+			"(function() { if(a) { return\nb(); } else { for(var i = 0; i < 3; i++) continue; } })()",
+			"for(let k in o) delete o[k]; for(const x of [1, 2]) { label: while(x) break label; } do x--; while(x > 0)",
+			"switch(s) { case \"a\": case 'b': return 1; default: console.assert(0); }",
+			"try { throw new Error(`x ${ { a: 1 }.a } y`); } catch(e) { } finally { }",
+			"var r = s.replace(/[０-９]\\//g, x => String.fromCharCode(x.charCodeAt(0) - 65248)) / 2 / 3;",
+			"a = b ? c : d, e += typeof f === \"undefined\" ? void 0 : new g.h[i](...j);",
+			"x\n++y\n// comment\n/* block\ncomment */ z = 0x1F + .5e-3 + 1.",
+			"(() => {\nlet t = (a, b = 1, ...c) => ({ t: a });\nreturn t;\n})()",
+			// a slash after a block starts a regular expression, and one after an object literal divides
+			"{}\n/a/.test(s)", "if(a) { b() }\n/x/g.exec(s); var o = { a: { b: 1 } } / 2, p = {} / 3;",
+			// line continuations(CR LF is a unit)
+			"var s = 'a\\\r\nb', t = \"c\\\nd\";"
+		];
+		for s in &scripts { if let Err(e) = validate(s) { panic!("{}\n{}", s, e); } }
+	}
+	#[test]
+	fn errors()
+	{
+		let e = error_of("(function() {\n\tvar box = document.body;\n\treturn box; }})()");
+		assert_eq!((&e.message as &str, e.line, e.column), ("Unexpected token '}'", 3, 15));
+		assert_eq!(e.fragment, " return box; }})()");
+		assert!(e.to_string().ends_with(" return box; }})()\n                  ^"));
+		assert_eq!(error_of("a.map(x => )").message, "Unexpected token ')'");
+		assert_eq!(error_of("f(a b)").message, "missing ) after argument list");
+		assert_eq!(error_of("a + b = c").message, "Invalid left-hand side in assignment");
+		assert_eq!(error_of("var s = \"abc;\nf()").message, "Invalid or unexpected token");
+		assert_eq!(error_of("x = /abc").message, "Invalid regular expression: missing /");
+		assert_eq!(error_of("x = /a/gg").message, "Invalid regular expression flags");
+		assert_eq!(error_of("({ a: 1 }").message, "Unexpected end of input");
+		assert_eq!(error_of("var if = 1").message, "Unexpected token 'if'");
+		assert_eq!(error_of("a b").message, "Unexpected identifier");
+		assert_eq!(error_of("throw\nx").message, "Illegal newline after throw");
+		assert_eq!(error_of("const a;").message, "Missing initializer in const declaration");
+		assert_eq!(error_of("3in x").message, "Invalid or unexpected token");
+		assert_eq!(error_of("var s = 'a\\\r\n\r\nb';").message, "Invalid or unexpected token");
+		// limitation: a function expression is taken as a block
+		assert_eq!(error_of("f = function() {} / 2").message, "Invalid regular expression: missing /");
+		// clipped around the position
+		let long = format!("f({});", vec!["a"; 100].join(" + ") + " +");
+		assert_eq!(error_of(&long).fragment, format!("...{}", &long[long.len() - 42..]));
+	}
+	#[test]
+	fn non_ascii_input()
+	{
+		assert!(validate("x = 0あ").is_err());
+		assert!(validate("x = 0；").is_err());
+		// no panics at any position of any token
+		let tokens = ["0", "0x1", "1.", "1e5", ".5", "'s'", "\"s\"", "`t${u}`", "/r/g", "a", "// c\n", "/* c */", "{}", "=>"];
+		let chars = ['あ', '；', 'é', '😀', '\u{a0}', '\u{2028}', '\u{feff}', '\\'];
+		for t in &tokens
+		{
+			for (i, _) in t.char_indices().chain(Some((t.len(), ' ')))
+			{
+				for c in &chars
+				{
+					let source = format!("x = {}{}{}", &t[..i], c, &t[i..]);
+					let _ = validate(&source);
+					let _ = validate(&source[..source.len() - t[i..].len()]);
+				}
+			}
+		}
+	}
+}
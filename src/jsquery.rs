@@ -1,28 +1,136 @@
 //! JavaScript Fragment Combinator
 
-use std::fmt::{Display, Result as FmtResult, Formatter};
+use std::fmt::{Display, Result as FmtResult, Formatter, Error as FmtError};
 use std::marker::PhantomData;
+use std::rc::Rc;
+use std::time::Duration;
+use serde::Serialize;
+use serde_json;
+use offline::{self, Environment};
+use GenericResult;
 
 pub mod types
 {
 	use std::marker::PhantomData;
 	use std::fmt::{Display, Result as FmtResult, Formatter};
+	use serde::de::DeserializeOwned;
+	use serde_json;
+	use serde_json::Value as JValue;
+	use GenericResult;
 	
 	pub enum Element {}
 	pub enum NodeList {}
 	pub enum String {}
+	pub enum Number {}
+	pub enum Boolean {}
 	pub enum Object {}
+	/// JSON text(`JSON.stringify` result)
+	pub enum Json {}
 	pub struct Array<T>(PhantomData<T>);
+	/// Fixed-length array whose elements have the types of the tuple `T`(e.g. `Tuple<(Number, String)>`)
+	pub struct Tuple<T>(PhantomData<T>);
 	pub struct Closure<T>(PhantomData<T>);
+	/// `T` or `null`(`undefined`)
+	pub struct Optional<T>(PhantomData<T>);
+	/// Promise resolved with `T`(awaited by the executors)
+	pub struct Promise<T>(PhantomData<T>);
 
-	pub trait QueryableElements {}
+	/// Value types. Operations on `Optional<T>` apply to `T` and propagate null
+	pub trait Value { type Base; const NULLABLE: bool; }
+	/// Value type of `Base`
+	pub type BaseOf<T> = <T as Value>::Base;
+	/// Type of an operation result `R` applied to a value of this type(`Optional` only once)
+	pub trait Lift<R> { type Output; }
+	macro_rules! plain_values
+	{
+		($($t: ty),*) => { $(
+			impl Value for $t { type Base = $t; const NULLABLE: bool = false; }
+			impl<R> Lift<R> for $t { type Output = R; }
+		)* }
+	}
+	plain_values!(Element, NodeList, String, Number, Boolean, Object, Json);
+	impl<T> Value for Array<T> { type Base = Array<T>; const NULLABLE: bool = false; }
+	impl<T, R> Lift<R> for Array<T> { type Output = R; }
+	impl<T> Value for Tuple<T> { type Base = Tuple<T>; const NULLABLE: bool = false; }
+	impl<T, R> Lift<R> for Tuple<T> { type Output = R; }
+	impl<T> Value for Closure<T> { type Base = Closure<T>; const NULLABLE: bool = false; }
+	impl<T, R> Lift<R> for Closure<T> { type Output = R; }
+	impl<T> Value for Optional<T> { type Base = T; const NULLABLE: bool = true; }
+	impl<T, R: Value> Lift<R> for Optional<T> { type Output = Optional<R::Base>; }
+	impl<T> Value for Promise<T> { type Base = Promise<T>; const NULLABLE: bool = false; }
+	impl<T, R> Lift<R> for Promise<T> { type Output = R; }
+
+	/// Conversion of a by-value result into the Rust type `T`
+	pub trait Decode<T> { fn decode(value: JValue) -> GenericResult<T>; }
+	impl<T: DeserializeOwned> Decode<T> for String { fn decode(value: JValue) -> GenericResult<T> { serde_json::from_value(value).map_err(From::from) } }
+	impl<T: DeserializeOwned> Decode<T> for Number { fn decode(value: JValue) -> GenericResult<T> { serde_json::from_value(value).map_err(From::from) } }
+	impl<T: DeserializeOwned> Decode<T> for Boolean { fn decode(value: JValue) -> GenericResult<T> { serde_json::from_value(value).map_err(From::from) } }
+	impl<T: DeserializeOwned> Decode<T> for Object { fn decode(value: JValue) -> GenericResult<T> { serde_json::from_value(value).map_err(From::from) } }
+	impl<E, T: DeserializeOwned> Decode<T> for Tuple<E> { fn decode(value: JValue) -> GenericResult<T> { serde_json::from_value(value).map_err(From::from) } }
+	impl<T: DeserializeOwned> Decode<T> for Json
+	{
+		fn decode(value: JValue) -> GenericResult<T>
+		{
+			match value
+			{
+				JValue::String(s) => serde_json::from_str(&s).map_err(From::from),
+				v => Err(format!("JSON text expected, but got {}", v).into())
+			}
+		}
+	}
+	impl<E: Decode<T>, T> Decode<Option<T>> for Optional<E>
+	{
+		fn decode(value: JValue) -> GenericResult<Option<T>>
+		{
+			match value { JValue::Null => Ok(None), v => E::decode(v).map(Some) }
+		}
+	}
+	/// The resolved value(`awaitPromise`)
+	impl<E: Decode<T>, T> Decode<T> for Promise<E> { fn decode(value: JValue) -> GenericResult<T> { E::decode(value) } }
+	impl<E: Decode<T>, T> Decode<Vec<T>> for Array<E>
+	{
+		fn decode(value: JValue) -> GenericResult<Vec<T>>
+		{
+			match value
+			{
+				JValue::Array(v) => v.into_iter().map(E::decode).collect(),
+				v => Err(format!("Array expected, but got {}", v).into())
+			}
+		}
+	}
+
+	/// JavaScript type of a Rust value embedded by `Literal`
+	pub trait LiteralType { type ValueTy; }
+	impl LiteralType for str { type ValueTy = String; }
+	impl LiteralType for ::std::string::String { type ValueTy = String; }
+	impl LiteralType for char { type ValueTy = String; }
+	impl LiteralType for bool { type ValueTy = Boolean; }
+	macro_rules! number_literals { ($($t: ty),*) => { $(impl LiteralType for $t { type ValueTy = Number; })* } }
+	number_literals!(i8, i16, i32, i64, isize, u8, u16, u32, u64, usize, f32, f64);
+	impl<T: LiteralType> LiteralType for [T] { type ValueTy = Array<T::ValueTy>; }
+	impl<T: LiteralType> LiteralType for Vec<T> { type ValueTy = Array<T::ValueTy>; }
+	impl<'a, T: LiteralType + ?Sized> LiteralType for &'a T { type ValueTy = T::ValueTy; }
+
+	pub trait QueryableElements: Value + Lift<NodeList> {}
 	impl QueryableElements for Element {}
+	impl QueryableElements for Optional<Element> {}
 	pub trait Callable { type ReturnTy; }
 	impl<T> Callable for Closure<T> { type ReturnTy = T; }
-	pub trait Iterable
+	pub trait Awaitable { type ResolvedTy; }
+	impl<T> Awaitable for Promise<T> { type ResolvedTy = T; }
+	pub trait Iterable: Value
 	{
 		fn js_format<Src: Display, MapFn: Display>(source: &Src, mapfn: &MapFn, formatter: &mut Formatter) -> FmtResult;
 	}
+	impl<T: Iterable> Iterable for Optional<T>
+	{
+		fn js_format<Src: Display, MapFn: Display>(source: &Src, mapfn: &MapFn, formatter: &mut Formatter) -> FmtResult
+		{
+			formatter.write_str("(_v => _v == null ? null : ")?;
+			T::js_format(&"_v", mapfn, formatter)?;
+			write!(formatter, ")({})", source)
+		}
+	}
 	impl Iterable for NodeList
 	{
 		fn js_format<Src: Display, MapFn: Display>(source: &Src, mapfn: &MapFn, formatter: &mut Formatter) -> FmtResult
@@ -45,11 +153,57 @@ pub struct Document;
 pub struct CustomExpression<T>(pub String, pub PhantomData<T>);
 /// Closure(Arrow) expression
 pub struct Closure<'s, InnerTy: QueryCombinator>(&'s str, InnerTy);
+/// Rust value embedded as a JavaScript literal(strings, numbers, booleans and arrays of them).
+/// Use this for every selector and user input instead of formatting with `{:?}`
+pub struct Literal<T: Serialize>(pub T);
+/// Variable bound by a closure(`map_auto`, `map_value_auto`)
+pub struct Var<'s, T>(&'s str, PhantomData<T>);
+/// Typed reference to a variable
+pub fn var<'s, T>(name: &'s str) -> Var<'s, T> { Var(name, PhantomData) }
+impl<'s, T> Clone for Var<'s, T> { fn clone(&self) -> Self { *self } }
+impl<'s, T> Copy for Var<'s, T> {}
+
+// statements //
+/// Statements of a script(let-bindings and function definitions), finished by a return value
+#[derive(Default)]
+pub struct Statements(Vec<Statement>);
+enum Statement
+{
+	/// `let name = value;`
+	Let(&'static str, Box<Fragment>),
+	/// `function name(param) { return body; }`
+	Function(&'static str, &'static str, Rc<Fragment>)
+}
+/// Function defined in `Statements`, referred by its name(a closure value)
+pub struct FunctionRef<'s, RetTy>(&'s str, PhantomData<RetTy>);
+impl<'s, RetTy> Clone for FunctionRef<'s, RetTy> { fn clone(&self) -> Self { *self } }
+impl<'s, RetTy> Copy for FunctionRef<'s, RetTy> {}
+/// Script evaluated in its own scope(`(() => { statements; return value; })()`)
+pub struct Script<ReturnTy: QueryCombinator>(Vec<Statement>, ReturnTy);
+impl Statements
+{
+	pub fn new() -> Self { Statements(Vec::new()) }
+	/// `let name = value;`
+	pub fn bind<Q: QueryCombinator + 'static>(&mut self, name: &'static str, value: Q) -> Var<'static, Q::ValueTy>
+	{
+		self.0.push(Statement::Let(name, Box::new(value)));
+		var(name)
+	}
+	/// `function name(param) { return body(param); }`
+	pub fn define<ArgTy, BodyTy, F>(&mut self, name: &'static str, param: &'static str, body: F) -> FunctionRef<'static, BodyTy::ValueTy>
+		where BodyTy: QueryCombinator + 'static, F: FnOnce(Var<'static, ArgTy>) -> BodyTy
+	{
+		self.0.push(Statement::Function(name, param, Rc::new(body(var(param)))));
+		FunctionRef(name, PhantomData)
+	}
+	/// Finishes the script with the return value
+	pub fn finish<ReturnTy: QueryCombinator>(self, value: ReturnTy) -> Script<ReturnTy> { Script(self.0, value) }
+}
 
 // specialized ops //
 /// method calling syntax of `JSON.stringify(Any)`
 pub struct ObjectStringify<InnerTy: QueryCombinator>(InnerTy);
-/// Element.querySelector
+/// Element.querySelector(null if no element matches)
 pub struct QuerySelector<ParentTy: QueryCombinator>(ParentTy, String) where ParentTy::ValueTy: types::QueryableElements;
 /// Element.querySelectorAll
 pub struct QuerySelectorAll<ParentTy: QueryCombinator>(ParentTy, String)
@@ -57,24 +211,126 @@ pub struct QuerySelectorAll<ParentTy: QueryCombinator>(ParentTy, String)
 /// Single value mapping operation(`({closure})({expr})`)
 pub struct ValueMapping<InnerTy: QueryCombinator, ClosureTy: QueryCombinator>(InnerTy, ClosureTy)
 	where ClosureTy::ValueTy: types::Callable;
-/// Multiple value mapping operation(`{expr}.map({closure})` or `Array.prototype.map.call({expr}, {closure})`)
+/// Multiple value mapping operation(`{expr}.map({closure})` or `Array.prototype.map.call({expr}, {closure})`).
+/// Null if the source is null
 pub struct Mapping<SourceTy: QueryCombinator, ClosureTy: QueryCombinator>(SourceTy, ClosureTy)
 	where SourceTy::ValueTy: types::Iterable, ClosureTy::ValueTy: types::Callable;
-pub struct ObjectConstructor<'v, 's: 'v>(pub &'v [(&'s str, &'s str)]);
+/// Object literal. Values are fragments(or raw expressions)
+pub struct ObjectConstructor(pub Vec<(&'static str, Box<Fragment>)>);
+/// Operation applied to the receiver(`receiver.op`, or null if the receiver is null)
+pub struct Chain<SourceTy: QueryCombinator, Op>(SourceTy, Op);
+/// Default for a null value
+pub struct UnwrapOr<SourceTy: QueryCombinator, DefaultTy: QueryCombinator>(SourceTy, DefaultTy);
+
+// asynchronous fragments //
+/// Timeout of `poll` and `after_mutation` unless specified(in seconds)
+pub const DEFAULT_TIMEOUT_SECS: u64 = 30;
+/// The element matching the selector, waiting for it to be inserted(rejected after the timeout)
+pub struct WaitFor { selector: String, timeout: Duration }
+/// The first value of the predicate other than null and false, evaluated every interval
+pub struct Poll<PredicateTy: QueryCombinator> { predicate: PredicateTy, interval: Duration, timeout: Duration }
+/// The element matching the selector, after the first change of its subtree or attributes
+pub struct AfterMutation { selector: String, timeout: Duration }
+/// Continuation of a promise(`promise.then(closure)`)
+pub struct Then<SourceTy: QueryCombinator, ClosureTy: QueryCombinator>(SourceTy, ClosureTy)
+	where SourceTy::ValueTy: types::Awaitable, ClosureTy::ValueTy: types::Callable;
+pub fn wait_for(selector: &str, timeout: Duration) -> WaitFor { WaitFor { selector: selector.to_owned(), timeout } }
+pub fn poll<PredicateTy: QueryCombinator>(predicate: PredicateTy, interval: Duration) -> Poll<PredicateTy> where PredicateTy::ValueTy: types::Value
+{
+	Poll { predicate, interval, timeout: Duration::from_secs(DEFAULT_TIMEOUT_SECS) }
+}
+pub fn after_mutation(selector: &str) -> AfterMutation
+{
+	AfterMutation { selector: selector.to_owned(), timeout: Duration::from_secs(DEFAULT_TIMEOUT_SECS) }
+}
+impl<PredicateTy: QueryCombinator> Poll<PredicateTy>
+{
+	pub fn timeout(self, timeout: Duration) -> Self { Poll { timeout, .. self } }
+}
+impl AfterMutation
+{
+	pub fn timeout(self, timeout: Duration) -> Self { AfterMutation { timeout, .. self } }
+}
+fn millis(d: Duration) -> u64 { d.as_secs() * 1000 + d.subsec_nanos() as u64 / 1_000_000 }
+
+// element and value ops //
+/// Operation on a value of type `Base`
+pub trait Operation<Base>
+{
+	/// Value type of the result
+	type ResultTy;
+	/// Writes the expression applying the operation to `receiver`(already parenthesized)
+	fn format(&self, receiver: &Display, fmt: &mut Formatter) -> FmtResult;
+	/// Applies the operation offline
+	fn apply(&self, receiver: offline::Value, env: &Environment) -> GenericResult<offline::Value>;
+}
+/// Result type of `Op` applied to a value of type `T`(before lifting)
+pub type ResultOf<Op, T> = <Op as Operation<types::BaseOf<T>>>::ResultTy;
+/// Node.textContent
+pub struct TextContent;
+/// HTMLElement.innerText(rendered text)
+pub struct InnerText;
+/// Element.getAttribute
+pub struct Attribute(String);
+/// Property access(`receiver[name]`) typed as `T`
+pub struct Property<T>(String, PhantomData<T>);
+/// String.prototype.trim
+pub struct Trim;
+/// String.prototype.replace with a regular expression(`$n` in the replacement refers to the groups)
+pub struct Replace { pattern: String, flags: &'static str, replacement: String }
+/// parseInt(radix 10)
+pub struct ParseInt;
+/// parseFloat
+pub struct ParseFloat;
+/// String.prototype.slice/Array.prototype.slice(`end` is the length if omitted)
+pub struct Slice(i64, Option<i64>);
+/// Element access(`receiver[n]`)
+pub struct Index(usize);
+/// Splits an array into arrays of `n` elements(the last one may be shorter)
+pub struct Chunks(usize);
+/// HTMLElement.click(results `true`)
+pub struct Click;
 
 /// Helper macro constructing ObjectConstructor
+#[macro_export]
 macro_rules! jsqGenObject
 {
-	{ $($k: ident : $v: expr),* } => { $crate::jsquery::ObjectConstructor(&[$((stringify!($k), $v)),*]) }
+	{ $($k: ident : $v: expr),* } => { $crate::jsquery::ObjectConstructor(vec![$((stringify!($k), Box::new($v) as Box<$crate::jsquery::Fragment>)),*]) }
 }
 /// Helper macro constructing CustomExpression
+#[macro_export]
 macro_rules! jsqCustomExpr
 {
-	([$t: ty] $e: expr) => { $crate::jsquery::CustomExpression::<$t>($e.into(), PhantomData) }
+	([$t: ty] $e: expr) => { $crate::jsquery::CustomExpression::<$t>($e.into(), ::std::marker::PhantomData) }
+}
+
+/// Evaluation without a browser(see `offline`)
+pub trait Evaluate
+{
+	fn evaluate(&self, env: &mut Environment) -> GenericResult<offline::Value>;
+	/// Calls the value as a function(closures and defined functions)
+	fn call(&self, _arg: offline::Value, _env: &mut Environment) -> GenericResult<offline::Value>
+	{
+		Err("TypeError: the value is not a function".into())
+	}
+}
+impl<T: Evaluate + ?Sized> Evaluate for Box<T>
+{
+	fn evaluate(&self, env: &mut Environment) -> GenericResult<offline::Value> { (**self).evaluate(env) }
+	fn call(&self, arg: offline::Value, env: &mut Environment) -> GenericResult<offline::Value> { (**self).call(arg, env) }
+}
+/// Untyped fragment(object fields and statements)
+pub trait Fragment: Display + Evaluate {}
+impl<T: Display + Evaluate> Fragment for T {}
+/// Backend running queries(`RemoteCampus` in the browser, `offline::StaticPage` on parsed HTML)
+pub trait Executor
+{
+	/// Runs the query and decodes the result into a Rust value as its `ValueTy` describes(promises are awaited)
+	fn execute<Q: QueryCombinator, T>(&mut self, context: Option<u64>, query: Q) -> GenericResult<T> where Q::ValueTy: types::Decode<T>;
 }
 
 /// Lazy-combined: JavaScript Fragment Combinator
-pub trait QueryCombinator: Sized + ::std::fmt::Display
+pub trait QueryCombinator: Sized + ::std::fmt::Display + Evaluate
 {
 	/// Expecting Value Type of this expression
 	type ValueTy;
@@ -112,24 +368,70 @@ pub trait QueryCombinator: Sized + ::std::fmt::Display
 		self.map(expr.into_closure(bound))
 	}
 
-	fn with_header(&self, header: &str) -> String { format!("{}\n{}", header, self) }
+	// asynchronous ops //
+	fn then<ClosureTy: QueryCombinator>(self, f: ClosureTy) -> Then<Self, ClosureTy>
+		where Self::ValueTy: types::Awaitable, ClosureTy::ValueTy: types::Callable
+	{
+		Then(self, f)
+	}
+	fn then_auto<'s, ExpressionTy: QueryCombinator>(self, bound: &'s str, expr: ExpressionTy)
+		-> Then<Self, Closure<'s, ExpressionTy>> where Self::ValueTy: types::Awaitable
+	{
+		self.then(expr.into_closure(bound))
+	}
+
+	// element and value ops //
+	fn text_content(self) -> Chain<Self, TextContent> where Self::ValueTy: types::Value, TextContent: Operation<types::BaseOf<Self::ValueTy>> { Chain(self, TextContent) }
+	fn inner_text(self) -> Chain<Self, InnerText> where Self::ValueTy: types::Value, InnerText: Operation<types::BaseOf<Self::ValueTy>> { Chain(self, InnerText) }
+	fn attribute(self, name: &str) -> Chain<Self, Attribute> where Self::ValueTy: types::Value, Attribute: Operation<types::BaseOf<Self::ValueTy>>
+	{
+		Chain(self, Attribute(name.to_owned()))
+	}
+	fn property<T>(self, name: &str) -> Chain<Self, Property<T>> where Self::ValueTy: types::Value, Property<T>: Operation<types::BaseOf<Self::ValueTy>>
+	{
+		Chain(self, Property(name.to_owned(), PhantomData))
+	}
+	fn trim(self) -> Chain<Self, Trim> where Self::ValueTy: types::Value, Trim: Operation<types::BaseOf<Self::ValueTy>> { Chain(self, Trim) }
+	/// Replaces the first match of the regular expression
+	fn replace(self, pattern: &str, replacement: &str) -> Chain<Self, Replace> where Self::ValueTy: types::Value, Replace: Operation<types::BaseOf<Self::ValueTy>>
+	{
+		Chain(self, Replace { pattern: pattern.to_owned(), flags: "", replacement: replacement.to_owned() })
+	}
+	/// Replaces every match of the regular expression
+	fn replace_all(self, pattern: &str, replacement: &str) -> Chain<Self, Replace> where Self::ValueTy: types::Value, Replace: Operation<types::BaseOf<Self::ValueTy>>
+	{
+		Chain(self, Replace { pattern: pattern.to_owned(), flags: "g", replacement: replacement.to_owned() })
+	}
+	fn parse_int(self) -> Chain<Self, ParseInt> where Self::ValueTy: types::Value, ParseInt: Operation<types::BaseOf<Self::ValueTy>> { Chain(self, ParseInt) }
+	fn parse_float(self) -> Chain<Self, ParseFloat> where Self::ValueTy: types::Value, ParseFloat: Operation<types::BaseOf<Self::ValueTy>> { Chain(self, ParseFloat) }
+	fn slice(self, start: i64, end: Option<i64>) -> Chain<Self, Slice> where Self::ValueTy: types::Value, Slice: Operation<types::BaseOf<Self::ValueTy>> { Chain(self, Slice(start, end)) }
+	fn index(self, n: usize) -> Chain<Self, Index> where Self::ValueTy: types::Value, Index: Operation<types::BaseOf<Self::ValueTy>> { Chain(self, Index(n)) }
+	fn chunks(self, n: usize) -> Chain<Self, Chunks> where Self::ValueTy: types::Value, Chunks: Operation<types::BaseOf<Self::ValueTy>> { Chain(self, Chunks(n)) }
+	fn click(self) -> Chain<Self, Click> where Self::ValueTy: types::Value, Click: Operation<types::BaseOf<Self::ValueTy>> { Chain(self, Click) }
+	/// `default` if the value is null
+	fn unwrap_or<DefaultTy>(self, default: DefaultTy) -> UnwrapOr<Self, DefaultTy>
+		where Self::ValueTy: types::Value, DefaultTy: QueryCombinator<ValueTy = types::BaseOf<Self::ValueTy>>
+	{
+		UnwrapOr(self, default)
+	}
 }
 impl QueryCombinator for Document { type ValueTy = types::Element; }
 impl<ParentTy: QueryCombinator> QueryCombinator for QuerySelector<ParentTy>
 	where ParentTy::ValueTy: types::QueryableElements
 {
-	type ValueTy = types::Element;
+	type ValueTy = types::Optional<types::Element>;
 }
 impl<ParentTy: QueryCombinator> QueryCombinator for QuerySelectorAll<ParentTy>
 	where ParentTy::ValueTy: types::QueryableElements
 {
-	type ValueTy = types::NodeList;
+	type ValueTy = <ParentTy::ValueTy as types::Lift<types::NodeList>>::Output;
 }
 impl<InnerTy: QueryCombinator> QueryCombinator for ObjectStringify<InnerTy>
 {
-	type ValueTy = types::String;
+	type ValueTy = types::Json;
 }
 impl<T> QueryCombinator for CustomExpression<T> { type ValueTy = T; }
+impl<T: Serialize + types::LiteralType> QueryCombinator for Literal<T> { type ValueTy = T::ValueTy; }
 impl<'s, InnerTy: QueryCombinator> QueryCombinator for Closure<'s, InnerTy>
 {
 	type ValueTy = types::Closure<InnerTy::ValueTy>;
@@ -137,15 +439,250 @@ impl<'s, InnerTy: QueryCombinator> QueryCombinator for Closure<'s, InnerTy>
 impl<InnerTy: QueryCombinator, ClosureTy: QueryCombinator> QueryCombinator for ValueMapping<InnerTy, ClosureTy>
 	where ClosureTy::ValueTy: types::Callable { type ValueTy = <ClosureTy::ValueTy as types::Callable>::ReturnTy; }
 impl<SourceTy: QueryCombinator, ClosureTy: QueryCombinator> QueryCombinator for Mapping<SourceTy, ClosureTy>
-	where SourceTy::ValueTy: types::Iterable, ClosureTy::ValueTy: types::Callable
+	where SourceTy::ValueTy: types::Iterable + types::Lift<types::Array<<ClosureTy::ValueTy as types::Callable>::ReturnTy>>,
+		ClosureTy::ValueTy: types::Callable
+{
+	type ValueTy = <SourceTy::ValueTy as types::Lift<types::Array<<ClosureTy::ValueTy as types::Callable>::ReturnTy>>>::Output;
+}
+impl QueryCombinator for ObjectConstructor { type ValueTy = types::Object; }
+impl<'s, T> QueryCombinator for Var<'s, T> { type ValueTy = T; }
+impl<'s, RetTy> QueryCombinator for FunctionRef<'s, RetTy> { type ValueTy = types::Closure<RetTy>; }
+impl<ReturnTy: QueryCombinator> QueryCombinator for Script<ReturnTy> { type ValueTy = ReturnTy::ValueTy; }
+impl<SourceTy: QueryCombinator, Op> QueryCombinator for Chain<SourceTy, Op>
+	where SourceTy::ValueTy: types::Value + types::Lift<ResultOf<Op, SourceTy::ValueTy>>, Op: Operation<types::BaseOf<SourceTy::ValueTy>>
+{
+	type ValueTy = <SourceTy::ValueTy as types::Lift<ResultOf<Op, SourceTy::ValueTy>>>::Output;
+}
+impl<SourceTy: QueryCombinator, DefaultTy: QueryCombinator> QueryCombinator for UnwrapOr<SourceTy, DefaultTy>
+{
+	type ValueTy = DefaultTy::ValueTy;
+}
+impl QueryCombinator for WaitFor { type ValueTy = types::Promise<types::Element>; }
+impl<PredicateTy: QueryCombinator> QueryCombinator for Poll<PredicateTy> where PredicateTy::ValueTy: types::Value
+{
+	type ValueTy = types::Promise<types::BaseOf<PredicateTy::ValueTy>>;
+}
+impl QueryCombinator for AfterMutation { type ValueTy = types::Promise<types::Element>; }
+impl<SourceTy: QueryCombinator, ClosureTy: QueryCombinator> QueryCombinator for Then<SourceTy, ClosureTy>
+	where SourceTy::ValueTy: types::Awaitable, ClosureTy::ValueTy: types::Callable
 {
-	type ValueTy = types::Array<<ClosureTy::ValueTy as types::Callable>::ReturnTy>;
+	type ValueTy = types::Promise<<ClosureTy::ValueTy as types::Callable>::ReturnTy>;
+}
+
+impl Operation<types::Element> for TextContent
+{
+	type ResultTy = types::String;
+	fn format(&self, receiver: &Display, fmt: &mut Formatter) -> FmtResult { write!(fmt, "{}.textContent", receiver) }
+	fn apply(&self, receiver: offline::Value, env: &Environment) -> GenericResult<offline::Value>
+	{
+		receiver.into_node("textContent").map(|n| offline::Value::String(env.node(n).text_content()))
+	}
+}
+impl Operation<types::Element> for InnerText
+{
+	type ResultTy = types::String;
+	fn format(&self, receiver: &Display, fmt: &mut Formatter) -> FmtResult { write!(fmt, "{}.innerText", receiver) }
+	/// No layout offline: same as `textContent`
+	fn apply(&self, receiver: offline::Value, env: &Environment) -> GenericResult<offline::Value>
+	{
+		receiver.into_node("innerText").map(|n| offline::Value::String(env.node(n).text_content()))
+	}
+}
+impl Operation<types::Element> for Attribute
+{
+	type ResultTy = types::Optional<types::String>;
+	fn format(&self, receiver: &Display, fmt: &mut Formatter) -> FmtResult { write!(fmt, "{}.getAttribute({})", receiver, Literal(&self.0)) }
+	fn apply(&self, receiver: offline::Value, env: &Environment) -> GenericResult<offline::Value>
+	{
+		let n = receiver.into_node("getAttribute")?;
+		Ok(env.node(n).attribute(&self.0).map(|v| offline::Value::String(v.to_owned())).unwrap_or(offline::Value::Null))
+	}
+}
+impl<Base, T> Operation<Base> for Property<T>
+{
+	type ResultTy = T;
+	fn format(&self, receiver: &Display, fmt: &mut Formatter) -> FmtResult { write!(fmt, "{}[{}]", receiver, Literal(&self.0)) }
+	/// Form control states, attributes reflected as properties, `length` and object fields. Others are undefined(null)
+	fn apply(&self, receiver: offline::Value, env: &Environment) -> GenericResult<offline::Value>
+	{
+		Ok(match (receiver, &self.0 as &str)
+		{
+			(offline::Value::Node(n), name) =>
+			{
+				let node = env.node(n);
+				match name
+				{
+					"value" => node.data().input_value.clone().or_else(|| node.attribute("value").map(ToOwned::to_owned))
+						.map(offline::Value::String).unwrap_or(offline::Value::Null),
+					"checked" => offline::Value::Boolean(node.data().input_checked || node.attribute("checked").is_some()),
+					"tagName" | "nodeName" => offline::Value::String(node.data().name.clone()),
+					"textContent" => offline::Value::String(node.text_content()),
+					"className" => offline::Value::String(node.attribute("class").unwrap_or("").to_owned()),
+					"id" | "href" | "src" | "name" | "title" => offline::Value::String(node.attribute(name).unwrap_or("").to_owned()),
+					_ => offline::Value::Null
+				}
+			},
+			(offline::Value::String(s), "length") => offline::Value::Number(s.encode_utf16().count() as f64),
+			(offline::Value::Array(v), "length") => offline::Value::Number(v.len() as f64),
+			(offline::Value::NodeList(v), "length") => offline::Value::Number(v.len() as f64),
+			(offline::Value::Object(o), name) => o.into_iter().find(|&(ref k, _)| k == name).map(|(_, v)| v).unwrap_or(offline::Value::Null),
+			(offline::Value::Null, name) => return Err(format!("TypeError: Cannot read property {:?} of null", name).into()),
+			_ => offline::Value::Null
+		})
+	}
+}
+impl Operation<types::String> for Trim
+{
+	type ResultTy = types::String;
+	fn format(&self, receiver: &Display, fmt: &mut Formatter) -> FmtResult { write!(fmt, "{}.trim()", receiver) }
+	fn apply(&self, receiver: offline::Value, _env: &Environment) -> GenericResult<offline::Value>
+	{
+		receiver.into_string("trim").map(|s| offline::Value::String(s.trim().to_owned()))
+	}
+}
+impl Operation<types::String> for Replace
+{
+	type ResultTy = types::String;
+	fn format(&self, receiver: &Display, fmt: &mut Formatter) -> FmtResult
+	{
+		write!(fmt, "{}.replace(new RegExp({}, {}), {})", receiver, Literal(&self.pattern), Literal(self.flags), Literal(&self.replacement))
+	}
+	fn apply(&self, receiver: offline::Value, _env: &Environment) -> GenericResult<offline::Value>
+	{
+		let s = receiver.into_string("replace")?;
+		offline::replace(&s, &self.pattern, self.flags, &self.replacement).map(offline::Value::String)
+	}
+}
+impl Operation<types::String> for ParseInt
+{
+	type ResultTy = types::Number;
+	fn format(&self, receiver: &Display, fmt: &mut Formatter) -> FmtResult { write!(fmt, "parseInt({}, 10)", receiver) }
+	fn apply(&self, receiver: offline::Value, _env: &Environment) -> GenericResult<offline::Value>
+	{
+		receiver.into_string("parseInt").map(|s| offline::Value::Number(offline::parse_int(&s)))
+	}
+}
+impl Operation<types::String> for ParseFloat
+{
+	type ResultTy = types::Number;
+	fn format(&self, receiver: &Display, fmt: &mut Formatter) -> FmtResult { write!(fmt, "parseFloat({})", receiver) }
+	fn apply(&self, receiver: offline::Value, _env: &Environment) -> GenericResult<offline::Value>
+	{
+		receiver.into_string("parseFloat").map(|s| offline::Value::Number(offline::parse_float(&s)))
+	}
+}
+impl Slice
+{
+	fn format_slice(&self, receiver: &Display, fmt: &mut Formatter) -> FmtResult
+	{
+		match self.1
+		{
+			Some(e) => write!(fmt, "{}.slice({}, {})", receiver, self.0, e),
+			None => write!(fmt, "{}.slice({})", receiver, self.0)
+		}
+	}
+	fn apply_slice(&self, receiver: offline::Value) -> GenericResult<offline::Value>
+	{
+		match receiver
+		{
+			// indices are in UTF-16 code units
+			offline::Value::String(s) =>
+			{
+				let units: Vec<u16> = s.encode_utf16().collect();
+				let (start, end) = offline::slice_range(units.len(), self.0, self.1);
+				Ok(offline::Value::String(String::from_utf16_lossy(&units[start..end])))
+			},
+			offline::Value::Array(mut v) =>
+			{
+				let (start, end) = offline::slice_range(v.len(), self.0, self.1);
+				v.truncate(end);
+				Ok(offline::Value::Array(v.split_off(start)))
+			},
+			v => Err(offline::type_error("slice", &v))
+		}
+	}
+}
+impl Operation<types::String> for Slice
+{
+	type ResultTy = types::String;
+	fn format(&self, receiver: &Display, fmt: &mut Formatter) -> FmtResult { self.format_slice(receiver, fmt) }
+	fn apply(&self, receiver: offline::Value, _env: &Environment) -> GenericResult<offline::Value> { self.apply_slice(receiver) }
+}
+impl<T> Operation<types::Array<T>> for Slice
+{
+	type ResultTy = types::Array<T>;
+	fn format(&self, receiver: &Display, fmt: &mut Formatter) -> FmtResult { self.format_slice(receiver, fmt) }
+	fn apply(&self, receiver: offline::Value, _env: &Environment) -> GenericResult<offline::Value> { self.apply_slice(receiver) }
+}
+impl<T> Operation<types::Array<T>> for Index
+{
+	type ResultTy = T;
+	fn format(&self, receiver: &Display, fmt: &mut Formatter) -> FmtResult { write!(fmt, "{}[{}]", receiver, self.0) }
+	fn apply(&self, receiver: offline::Value, _env: &Environment) -> GenericResult<offline::Value>
+	{
+		// out of range is undefined
+		Ok(receiver.into_array("[]")?.into_iter().nth(self.0).unwrap_or(offline::Value::Null))
+	}
+}
+impl Operation<types::NodeList> for Index
+{
+	type ResultTy = types::Optional<types::Element>;
+	fn format(&self, receiver: &Display, fmt: &mut Formatter) -> FmtResult { write!(fmt, "{}[{}]", receiver, self.0) }
+	fn apply(&self, receiver: offline::Value, _env: &Environment) -> GenericResult<offline::Value>
+	{
+		match receiver
+		{
+			offline::Value::NodeList(v) => Ok(v.get(self.0).map(|&n| offline::Value::Node(n)).unwrap_or(offline::Value::Null)),
+			v => Err(offline::type_error("[]", &v))
+		}
+	}
+}
+impl<T> Operation<types::Array<T>> for Chunks
+{
+	type ResultTy = types::Array<types::Array<T>>;
+	fn format(&self, receiver: &Display, fmt: &mut Formatter) -> FmtResult
+	{
+		write!(fmt, "(a => {{ var r = []; for(var i = 0; i < a.length; i += {n}) r.push(a.slice(i, i + {n})); return r; }})({})",
+			receiver, n = self.0)
+	}
+	fn apply(&self, receiver: offline::Value, _env: &Environment) -> GenericResult<offline::Value>
+	{
+		let v = receiver.into_array("chunks")?;
+		Ok(offline::Value::Array(v.chunks(self.0.max(1)).map(|c| offline::Value::Array(c.to_vec())).collect()))
+	}
+}
+impl Operation<types::Element> for Click
+{
+	type ResultTy = types::Boolean;
+	fn format(&self, receiver: &Display, fmt: &mut Formatter) -> FmtResult { write!(fmt, "({}.click(), true)", receiver) }
+	fn apply(&self, _receiver: offline::Value, _env: &Environment) -> GenericResult<offline::Value>
+	{
+		Err("click() cannot be performed offline".into())
+	}
+}
+
+/// Writes `apply` on the source, skipping it if the source is nullable and null
+fn format_lifted<SourceTy: Display, F>(nullable: bool, source: &SourceTy, fmt: &mut Formatter, apply: F) -> FmtResult
+	where F: FnOnce(&Display, &mut Formatter) -> FmtResult
+{
+	if !nullable { return apply(&format_args!("({})", source), fmt); }
+	fmt.write_str("(_v => _v == null ? null : ")?;
+	apply(&"_v", fmt)?;
+	write!(fmt, ")({})", source)
 }
-impl<'v, 's> QueryCombinator for ObjectConstructor<'v, 's> { type ValueTy = types::Object; }
 
 // generate script //
 impl<T> Display for CustomExpression<T> { fn fmt(&self, fmt: &mut Formatter) -> FmtResult { self.0.fmt(fmt) } }
 impl Display for Document { fn fmt(&self, fmt: &mut Formatter) -> FmtResult { write!(fmt, "document") } }
+impl<T: Serialize> Display for Literal<T>
+{
+	fn fmt(&self, fmt: &mut Formatter) -> FmtResult
+	{
+		let json = serde_json::to_string(&self.0).map_err(|_| FmtError)?;
+		// JSON allows LINE/PARAGRAPH SEPARATOR in strings, but JavaScript(before ES2019) does not
+		fmt.write_str(&json.replace('\u{2028}', "\\u2028").replace('\u{2029}', "\\u2029"))
+	}
+}
 impl<'s, InnerTy: QueryCombinator> Display for Closure<'s, InnerTy>
 {
 	fn fmt(&self, fmt: &mut Formatter) -> FmtResult { write!(fmt, "{} => {}", self.0, self.1) }
@@ -155,7 +692,8 @@ impl<ParentTy: QueryCombinator> Display for QuerySelector<ParentTy>
 {
 	fn fmt(&self, fmt: &mut Formatter) -> FmtResult
 	{
-		write!(fmt, "({}).querySelector({:?})", self.0, self.1)
+		format_lifted(<ParentTy::ValueTy as types::Value>::NULLABLE, &self.0, fmt,
+			|r, fmt| write!(fmt, "{}.querySelector({})", r, Literal(&self.1)))
 	}
 }
 impl<ParentTy: QueryCombinator> Display for QuerySelectorAll<ParentTy>
@@ -163,7 +701,8 @@ impl<ParentTy: QueryCombinator> Display for QuerySelectorAll<ParentTy>
 {
 	fn fmt(&self, fmt: &mut Formatter) -> FmtResult
 	{
-		write!(fmt, "({}).querySelectorAll({:?})", self.0, self.1)
+		format_lifted(<ParentTy::ValueTy as types::Value>::NULLABLE, &self.0, fmt,
+			|r, fmt| write!(fmt, "{}.querySelectorAll({})", r, Literal(&self.1)))
 	}
 }
 impl<InnerTy: QueryCombinator> Display for ObjectStringify<InnerTy>
@@ -180,10 +719,400 @@ impl<SourceTy: QueryCombinator, ClosureTy: QueryCombinator> Display for Mapping<
 {
 	fn fmt(&self, fmt: &mut Formatter) -> FmtResult { <SourceTy::ValueTy as types::Iterable>::js_format(&self.0, &self.1, fmt) }
 }
-impl<'v, 's> Display for ObjectConstructor<'v, 's>
+impl Display for ObjectConstructor
 {
 	fn fmt(&self, fmt: &mut Formatter) -> FmtResult
 	{
 		write!(fmt, "({{ {} }})", self.0.iter().map(|&(ref k, ref v)| format!("{}: {}", k, v)).collect::<Vec<String>>().join(","))
 	}
 }
+impl<'s, T> Display for Var<'s, T> { fn fmt(&self, fmt: &mut Formatter) -> FmtResult { fmt.write_str(self.0) } }
+impl<'s, RetTy> Display for FunctionRef<'s, RetTy> { fn fmt(&self, fmt: &mut Formatter) -> FmtResult { fmt.write_str(self.0) } }
+impl<ReturnTy: QueryCombinator> Display for Script<ReturnTy>
+{
+	fn fmt(&self, fmt: &mut Formatter) -> FmtResult
+	{
+		fmt.write_str("(() => {\n")?;
+		for s in &self.0 { writeln!(fmt, "{}", s)?; }
+		write!(fmt, "return {};\n}})()", self.1)
+	}
+}
+impl<SourceTy: QueryCombinator, Op> Display for Chain<SourceTy, Op>
+	where SourceTy::ValueTy: types::Value, Op: Operation<types::BaseOf<SourceTy::ValueTy>>
+{
+	fn fmt(&self, fmt: &mut Formatter) -> FmtResult
+	{
+		format_lifted(<SourceTy::ValueTy as types::Value>::NULLABLE, &self.0, fmt, |r, fmt| self.1.format(r, fmt))
+	}
+}
+impl<SourceTy: QueryCombinator, DefaultTy: QueryCombinator> Display for UnwrapOr<SourceTy, DefaultTy>
+{
+	fn fmt(&self, fmt: &mut Formatter) -> FmtResult { write!(fmt, "(_v => _v == null ? {} : _v)({})", self.1, self.0) }
+}
+impl Display for WaitFor
+{
+	fn fmt(&self, fmt: &mut Formatter) -> FmtResult
+	{
+		write!(fmt, concat!("new Promise((_resolve, _reject) => {{ ",
+			"const _find = () => document.querySelector({sel}); const _e = _find(); if(_e != null) return _resolve(_e); ",
+			"const _observer = new MutationObserver(() => {{ const _e = _find(); if(_e != null) {{ _observer.disconnect(); clearTimeout(_timer); _resolve(_e); }} }}); ",
+			"const _timer = setTimeout(() => {{ _observer.disconnect(); _reject(new Error(\"Timed out waiting for \" + {sel})); }}, {timeout}); ",
+			"_observer.observe(document, {{ childList: true, subtree: true }}); }})"), sel = Literal(&self.selector), timeout = millis(self.timeout))
+	}
+}
+impl<PredicateTy: QueryCombinator> Display for Poll<PredicateTy>
+{
+	fn fmt(&self, fmt: &mut Formatter) -> FmtResult
+	{
+		write!(fmt, concat!("new Promise((_resolve, _reject) => {{ const _started = Date.now(); const _tick = () => {{ ",
+			"let _r; try {{ _r = {predicate}; }} catch(e) {{ return _reject(e); }} ",
+			"if(_r != null && _r !== false) return _resolve(_r); ",
+			"if(Date.now() - _started >= {timeout}) return _reject(new Error(\"Timed out polling\")); ",
+			"setTimeout(_tick, {interval}); }}; _tick(); }})"), predicate = self.predicate, timeout = millis(self.timeout), interval = millis(self.interval))
+	}
+}
+impl Display for AfterMutation
+{
+	fn fmt(&self, fmt: &mut Formatter) -> FmtResult
+	{
+		write!(fmt, concat!("new Promise((_resolve, _reject) => {{ ",
+			"const _target = document.querySelector({sel}); if(_target == null) return _reject(new Error(\"No element matches \" + {sel})); ",
+			"const _observer = new MutationObserver(() => {{ _observer.disconnect(); clearTimeout(_timer); _resolve(_target); }}); ",
+			"const _timer = setTimeout(() => {{ _observer.disconnect(); _reject(new Error(\"Timed out waiting for a mutation of \" + {sel})); }}, {timeout}); ",
+			"_observer.observe(_target, {{ childList: true, subtree: true, characterData: true, attributes: true }}); }})"),
+			sel = Literal(&self.selector), timeout = millis(self.timeout))
+	}
+}
+impl<SourceTy: QueryCombinator, ClosureTy: QueryCombinator> Display for Then<SourceTy, ClosureTy>
+	where SourceTy::ValueTy: types::Awaitable, ClosureTy::ValueTy: types::Callable
+{
+	fn fmt(&self, fmt: &mut Formatter) -> FmtResult { write!(fmt, "({}).then({})", self.0, self.1) }
+}
+impl Display for Statement
+{
+	fn fmt(&self, fmt: &mut Formatter) -> FmtResult
+	{
+		match *self
+		{
+			Statement::Let(name, ref value) => write!(fmt, "let {} = {};", name, value),
+			Statement::Function(name, param, ref body) => write!(fmt, "function {}({}) {{ return {}; }}", name, param, body)
+		}
+	}
+}
+
+/// Evaluates `apply` on the source, skipping it if the source is nullable and null
+fn evaluate_lifted<SourceTy: Evaluate, F>(nullable: bool, source: &SourceTy, env: &mut Environment, apply: F) -> GenericResult<offline::Value>
+	where F: FnOnce(offline::Value, &mut Environment) -> GenericResult<offline::Value>
+{
+	match source.evaluate(env)?
+	{
+		offline::Value::Null if nullable => Ok(offline::Value::Null),
+		v => apply(v, env)
+	}
+}
+
+// evaluate offline //
+impl<T> Evaluate for CustomExpression<T>
+{
+	fn evaluate(&self, _env: &mut Environment) -> GenericResult<offline::Value>
+	{
+		Err(format!("Custom expressions cannot be evaluated offline: {}", self.0).into())
+	}
+}
+impl Evaluate for Document { fn evaluate(&self, _env: &mut Environment) -> GenericResult<offline::Value> { Ok(offline::Value::Node(0)) } }
+impl<T: Serialize> Evaluate for Literal<T>
+{
+	fn evaluate(&self, _env: &mut Environment) -> GenericResult<offline::Value>
+	{
+		serde_json::to_value(&self.0).map(offline::Value::from_json).map_err(From::from)
+	}
+}
+impl<'s, InnerTy: QueryCombinator> Evaluate for Closure<'s, InnerTy>
+{
+	fn evaluate(&self, _env: &mut Environment) -> GenericResult<offline::Value> { Err("Functions cannot be values offline".into()) }
+	fn call(&self, arg: offline::Value, env: &mut Environment) -> GenericResult<offline::Value>
+	{
+		env.scope(|env| { env.bind(self.0, arg); self.1.evaluate(env) })
+	}
+}
+impl<ParentTy: QueryCombinator> Evaluate for QuerySelector<ParentTy> where ParentTy::ValueTy: types::QueryableElements
+{
+	fn evaluate(&self, env: &mut Environment) -> GenericResult<offline::Value>
+	{
+		evaluate_lifted(<ParentTy::ValueTy as types::Value>::NULLABLE, &self.0, env, |v, env|
+		{
+			let found = env.node(v.into_node("querySelector")?).select_first(&self.1)?;
+			Ok(found.map(|n| offline::Value::Node(n.index())).unwrap_or(offline::Value::Null))
+		})
+	}
+}
+impl<ParentTy: QueryCombinator> Evaluate for QuerySelectorAll<ParentTy> where ParentTy::ValueTy: types::QueryableElements
+{
+	fn evaluate(&self, env: &mut Environment) -> GenericResult<offline::Value>
+	{
+		evaluate_lifted(<ParentTy::ValueTy as types::Value>::NULLABLE, &self.0, env, |v, env|
+		{
+			let found = env.node(v.into_node("querySelectorAll")?).select(&self.1)?;
+			Ok(offline::Value::NodeList(found.into_iter().map(|n| n.index()).collect()))
+		})
+	}
+}
+impl<InnerTy: QueryCombinator> Evaluate for ObjectStringify<InnerTy>
+{
+	fn evaluate(&self, env: &mut Environment) -> GenericResult<offline::Value>
+	{
+		let v = self.0.evaluate(env)?;
+		serde_json::to_string(&v.into_json()).map(offline::Value::String).map_err(From::from)
+	}
+}
+impl<SourceTy: QueryCombinator, ClosureTy: QueryCombinator> Evaluate for ValueMapping<SourceTy, ClosureTy>
+	where ClosureTy::ValueTy: types::Callable
+{
+	fn evaluate(&self, env: &mut Environment) -> GenericResult<offline::Value>
+	{
+		let v = self.0.evaluate(env)?;
+		self.1.call(v, env)
+	}
+}
+impl<SourceTy: QueryCombinator, ClosureTy: QueryCombinator> Evaluate for Mapping<SourceTy, ClosureTy>
+	where SourceTy::ValueTy: types::Iterable, ClosureTy::ValueTy: types::Callable
+{
+	fn evaluate(&self, env: &mut Environment) -> GenericResult<offline::Value>
+	{
+		let mapfn = &self.1;
+		let values = match self.0.evaluate(env)?
+		{
+			offline::Value::Null if <SourceTy::ValueTy as types::Value>::NULLABLE => return Ok(offline::Value::Null),
+			offline::Value::NodeList(v) => v.into_iter().map(offline::Value::Node).collect(),
+			offline::Value::Array(v) => v,
+			v => return Err(offline::type_error("map", &v))
+		};
+		values.into_iter().map(|v| mapfn.call(v, env)).collect::<GenericResult<_>>().map(offline::Value::Array)
+	}
+}
+impl Evaluate for ObjectConstructor
+{
+	fn evaluate(&self, env: &mut Environment) -> GenericResult<offline::Value>
+	{
+		self.0.iter().map(|&(k, ref v)| v.evaluate(env).map(|v| (k.to_owned(), v))).collect::<GenericResult<_>>().map(offline::Value::Object)
+	}
+}
+impl<'s, T> Evaluate for Var<'s, T> { fn evaluate(&self, env: &mut Environment) -> GenericResult<offline::Value> { env.variable(self.0) } }
+impl<'s, RetTy> Evaluate for FunctionRef<'s, RetTy>
+{
+	fn evaluate(&self, _env: &mut Environment) -> GenericResult<offline::Value> { Err("Functions cannot be values offline".into()) }
+	fn call(&self, arg: offline::Value, env: &mut Environment) -> GenericResult<offline::Value> { env.call_function(self.0, arg) }
+}
+impl<ReturnTy: QueryCombinator> Evaluate for Script<ReturnTy>
+{
+	fn evaluate(&self, env: &mut Environment) -> GenericResult<offline::Value>
+	{
+		env.scope(|env|
+		{
+			for s in &self.0
+			{
+				match *s
+				{
+					Statement::Let(name, ref value) => { let v = value.evaluate(env)?; env.bind(name, v); },
+					Statement::Function(name, param, ref body) => env.define_function(name, param, body.clone())
+				}
+			}
+			self.1.evaluate(env)
+		})
+	}
+}
+impl<SourceTy: QueryCombinator, Op> Evaluate for Chain<SourceTy, Op>
+	where SourceTy::ValueTy: types::Value, Op: Operation<types::BaseOf<SourceTy::ValueTy>>
+{
+	fn evaluate(&self, env: &mut Environment) -> GenericResult<offline::Value>
+	{
+		evaluate_lifted(<SourceTy::ValueTy as types::Value>::NULLABLE, &self.0, env, |v, env| self.1.apply(v, env))
+	}
+}
+impl<SourceTy: QueryCombinator, DefaultTy: QueryCombinator> Evaluate for UnwrapOr<SourceTy, DefaultTy>
+{
+	fn evaluate(&self, env: &mut Environment) -> GenericResult<offline::Value>
+	{
+		match self.0.evaluate(env)? { offline::Value::Null => self.1.evaluate(env), v => Ok(v) }
+	}
+}
+// A static page never changes: promises settle at once
+impl Evaluate for WaitFor
+{
+	fn evaluate(&self, env: &mut Environment) -> GenericResult<offline::Value>
+	{
+		match env.document().select_first(&self.selector)?
+		{
+			Some(n) => Ok(offline::Value::Node(n.index())),
+			None => Err(format!("Timed out waiting for {:?}", self.selector).into())
+		}
+	}
+}
+impl<PredicateTy: QueryCombinator> Evaluate for Poll<PredicateTy>
+{
+	fn evaluate(&self, env: &mut Environment) -> GenericResult<offline::Value>
+	{
+		match self.predicate.evaluate(env)?
+		{
+			offline::Value::Null | offline::Value::Boolean(false) => Err("Timed out polling".into()),
+			v => Ok(v)
+		}
+	}
+}
+impl Evaluate for AfterMutation
+{
+	/// The page is taken as already updated
+	fn evaluate(&self, env: &mut Environment) -> GenericResult<offline::Value>
+	{
+		match env.document().select_first(&self.selector)?
+		{
+			Some(n) => Ok(offline::Value::Node(n.index())),
+			None => Err(format!("No element matches {:?}", self.selector).into())
+		}
+	}
+}
+impl<SourceTy: QueryCombinator, ClosureTy: QueryCombinator> Evaluate for Then<SourceTy, ClosureTy>
+	where SourceTy::ValueTy: types::Awaitable, ClosureTy::ValueTy: types::Callable
+{
+	fn evaluate(&self, env: &mut Environment) -> GenericResult<offline::Value>
+	{
+		let v = self.0.evaluate(env)?;
+		self.1.call(v, env)
+	}
+}
+
+#[cfg(test)]
+mod tests
+{
+	use super::*;
+
+	#[test]
+	fn string_literals()
+	{
+		assert_eq!(Literal("a\"b\\c").to_string(), r#""a\"b\\c""#);
+		assert_eq!(Literal("改行\n\ttab").to_string(), r#""改行\n\ttab""#);
+		// Rust's Debug would emit \u{1} and \u{7f}, which are not JavaScript escapes
+		assert_eq!(Literal("\u{1}\u{7f}").to_string(), "\"\\u0001\u{7f}\"");
+		assert_eq!(Literal("a\u{2028}b\u{2029}").to_string(), r#""a\u2028b\u2029""#);
+		assert_eq!(Literal("</script>").to_string(), r#""</script>""#);
+	}
+	#[test]
+	fn other_literals()
+	{
+		assert_eq!(Literal(3).to_string(), "3");
+		assert_eq!(Literal(1.5).to_string(), "1.5");
+		assert_eq!(Literal(true).to_string(), "true");
+		assert_eq!(Literal(vec!["a", "b"]).to_string(), r#"["a","b"]"#);
+		assert_eq!(Literal(&[1, 2][..]).to_string(), "[1,2]");
+	}
+	#[test]
+	fn selectors_are_literals()
+	{
+		let q = Document.query_selector(r#"a[title="x\y"]"#.to_owned()).query_selector_all("td".into());
+		assert_eq!(q.to_string(), r#"(_v => _v == null ? null : _v.querySelectorAll("td"))((document).querySelector("a[title=\"x\\y\"]"))"#);
+	}
+	#[test]
+	fn element_ops()
+	{
+		let q = var::<types::Element>("r").query_selector_all("td".into()).index(0).text_content();
+		assert_eq!(q.to_string(), r#"(_v => _v == null ? null : _v.textContent)(((r).querySelectorAll("td"))[0])"#);
+		assert_eq!(var::<types::Element>("r").attribute("onclick").to_string(), r#"(r).getAttribute("onclick")"#);
+		assert_eq!(var::<types::Element>("r").inner_text().to_string(), "(r).innerText");
+		assert_eq!(var::<types::Element>("i").property::<types::Boolean>("checked").to_string(), r#"(i)["checked"]"#);
+	}
+	#[test]
+	fn value_ops()
+	{
+		let cells = || var::<types::Array<types::String>>("cells");
+		assert_eq!(cells().index(2).parse_int().to_string(), "parseInt(((cells)[2]), 10)");
+		assert_eq!(cells().slice(5, None).to_string(), "(cells).slice(5)");
+		assert_eq!(var::<types::String>("s").slice(0, Some(-1)).parse_float().to_string(), "parseFloat(((s).slice(0, -1)))");
+		assert_eq!(var::<types::String>("s").replace(r"(\d+)/(\d+)", "$1-$2").to_string(),
+			r#"(s).replace(new RegExp("(\\d+)/(\\d+)", ""), "$1-$2")"#);
+		assert_eq!(var::<types::String>("s").replace_all("\"", "").to_string(), r#"(s).replace(new RegExp("\"", "g"), "")"#);
+	}
+	#[test]
+	fn object_constructor()
+	{
+		let o = jsqGenObject!{ a: var::<types::Array<types::String>>("cells").index(0), b: Literal(0) };
+		assert_eq!(o.into_closure("cells").to_string(), "cells => ({ a: (cells)[0],b: 0 })");
+	}
+	fn assert_type<T, Q: QueryCombinator<ValueTy = T>>(_: &Q) {}
+	#[test]
+	fn null_propagation()
+	{
+		// the whole chain is null if the link is missing
+		let title = var::<types::Element>("k").query_selector("a".into()).text_content().trim();
+		assert_type::<types::Optional<types::String>, _>(&title);
+		assert_eq!(title.to_string(),
+			r#"(_v => _v == null ? null : _v.trim())((_v => _v == null ? null : _v.textContent)((k).querySelector("a")))"#);
+		// attributes are optional, and stay optional only once
+		let onclick = var::<types::Element>("r").attribute("onclick").replace("^javascript:", "");
+		assert_type::<types::Optional<types::String>, _>(&onclick);
+		let n = var::<types::Element>("r").attribute("colspan").unwrap_or(Literal("1")).parse_int();
+		assert_type::<types::Number, _>(&n);
+		assert_eq!(n.to_string(), r#"parseInt(((_v => _v == null ? "1" : _v)((r).getAttribute("colspan"))), 10)"#);
+	}
+	#[test]
+	fn optional_decoding()
+	{
+		use serde_json::Value as JValue;
+		use self::types::Decode;
+		fn js(text: &str) -> JValue { serde_json::from_str(text).unwrap() }
+		assert_eq!(<types::Optional<types::String> as Decode<Option<String>>>::decode(JValue::Null).unwrap(), None);
+		assert_eq!(<types::Optional<types::String> as Decode<Option<String>>>::decode(js(r#""a""#)).unwrap(), Some("a".to_owned()));
+		let v = <types::Array<types::Optional<types::Number>> as Decode<Vec<Option<u32>>>>::decode(js("[1, null]")).unwrap();
+		assert_eq!(v, vec![Some(1), None]);
+		assert!(<types::Optional<types::Number> as Decode<Option<u32>>>::decode(js(r#""x""#)).is_err());
+	}
+	#[test]
+	fn statements()
+	{
+		let mut s = Statements::new();
+		let title = s.define("title", "k", |k: Var<types::Element>| k.query_selector("a".into()).text_content());
+		let tables = s.bind("tables", Document.query_selector_all("table".into()));
+		let q = s.finish(tables.index(1).query_selector_all("td".into()).map(title).chunks(2).stringify());
+		assert_type::<types::Json, _>(&q);
+		assert_eq!(q.to_string(), concat!("(() => {\n",
+			r#"function title(k) { return (_v => _v == null ? null : _v.textContent)((k).querySelector("a")); }"#, "\n",
+			r#"let tables = (document).querySelectorAll("table");"#, "\n",
+			r#"return JSON.stringify((_v => _v == null ? null : (a => { var r = []; for(var i = 0; i < a.length; i += 2) r.push(a.slice(i, i + 2)); return r; })(_v))"#,
+			r#"((_v => _v == null ? null : Array.prototype.map.call(_v, title))((_v => _v == null ? null : _v.querySelectorAll("td"))((tables)[1]))));"#,
+			"\n})()"));
+	}
+	#[test]
+	fn chunks()
+	{
+		let rows = var::<types::Array<types::String>>("cells").chunks(3).map_auto("row", var::<types::Array<types::String>>("row").index(0));
+		assert_type::<types::Array<types::String>, _>(&rows);
+		assert_eq!(rows.to_string(),
+			"((a => { var r = []; for(var i = 0; i < a.length; i += 3) r.push(a.slice(i, i + 3)); return r; })((cells))).map(row => (row)[0])");
+	}
+	#[test]
+	fn promises()
+	{
+		use js_syntax::{validate, validate_query};
+		use offline::StaticPage;
+
+		let waited = wait_for("#dg", Duration::from_millis(1500)).then_auto("t", var::<types::Element>("t").query_selector_all("td".into()).index(0).text_content());
+		assert_type::<types::Promise<types::Optional<types::String>>, _>(&waited);
+		assert!(waited.to_string().ends_with(r#"}, 1500); _observer.observe(document, { childList: true, subtree: true }); })).then(t => (_v => _v == null ? null : _v.textContent)(((t).querySelectorAll("td"))[0]))"#));
+		let polled = poll(Document.query_selector("#dg".into()), Duration::from_millis(100)).timeout(Duration::from_secs(2));
+		assert_type::<types::Promise<types::Element>, _>(&polled);
+		assert!(polled.to_string().contains("Date.now() - _started >= 2000") && polled.to_string().contains("setTimeout(_tick, 100)"));
+		let mutated = after_mutation("#list");
+		for s in &[waited.to_string(), polled.to_string(), mutated.to_string()] { if let Err(e) = validate(s) { panic!("{}\n{}", s, e); } }
+		validate_query(&mutated).unwrap();
+
+		// a static page is settled: resolved if present, rejected otherwise
+		let mut page = StaticPage::parse("about:blank", "<table id=dg><tr><td>x</td></tr></table>");
+		let text: Option<String> = page.execute(None, waited).unwrap();
+		assert_eq!(text, Some("x".to_owned()));
+		assert!(page.execute::<_, Option<String>>(None, wait_for("#none", Duration::from_secs(1)).then_auto("t", var::<types::Element>("t").inner_text())).is_err());
+		let polled: String = page.execute(None, poll(Document.query_selector("#dg td".into()).text_content(), Duration::from_millis(10))).unwrap();
+		assert_eq!(polled, "x");
+		let found: usize = page.execute(None, after_mutation("#dg").then_auto("t", var::<types::Element>("t").query_selector_all("td".into()).property::<types::Number>("length"))).unwrap();
+		assert_eq!(found, 1);
+		assert!(page.execute::<_, bool>(None, poll(Literal(false), Duration::from_millis(10))).is_err());
+	}
+}
@@ -5,6 +5,9 @@ extern crate websocket;
 extern crate serde; extern crate serde_json;
 #[macro_use] extern crate serde_derive;
 extern crate chrono;
+extern crate regex;
+#[macro_use] extern crate dc_web_derive;
+#[cfg(unix)] extern crate libc;
 
 #[cfg(feature = "verbose")] extern crate colored;
 
@@ -16,8 +19,20 @@ macro_rules! api_corruption
 	(invalid_format) => (panic!("Invalid JSON format. the API may be corrupted"))
 }
 
+pub mod logging;
 pub mod headless_chrome;
-#[macro_use] mod jsquery;
+pub mod frame_tracker;
+pub mod remote_object;
+pub mod dom_tree;
+pub mod html;
+pub mod browser;
+#[macro_use] pub mod jsquery;
+mod helper_library;
+#[macro_use] pub mod table_row;
+pub mod offline;
+pub mod js_syntax;
 mod remote_campus;
+mod tab_pool;
 
 pub use remote_campus::*;
+pub use tab_pool::*;
@@ -0,0 +1,86 @@
+//! Logging facade for protocol traffic
+
+use serde_json;
+use serde_json::Value as JValue;
+#[cfg(feature = "verbose")] use colored::*;
+
+/// Replacement text of masked values
+pub const REDACTED: &'static str = "<redacted>";
+
+/// Verbosity of a log message
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Level { Error, Warn, Info, Debug, Trace }
+
+/// Receives log messages. `target` tells where the message came from("send", "recv", ...)
+pub trait Logger
+{
+	fn enabled(&self, level: Level) -> bool;
+	fn log(&self, level: Level, target: &str, message: &str);
+}
+/// Discards everything
+pub struct NullLogger;
+impl Logger for NullLogger
+{
+	fn enabled(&self, _: Level) -> bool { false }
+	fn log(&self, _: Level, _: &str, _: &str) {}
+}
+/// Prints to the standard output(colored with `verbose` feature)
+pub struct ConsoleLogger { pub max_level: Level }
+impl Logger for ConsoleLogger
+{
+	fn enabled(&self, level: Level) -> bool { level <= self.max_level }
+	#[cfg(feature = "verbose")]
+	fn log(&self, level: Level, target: &str, message: &str)
+	{
+		if !self.enabled(level) { return; }
+		match target
+		{
+			"send" => println!("{}", format!("-->> [{:?}]{}", level, message).green().bold()),
+			"recv" => println!("{}", format!("<<-- [{:?}]{}", level, message).blue().bold()),
+			_ => println!("[{:?}][{}]{}", level, target, message)
+		}
+	}
+	#[cfg(not(feature = "verbose"))]
+	fn log(&self, level: Level, target: &str, message: &str)
+	{
+		if self.enabled(level) { println!("[{:?}][{}]{}", level, target, message); }
+	}
+}
+/// Logger used by sessions unless replaced: everything with `verbose` feature, nothing otherwise
+pub fn default_logger() -> Box<Logger>
+{
+	if cfg!(feature = "verbose") { Box::new(ConsoleLogger { max_level: Level::Trace }) } else { Box::new(NullLogger) }
+}
+
+/// Masks parameters and results of a protocol message, keeping the method name and the request id
+pub fn redact_message(text: &str) -> String
+{
+	match serde_json::from_str(text)
+	{
+		Ok(JValue::Object(mut o)) =>
+		{
+			for k in &["params", "result"]
+			{
+				if o.contains_key(*k) { o.insert((*k).to_owned(), JValue::String(REDACTED.to_owned())); }
+			}
+			serde_json::to_string(&o).unwrap()
+		},
+		_ => REDACTED.to_owned()
+	}
+}
+/// Masks the request bodies carried by `Network.*` events(posted forms may contain passwords).
+/// Returns `None` if nothing has been masked
+pub fn redact_request_bodies(text: &str) -> Option<String>
+{
+	let mut v: JValue = match serde_json::from_str(text) { Ok(v) => v, Err(_) => return None };
+	if !v["method"].as_str().map(|m| m.starts_with("Network.")).unwrap_or(false) { return None; }
+	let mut masked = false;
+	if let Some(r) = v["params"]["request"].as_object_mut()
+	{
+		for k in &["postData", "postDataEntries"]
+		{
+			if r.contains_key(*k) { r.insert((*k).to_owned(), JValue::String(REDACTED.to_owned())); masked = true; }
+		}
+	}
+	if masked { Some(serde_json::to_string(&v).unwrap()) } else { None }
+}
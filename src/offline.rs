@@ -0,0 +1,305 @@
+//! Offline backend: evaluates jsquery fragments against a `DomTree` parsed in Rust(no browser)
+//!
+//! Layout-dependent things(`innerText` rendering, `click`) are not available: `innerText` is the text content and `click` fails
+
+use std::rc::Rc;
+use std::error::Error;
+use serde_json::{Value as JValue, Number as JNumber};
+use regex::Regex;
+use dom_tree::{DomTree, DomNode};
+use jsquery::{QueryCombinator, Fragment, Executor};
+use jsquery::types::Decode;
+use html;
+use GenericResult;
+
+/// JavaScript value
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value
+{
+	/// `null` or `undefined`
+	Null, Boolean(bool), Number(f64), String(String),
+	/// Node in the document(index in the tree)
+	Node(usize), NodeList(Vec<usize>),
+	Array(Vec<Value>), Object(Vec<(String, Value)>)
+}
+impl Value
+{
+	pub fn from_json(value: JValue) -> Self
+	{
+		match value
+		{
+			JValue::Null => Value::Null, JValue::Bool(b) => Value::Boolean(b),
+			JValue::Number(n) => Value::Number(n.as_f64().unwrap_or(::std::f64::NAN)),
+			JValue::String(s) => Value::String(s),
+			JValue::Array(v) => Value::Array(v.into_iter().map(Value::from_json).collect()),
+			JValue::Object(o) => Value::Object(o.into_iter().map(|(k, v)| (k, Value::from_json(v))).collect())
+		}
+	}
+	/// By-value representation(as `returnByValue` does). Nodes are empty objects, NaN and infinities are null
+	pub fn into_json(self) -> JValue
+	{
+		match self
+		{
+			Value::Null => JValue::Null, Value::Boolean(b) => JValue::Bool(b), Value::String(s) => JValue::String(s),
+			Value::Number(n) if n.is_finite() && n.fract() == 0.0 && n.abs() < 9007199254740992.0 => JValue::Number(JNumber::from(n as i64)),
+			Value::Number(n) => JNumber::from_f64(n).map(JValue::Number).unwrap_or(JValue::Null),
+			Value::Node(_) => JValue::Object(Default::default()),
+			Value::NodeList(v) => JValue::Array(v.into_iter().map(|_| JValue::Object(Default::default())).collect()),
+			Value::Array(v) => JValue::Array(v.into_iter().map(Value::into_json).collect()),
+			Value::Object(o) => JValue::Object(o.into_iter().map(|(k, v)| (k, v.into_json())).collect())
+		}
+	}
+	pub fn type_name(&self) -> &'static str
+	{
+		match *self
+		{
+			Value::Null => "null", Value::Boolean(_) => "boolean", Value::Number(_) => "number", Value::String(_) => "string",
+			Value::Node(_) => "node", Value::NodeList(_) => "NodeList", Value::Array(_) => "array", Value::Object(_) => "object"
+		}
+	}
+
+	pub fn into_node(self, op: &str) -> GenericResult<usize>
+	{
+		match self { Value::Node(n) => Ok(n), v => Err(type_error(op, &v)) }
+	}
+	pub fn into_string(self, op: &str) -> GenericResult<String>
+	{
+		match self { Value::String(s) => Ok(s), v => Err(type_error(op, &v)) }
+	}
+	pub fn into_array(self, op: &str) -> GenericResult<Vec<Value>>
+	{
+		match self { Value::Array(v) => Ok(v), v => Err(type_error(op, &v)) }
+	}
+}
+/// TypeError of an operation applied to a value of a wrong type
+pub fn type_error(op: &str, value: &Value) -> Box<Error>
+{
+	format!("TypeError: {} cannot be applied to {}", op, value.type_name()).into()
+}
+
+/// Variables and functions in scope while evaluating
+pub struct Environment<'t>
+{
+	document: &'t DomTree,
+	variables: Vec<(String, Value)>,
+	functions: Vec<(&'static str, &'static str, Rc<Fragment>)>
+}
+impl<'t> Environment<'t>
+{
+	pub fn new(document: &'t DomTree) -> Self { Environment { document, variables: Vec::new(), functions: Vec::new() } }
+	pub fn document(&self) -> &'t DomTree { self.document }
+	pub fn node(&self, index: usize) -> DomNode<'t> { self.document.node(index).expect("Node out of the document") }
+
+	/// Runs `f` in a new scope: bindings made in it are dropped after
+	pub fn scope<F, R>(&mut self, f: F) -> R where F: FnOnce(&mut Self) -> R
+	{
+		let (nv, nf) = (self.variables.len(), self.functions.len());
+		let r = f(self);
+		self.variables.truncate(nv); self.functions.truncate(nf);
+		r
+	}
+	/// Binds a variable in the current scope
+	pub fn bind(&mut self, name: &str, value: Value) { self.variables.push((name.to_owned(), value)); }
+	pub fn variable(&self, name: &str) -> GenericResult<Value>
+	{
+		self.variables.iter().rev().find(|&&(ref n, _)| n == name).map(|&(_, ref v)| v.clone())
+			.ok_or_else(|| format!("ReferenceError: {} is not defined", name).into())
+	}
+	/// Defines a function(`function name(param) { return body; }`) in the current scope
+	pub fn define_function(&mut self, name: &'static str, param: &'static str, body: Rc<Fragment>)
+	{
+		self.functions.push((name, param, body));
+	}
+	pub fn call_function(&mut self, name: &str, arg: Value) -> GenericResult<Value>
+	{
+		let (param, body) = match self.functions.iter().rev().find(|&&(n, _, _)| n == name)
+		{
+			Some(&(_, param, ref body)) => (param, body.clone()),
+			None => return Err(format!("ReferenceError: {} is not defined", name).into())
+		};
+		self.scope(|env| { env.bind(param, arg); body.evaluate(env) })
+	}
+}
+
+/// Evaluates the query against the document
+pub fn evaluate<Q: QueryCombinator>(document: &DomTree, query: &Q) -> GenericResult<Value>
+{
+	query.evaluate(&mut Environment::new(document))
+}
+
+/// Saved or downloaded page(the offline counterpart of `RemoteCampus`).
+/// Frames are not distinguished: contexts are ignored and every query runs against this document
+pub struct StaticPage { document: DomTree }
+impl StaticPage
+{
+	pub fn new(document: DomTree) -> Self { StaticPage { document } }
+	/// Parses the HTML text of the page
+	pub fn parse(url: &str, html: &str) -> Self { StaticPage::new(html::parse(url, html)) }
+	pub fn document(&self) -> &DomTree { &self.document }
+}
+impl Executor for StaticPage
+{
+	fn execute<Q: QueryCombinator, T>(&mut self, _context: Option<u64>, query: Q) -> GenericResult<T> where Q::ValueTy: Decode<T>
+	{
+		let v = evaluate(&self.document, &query)?;
+		<Q::ValueTy as Decode<T>>::decode(v.into_json())
+	}
+}
+
+// JavaScript builtins //
+/// `parseInt(s, 10)`: the leading integer, NaN if there is none
+pub fn parse_int(s: &str) -> f64
+{
+	let s = s.trim_left();
+	let (sign, digits) = if s.starts_with('-') { (-1.0, &s[1..]) } else if s.starts_with('+') { (1.0, &s[1..]) } else { (1.0, s) };
+	let end = digits.find(|c: char| !c.is_ascii_digit()).unwrap_or(digits.len());
+	if end == 0 { ::std::f64::NAN } else { sign * digits[..end].parse::<f64>().unwrap_or(::std::f64::NAN) }
+}
+/// `parseFloat(s)`: the leading decimal number, NaN if there is none
+pub fn parse_float(s: &str) -> f64
+{
+	let s = s.trim_left();
+	let (sign, rest) = if s.starts_with('-') { (-1.0, &s[1..]) } else if s.starts_with('+') { (1.0, &s[1..]) } else { (1.0, s) };
+	if rest.starts_with("Infinity") { return sign * ::std::f64::INFINITY; }
+	let number = Regex::new(r"^([0-9]+\.?[0-9]*|\.[0-9]+)([eE][+-]?[0-9]+)?").unwrap();
+	number.find(rest).and_then(|m| m.as_str().parse::<f64>().ok()).map(|n| sign * n).unwrap_or(::std::f64::NAN)
+}
+/// Translates a JavaScript regular expression into the `regex` syntax(`\d` and `\w` are ASCII only in JavaScript)
+pub fn regex(pattern: &str, flags: &str) -> GenericResult<Regex>
+{
+	let mut translated = String::with_capacity(pattern.len());
+	if flags.contains('i') { translated.push_str("(?i)"); }
+	let (mut chars, mut in_class) = (pattern.chars(), false);
+	while let Some(c) = chars.next()
+	{
+		match c
+		{
+			'\\' => match chars.next()
+			{
+				Some('d') => translated.push_str(if in_class { "0-9" } else { "[0-9]" }),
+				Some('D') if !in_class => translated.push_str("[^0-9]"),
+				Some('w') => translated.push_str(if in_class { "0-9A-Za-z_" } else { "[0-9A-Za-z_]" }),
+				Some('W') if !in_class => translated.push_str("[^0-9A-Za-z_]"),
+				// escaped punctuations are literals(`\/` is not an escape in `regex`)
+				Some(e) if !e.is_alphanumeric() => translated.push_str(&::regex::escape(&e.to_string())),
+				Some(e) => { translated.push('\\'); translated.push(e); },
+				None => translated.push_str(r"\\")
+			},
+			'[' if !in_class => { in_class = true; translated.push(c); },
+			']' if in_class => { in_class = false; translated.push(c); },
+			_ => translated.push(c)
+		}
+	}
+	Regex::new(&translated).map_err(|e| format!("SyntaxError: Invalid regular expression /{}/: {}", pattern, e).into())
+}
+/// `String.prototype.replace` with a regular expression(`g` in the flags replaces every match)
+pub fn replace(s: &str, pattern: &str, flags: &str, replacement: &str) -> GenericResult<String>
+{
+	// `$n`/`$&` in JavaScript are `${n}`/`${0}` in `regex`, and other `$`s are literals
+	let mut expansion = String::with_capacity(replacement.len());
+	let mut chars = replacement.chars().peekable();
+	while let Some(c) = chars.next()
+	{
+		if c != '$' { expansion.push(c); continue; }
+		match chars.peek().cloned()
+		{
+			Some('&') => { chars.next(); expansion.push_str("${0}"); },
+			Some('$') => { chars.next(); expansion.push_str("$$"); },
+			Some(d) if d.is_ascii_digit() =>
+			{
+				expansion.push_str("${");
+				while let Some(d) = chars.peek().cloned() { if !d.is_ascii_digit() { break; } expansion.push(d); chars.next(); }
+				expansion.push('}');
+			},
+			_ => expansion.push_str("$$")
+		}
+	}
+	let r = regex(pattern, flags)?;
+	Ok(if flags.contains('g') { r.replace_all(s, &expansion as &str) } else { r.replace(s, &expansion as &str) }.into_owned())
+}
+/// `slice(start, end)` indices(negative ones count from the end)
+pub fn slice_range(len: usize, start: i64, end: Option<i64>) -> (usize, usize)
+{
+	let clamp = |i: i64| if i < 0 { (len as i64 + i).max(0) as usize } else { (i as usize).min(len) };
+	let (s, e) = (clamp(start), end.map(clamp).unwrap_or(len));
+	(s, e.max(s))
+}
+
+#[cfg(test)]
+mod tests
+{
+	use super::*;
+	use jsquery as jsq;
+	use jsquery::types;
+	use helper_library;
+
+	fn page() -> StaticPage
+	{
+		StaticPage::parse("about:blank", r#"<div id="list"><table>
+			<tr class="pointer" onclick="javascript:open(1)"><td> 2018/04/01 </td><td>a</td><td>３</td></tr>
+			<tr class="pointer"><td>2018/04/02 12:30</td><td></td><td>10</td></tr>
+		</table></div>"#)
+	}
+
+	#[test]
+	fn builtins()
+	{
+		assert_eq!(parse_int(" 42px"), 42.0);
+		assert_eq!(parse_int("-7"), -7.0);
+		assert!(parse_int("４２").is_nan());
+		assert_eq!(parse_float("95.5%"), 95.5);
+		assert_eq!(parse_float(".5e1"), 5.0);
+		assert!(parse_float("abc").is_nan());
+		assert_eq!(replace("2018/04/01", r"(\d+)/(\d+)/(\d+)", "", "$1-$2-$3T00:00:00Z").unwrap(), "2018-04-01T00:00:00Z");
+		assert_eq!(replace(r#"a"b"c"#, "\"", "g", "").unwrap(), "abc");
+		assert_eq!(replace("aAa", "a", "i", "[$&]$").unwrap(), "[a]$Aa");
+		assert_eq!(replace("a/b", r"\/", "", "-").unwrap(), "a-b");
+		// \d is ASCII only in JavaScript
+		assert_eq!(replace("１2", r"\d", "g", "x").unwrap(), "１x");
+		assert_eq!(slice_range(5, -2, None), (3, 5));
+		assert_eq!(slice_range(5, 4, Some(1)), (4, 4));
+	}
+	#[test]
+	fn queries()
+	{
+		let mut p = page();
+		let cells: Vec<Vec<String>> = p.execute(None, jsq::Document.query_selector_all("tr".into())
+			.map_auto("r", jsq::var::<types::Element>("r").query_selector_all("td".into())
+				.map_auto("x", jsq::var::<types::Element>("x").text_content().trim()))).unwrap();
+		assert_eq!(cells, vec![vec!["2018/04/01", "a", "３"], vec!["2018/04/02 12:30", "", "10"]]);
+		let missing: Option<String> = p.execute(None, jsq::Document.query_selector("#none".into()).text_content()).unwrap();
+		assert_eq!(missing, None);
+		let onclick: Vec<Option<String>> = p.execute(None, jsq::Document.query_selector_all("tr".into())
+			.map_auto("r", jsq::var::<types::Element>("r").attribute("onclick").replace("^javascript:", ""))).unwrap();
+		assert_eq!(onclick, vec![Some("open(1)".to_owned()), None]);
+		let digits: u32 = p.execute(None, helper_library::to_half_width_digits(jsq::Literal("３")).parse_int()).unwrap();
+		assert_eq!(digits, 3);
+		let json: String = p.execute(None, jsqGenObject!{ n: jsq::Literal("x").parse_float(), a: jsq::Literal(vec![1, 2, 3]).chunks(2) }).map(|v: JValue| v.to_string()).unwrap();
+		assert_eq!(json, r#"{"a":[[1,2],[3]],"n":null}"#);
+	}
+	#[test]
+	fn table_rows()
+	{
+		#[derive(TableRow, Debug, PartialEq)]
+		struct Row
+		{
+			#[column(0, date)] date: String, #[column(0, datetime)] at: String, #[column(1)] title: String,
+			#[column(2, digits)] time: u32, #[onclick] script: Option<String>
+		}
+		let rows: Vec<Row> = page().execute(None, jsq::Document.query_selector_all("tr.pointer".into()).map(::table_row::extractor::<Row>())).unwrap();
+		// `date` converts only a whole date(the cell with a time is left as is, and fails as a `DateTime`)
+		assert_eq!(rows, vec![
+			Row { date: "2018-04-01T00:00:00Z".to_owned(), at: "2018-04-01T00:00:00Z".to_owned(), title: "a".to_owned(), time: 3, script: Some("open(1)".to_owned()) },
+			Row { date: "2018/04/02 12:30".to_owned(), at: "2018-04-02T12:30:00Z".to_owned(), title: "".to_owned(), time: 10, script: None }
+		]);
+	}
+	#[test]
+	fn errors()
+	{
+		let mut p = page();
+		let r: GenericResult<String> = p.execute(None, jsq::Document.query_selector("#none".into()).text_content().unwrap_or(jsq::var("x")));
+		assert!(r.unwrap_err().to_string().contains("ReferenceError"));
+		let r: GenericResult<Option<bool>> = p.execute(None, jsq::Document.query_selector("tr".into()).click());
+		assert!(r.is_err());
+	}
+}
@@ -3,45 +3,242 @@
 #![allow(dead_code)]
 
 use {headless_chrome, GenericResult};
-use headless_chrome::{Event, RequestID};
-use std::net::TcpStream;
+use headless_chrome::{RequestID, SessionEvent};
 use serde_json;
-use serde_json::Value as JValue;
 use std::marker::PhantomData;
-use std::mem::{replace, transmute};
+use std::rc::Rc;
+use std::cell::RefCell;
+use std::mem::transmute;
 use chrono::prelude::*;
 
-use headless_chrome::{page, runtime};
-use headless_chrome::runtime::JSONTyping;
+use headless_chrome::{page, runtime, security, network};
+use headless_chrome::runtime::ExecutionContextID;
 use jsquery as jsq;
 use jsquery::QueryCombinator;
+use frame_tracker::{FrameTracker, FrameQuery};
+use remote_object::RemoteObjectHandle;
+use dom_tree::DomTree;
+use helper_library;
+use table_row::{self, TableRow};
+use table_row::convert::text as cell;
+use logging::Logger;
 
-pub struct RemoteCampus { session: headless_chrome::Session<TcpStream, TcpStream>, request_id: RequestID }
+/// Settings applied to a session on connection(and replayed on reconnection)
+#[derive(Debug, Clone, Default)]
+pub struct SessionOptions
+{
+	pub ua_override: Option<String>,
+	/// Loads pages served with invalid certificates(e.g. a local stand-in server with a self-signed certificate)
+	pub ignore_certificate_errors: bool,
+	/// Emulated link quality(`None` leaves the network as is)
+	pub network_conditions: Option<network::NetworkConditions>,
+	pub cache_disabled: bool
+}
+impl SessionOptions
+{
+	pub fn with_ua(ua_override: Option<&str>) -> Self
+	{
+		SessionOptions { ua_override: ua_override.map(ToOwned::to_owned), .. Default::default() }
+	}
+}
+
+/// Page controller. Scripts run in an isolated world of the frame unless disabled by `set_script_isolation`.
+/// Queries run by `execute` are compiled once per context unless disabled by `set_script_cache`
+pub struct RemoteCampus
+{
+	session: headless_chrome::Session, request_id: RequestID, options: Rc<RefCell<SessionOptions>>,
+	isolate_scripts: bool, cache_scripts: bool
+}
 impl RemoteCampus
 {
+	/// Name of the isolated world running the scraping scripts
+	pub const ISOLATED_WORLD_NAME: &'static str = "dc_web";
+	/// Computed styles captured by `snapshot`
+	pub const SNAPSHOT_STYLES: &'static [&'static str] = &["display", "visibility"];
+
 	pub fn connect(addr: &str, ua_override: Option<&str>) -> GenericResult<Self>
 	{
-		let mut object = headless_chrome::Session::connect(addr).map(|session| RemoteCampus { session, request_id: 1 })?;
-		object.session.page().enable(0)?; object.session.wait_result(0)?;
-		object.session.dom().enable(0)?; object.session.wait_result(0)?;
-		object.session.runtime().enable(0)?; object.session.wait_result(0)?;
-		if let Some(ua) = ua_override
+		Self::connect_with_options(addr, SessionOptions::with_ua(ua_override))
+	}
+	pub fn connect_with_options(addr: &str, options: SessionOptions) -> GenericResult<Self>
+	{
+		headless_chrome::Session::connect(addr).and_then(|session| Self::with_session_options(session, options))
+	}
+	/// Sets up a controller on an already connected session(e.g. one multiplexed on a `BrowserConnection`)
+	pub fn with_session(session: headless_chrome::Session, ua_override: Option<&str>) -> GenericResult<Self>
+	{
+		Self::with_session_options(session, SessionOptions::with_ua(ua_override))
+	}
+	pub fn with_session_options(mut session: headless_chrome::Session, options: SessionOptions) -> GenericResult<Self>
+	{
+		Self::setup_session(&mut session, &options)?;
+		// the same setup is replayed when the connection is re-established(with the settings changed since then)
+		let options = Rc::new(RefCell::new(options));
+		let hook_options = options.clone();
+		session.set_reconnect_hook(Box::new(move |s| Self::setup_session(s, &hook_options.borrow())));
+		Ok(RemoteCampus { session, request_id: 1, options, isolate_scripts: true, cache_scripts: true })
+	}
+	fn setup_session(session: &mut headless_chrome::Session, options: &SessionOptions) -> GenericResult<()>
+	{
+		session.page().enable(0)?; session.wait_result(0)?;
+		session.dom().enable(0)?; session.wait_result(0)?;
+		session.sync_frame_tree(0)?;
+		session.runtime().enable(0)?; session.wait_result(0)?;
+		session.inspector().enable(0)?; session.wait_result(0)?;
+		session.security().enable(0)?; session.wait_result(0)?;
+		// helpers for the scripts in both of the page's world and the isolated world
+		let helpers = helper_library::script();
+		session.page().add_script_to_evaluate_on_new_document_sync(0, &helpers, None)?;
+		session.page().add_script_to_evaluate_on_new_document_sync(0, &helpers, Some(Self::ISOLATED_WORLD_NAME))?;
+		if options.ignore_certificate_errors
+		{
+			session.security().set_ignore_certificate_errors(0, true)?;
+			session.wait_result(0)?;
+		}
+		if let Some(ref ua) = options.ua_override
+		{
+			session.network().set_user_agent_override(0, ua)?;
+			session.wait_result(0)?;
+		}
+		if options.network_conditions.is_some() || options.cache_disabled
+		{
+			session.network().enable(0)?; session.wait_result(0)?;
+		}
+		if let Some(ref c) = options.network_conditions
 		{
-			object.session.network().set_user_agent_override(0, ua)?;
-			object.session.wait_result(0)?;
+			session.network().emulate_network_conditions(0, c)?; session.wait_result(0)?;
 		}
-		Ok(object)
+		if options.cache_disabled { session.network().set_cache_disabled(0, true)?; session.wait_result(0)?; }
+		Ok(())
+	}
+	/// Emulates offline mode, latency and throughput. `None` restores the real network
+	pub fn emulate_network(&mut self, conditions: Option<network::NetworkConditions>) -> GenericResult<&mut Self>
+	{
+		let id = self.new_request_id();
+		self.session.network().enable(id)?; self.session.wait_result(id)?;
+		let id = self.new_request_id();
+		let c = conditions.clone().unwrap_or_else(network::NetworkConditions::no_throttling);
+		self.session.network().emulate_network_conditions(id, &c)?; self.session.wait_result(id)?;
+		self.options.borrow_mut().network_conditions = conditions;
+		Ok(self)
+	}
+	/// Makes every request bypass the cache
+	pub fn set_cache_disabled(&mut self, disabled: bool) -> GenericResult<&mut Self>
+	{
+		let id = self.new_request_id();
+		self.session.network().enable(id)?; self.session.wait_result(id)?;
+		let id = self.new_request_id();
+		self.session.network().set_cache_disabled(id, disabled)?; self.session.wait_result(id)?;
+		self.options.borrow_mut().cache_disabled = disabled;
+		Ok(self)
+	}
+	/// Number of times the connection has been re-established.
+	/// Pending operations fail with `ConnectionError::Reconnected` on reconnection, then the page state should be re-synchronized
+	pub fn reconnections(&self) -> u32 { self.session.reconnections() }
+	pub fn set_reconnect_policy(&mut self, policy: Option<headless_chrome::ReconnectPolicy>) -> &mut Self
+	{
+		self.session.set_reconnect_policy(policy); self
+	}
+	/// Watches the browser process and sets the behavior on crashes.
+	/// Waits fail with `ConnectionError::BrowserGone` unless resumed by `CrashPolicy::RelaunchAndResume`
+	pub fn watch_process(&mut self, process: Rc<RefCell<headless_chrome::Process>>, policy: headless_chrome::CrashPolicy) -> &mut Self
+	{
+		self.session.watch_process(process); self.session.set_crash_policy(policy); self
 	}
 	fn new_request_id(&mut self) -> RequestID
 	{
 		let r = self.request_id; self.request_id += 1; r
 	}
+	/// Runs operations carrying secrets(passwords, login ids). Their messages are masked in the protocol log
+	pub fn sensitive<T, F: FnOnce(&mut Self) -> T>(&mut self, f: F) -> T
+	{
+		let prev = self.session.set_sensitive(true);
+		let r = f(self);
+		self.session.set_sensitive(prev); r
+	}
+	/// Replaces the protocol traffic logger
+	pub fn set_logger(&mut self, logger: Box<Logger>) -> &mut Self
+	{
+		self.session.set_logger(logger); self
+	}
+	/// Frames and execution contexts observed so far
+	pub fn frames(&self) -> &FrameTracker { self.session.frames() }
+	/// The default execution context of the frame
+	pub fn frame_context(&self, query: FrameQuery) -> Option<ExecutionContextID> { self.session.frames().context_of(query) }
+	/// JavaScript dialog handling policy(dismisses all dialogs by default)
+	pub fn set_dialog_policy(&mut self, policy: headless_chrome::DialogPolicy) -> &mut Self
+	{
+		self.session.set_dialog_policy(policy); self
+	}
+	/// Dialogs opened so far(error messages of the site are often shown by `alert`)
+	pub fn dialogs(&self) -> &[page::JavascriptDialogOpening] { self.session.dialogs() }
+	/// Takes the recorded dialogs out
+	pub fn take_dialogs(&mut self) -> Vec<page::JavascriptDialogOpening> { self.session.take_dialogs() }
+	/// Restores the settings changed through the controller(dialog policy, network emulation, cache, script isolation and caching)
+	/// and forgets the recorded dialogs and security issues
+	pub fn reset_settings(&mut self) -> GenericResult<&mut Self>
+	{
+		self.session.set_dialog_policy(headless_chrome::DialogPolicy::Dismiss);
+		self.session.take_dialogs(); self.session.take_security_issues();
+		self.isolate_scripts = true; self.cache_scripts = true;
+		if self.options.borrow().network_conditions.is_some() { self.emulate_network(None)?; }
+		if self.options.borrow().cache_disabled { self.set_cache_disabled(false)?; }
+		Ok(self)
+	}
+	/// The last reported security state of the page
+	pub fn security_state(&self) -> Option<&security::SecurityStateChanged> { self.session.security_state() }
+	/// Insecure or mixed-content states reported so far
+	pub fn security_issues(&self) -> &[security::SecurityStateChanged] { self.session.security_issues() }
+	/// Takes the recorded security issues out
+	pub fn take_security_issues(&mut self) -> Vec<security::SecurityStateChanged> { self.session.take_security_issues() }
+	/// Waits for the next event of the page(frames and dialogs are processed before being delivered)
+	pub fn next_event(&mut self) -> GenericResult<SessionEvent> { self.session.next_event() }
+	/// Stream of the page events, for waiting on states not covered by the page controllers
+	pub fn events(&mut self) -> headless_chrome::Events { self.session.events() }
 	pub fn subscribe_frame_navigated<S: headless_chrome::FrameNavigatedEventSubscriber>(&mut self, subscriber: &'static S)
 	{
 		self.session.subscribe_session_event(subscriber);
 	}
 
+	/// Runs scripts in the page's own world(`false`) or in an isolated world per frame(`true`, default).
+	/// Isolated worlds share the DOM but not the globals, so page scripts overriding builtins cannot disturb the parsers
+	pub fn set_script_isolation(&mut self, isolate: bool) -> &mut Self { self.isolate_scripts = isolate; self }
+	/// Compiles the queries of `execute` once per context(`Runtime.compileScript`) and reruns them by the script id(default).
+	/// Compiled scripts are forgotten with their context
+	pub fn set_script_cache(&mut self, cache: bool) -> &mut Self { self.cache_scripts = cache; self }
+	/// Translates a default context(or the main frame for `None`) into the isolated world of the same frame.
+	/// Unknown contexts are kept as is
+	fn script_context(&mut self, context: Option<u64>) -> GenericResult<Option<u64>>
+	{
+		if !self.isolate_scripts { return Ok(context); }
+		let fid = match context
+		{
+			Some(cid) => self.session.frames().frame_of_context(cid).map(|f| f.id.clone()),
+			None => self.session.frames().main_frame().map(|f| f.id.clone())
+		};
+		match fid
+		{
+			Some(fid) =>
+			{
+				let id = self.new_request_id();
+				self.session.isolated_world(id, &fid, Self::ISOLATED_WORLD_NAME).map(Some)
+			},
+			None => Ok(context)
+		}
+	}
+
 	pub fn query(&mut self, context: Option<u64>, expression: &str) -> GenericResult<()>
+	{
+		let context = self.script_context(context)?;
+		self.evaluate_discarding_result(context, expression)
+	}
+	/// Runs in the page's own world regardless of the script isolation.
+	/// For the scripts of the page itself, which call its globals(e.g. `on_click_script` of the notification rows)
+	pub fn query_in_page(&mut self, context: Option<u64>, expression: &str) -> GenericResult<()>
+	{
+		self.evaluate_discarding_result(context, expression)
+	}
+	fn evaluate_discarding_result(&mut self, context: Option<u64>, expression: &str) -> GenericResult<()>
 	{
 		let id = self.new_request_id();
 		let q = if let Some(cid) = context
@@ -54,29 +251,137 @@ impl RemoteCampus
 		};
 		if q.result.subtype == Some(headless_chrome::runtime::ObjectSubtype::Error)
 		{
-			// Error occured
-			panic!("Error in querying browser: {:?}", q);
+			// Error occured(e.g. a missing element was accessed)
+			return Err(format!("Error in querying browser: {:?}", q).into());
 		}
-		else { Ok(()) }
+		// the result is not used, but an object result stays alive in the page until released
+		drop(self.session.wrap_object(q.result, None));
+		Ok(())
 	}
 	pub fn query_value(&mut self, context: Option<u64>, expression: &str) -> GenericResult<headless_chrome::runtime::RemoteObject>
 	{
+		let context = self.script_context(context)?;
+		let q = self.evaluate_value(context, expression)?;
+		Self::unwrap_query_value(q)
+	}
+	/// `query_value` for scripts calling the helper library(`dcWeb.*`).
+	/// The library is installed into the context first if missing or stale(documents loaded before the registration)
+	pub fn query_value_with_helpers(&mut self, context: Option<u64>, expression: &str) -> GenericResult<headless_chrome::runtime::RemoteObject>
+	{
+		self.query_guarded_value(context, expression, false)
+	}
+	fn query_guarded_value(&mut self, context: Option<u64>, expression: &str, cache: bool) -> GenericResult<headless_chrome::runtime::RemoteObject>
+	{
+		let context = self.script_context(context)?;
+		let guarded = format!("{}\n{}", helper_library::guard(), expression);
+		let q = self.evaluate_guarded(context, &guarded, cache)?;
+		let missing = q.exception_details.as_ref().and_then(|e| e.exception.as_ref()).and_then(|x| x.description.as_ref())
+			.map(|d| d.contains(helper_library::MISSING_MARKER)).unwrap_or(false);
+		if !missing { return Self::unwrap_query_value(q); }
+		self.evaluate_value(context, &helper_library::script())?;
+		let q = self.evaluate_guarded(context, &guarded, cache)?;
+		Self::unwrap_query_value(q)
+	}
+	/// Evaluates by value awaiting promises, through the compiled script cache if `cache`(the main frame's context for `None`)
+	fn evaluate_guarded(&mut self, context: Option<u64>, expression: &str, cache: bool) -> GenericResult<runtime::EvaluateResult>
+	{
+		let cid = if cache { context.or_else(|| self.session.frames().context_of(FrameQuery::Path(&[]))) } else { None };
 		let id = self.new_request_id();
-		let q = if let Some(cid) = context
+		match cid
 		{
-			self.session.runtime().evaluate_value_in_sync(id, cid, expression)?
+			Some(cid) => self.session.run_cached_script(id, cid, expression),
+			None => self.session.runtime().evaluate_awaited_sync(id, context, expression)
 		}
-		else
-		{
-			self.session.runtime().evaluate_value_sync(id, expression)?
-		};
-		if q.result.subtype == Some(headless_chrome::runtime::ObjectSubtype::Error)
+	}
+	/// Runs the query(with the helper library) and decodes the result into a Rust value as its `ValueTy` describes.
+	/// Promises(`jsq::wait_for` etc.) are awaited and decoded as the resolved value.
+	/// e.g. `Vec<String>` for `Array<String>`, any `DeserializeOwned` for `Json`(stringified objects)
+	pub fn execute<Q: QueryCombinator, T>(&mut self, context: Option<u64>, query: Q) -> GenericResult<T> where Q::ValueTy: jsq::types::Decode<T>
+	{
+		let cache = self.cache_scripts;
+		let v = self.query_guarded_value(context, &query.to_string(), cache)?;
+		<Q::ValueTy as jsq::types::Decode<T>>::decode(v.value.unwrap_or(serde_json::Value::Null))
+	}
+	fn evaluate_value(&mut self, context: Option<u64>, expression: &str) -> GenericResult<runtime::EvaluateResult>
+	{
+		let id = self.new_request_id();
+		if let Some(cid) = context { self.session.runtime().evaluate_value_in_sync(id, cid, expression) }
+		else { self.session.runtime().evaluate_value_sync(id, expression) }
+	}
+	fn unwrap_query_value(q: runtime::EvaluateResult) -> GenericResult<runtime::RemoteObject>
+	{
+		if q.result.subtype == Some(headless_chrome::runtime::ObjectSubtype::Error) || q.exception_details.is_some()
 		{
-			// Error occured
-			panic!("Error in querying value to browser: {:?}", q);
+			// Error occured(or a promise was rejected)
+			Err(format!("Error in querying value to browser: {:?}", q).into())
 		}
 		else { Ok(q.result) }
 	}
+	/// Evaluates and keeps the result remote. Objects released together by `release_object_group` if `group` is given
+	pub fn query_object(&mut self, context: Option<u64>, expression: &str, group: Option<&str>) -> GenericResult<RemoteObjectHandle>
+	{
+		let context = self.script_context(context)?;
+		let id = self.new_request_id();
+		let q = self.session.runtime().evaluate_object_sync(id, context, expression, group)?;
+		if let Some(e) = q.exception_details { return Err(format!("Error in querying object to browser: {:?}", e).into()); }
+		Ok(self.session.wrap_object(q.result, group))
+	}
+	/// Own properties of the remote object
+	pub fn object_properties(&mut self, object: &RemoteObjectHandle) -> GenericResult<Vec<(String, RemoteObjectHandle)>>
+	{
+		let id = self.new_request_id(); object.properties(&mut self.session, id)
+	}
+	/// Calls `function_declaration` with the remote object as `this`
+	pub fn call_object_function(&mut self, object: &RemoteObjectHandle, function_declaration: &str, arguments: &[runtime::CallArgument])
+		-> GenericResult<RemoteObjectHandle>
+	{
+		let id = self.new_request_id(); object.call_function(&mut self.session, id, function_declaration, arguments)
+	}
+	/// DOM node id of the remote object
+	pub fn object_node_id(&mut self, object: &RemoteObjectHandle) -> GenericResult<isize>
+	{
+		let id = self.new_request_id();
+		// DOM.requestNode requires the document in the front-end
+		self.session.dom().get_document_sync(id)?;
+		let id = self.new_request_id(); object.node_id(&mut self.session, id)
+	}
+	/// Captures the document of the frame(the main frame for `None`) as a tree, with layout and `SNAPSHOT_STYLES`
+	pub fn snapshot(&mut self, frame: Option<FrameQuery>) -> GenericResult<DomTree>
+	{
+		let fid = match frame
+		{
+			Some(q) => self.session.frames().find(q),
+			None => self.session.frames().main_frame()
+		}.map(|f| f.id.clone()).ok_or_else(|| format!("Frame not found: {:?}", frame))?;
+		let id = self.new_request_id();
+		let s = self.session.dom_snapshot().capture_snapshot_sync(id, Self::SNAPSHOT_STYLES)?;
+		let doc = s.document_of_frame(&fid).ok_or_else(|| format!("The document of frame {} is not in the snapshot", fid))?;
+		DomTree::from_snapshot(&s, doc, Self::SNAPSHOT_STYLES)
+	}
+	/// Installs a global function `name(payload: string)` calling back into the controller(in the world running the queries)
+	pub fn add_binding(&mut self, name: &str) -> GenericResult<&mut Self>
+	{
+		let id = self.new_request_id();
+		let world = if self.isolate_scripts { Some(Self::ISOLATED_WORLD_NAME) } else { None };
+		self.session.runtime().add_binding(id, name, world)?; self.session.wait_result(id)?;
+		Ok(self)
+	}
+	pub fn remove_binding(&mut self, name: &str) -> GenericResult<&mut Self>
+	{
+		let id = self.new_request_id();
+		self.session.runtime().remove_binding(id, name)?; self.session.wait_result(id)?;
+		Ok(self)
+	}
+	/// Waits for a call of the binding and returns its payload
+	pub fn wait_binding_call(&mut self, name: &str) -> GenericResult<String>
+	{
+		self.session.wait_binding_call(name).map(|c| c.payload)
+	}
+	/// Releases all remote objects in the group at once
+	pub fn release_object_group(&mut self, group: &str) -> GenericResult<()>
+	{
+		let id = self.new_request_id(); self.session.runtime().release_object_group_sync(id, group)
+	}
 	pub fn query_page_location(&mut self, cid: Option<u64>) -> GenericResult<String>
 	{
 		self.query_value(cid, "location.href").map(runtime::RemoteObject::assume_string)
@@ -92,11 +397,15 @@ impl RemoteCampus
 
 	pub fn click_element(&mut self, context: Option<u64>, selector: &str) -> GenericResult<&mut Self>
 	{
-		self.query(context, &format!(r#"document.querySelector({:?}).click()"#, selector)).map(move |_| self)
+		let clicked: Option<bool> = self.execute(context, jsq::Document.query_selector(selector.to_owned()).click())?;
+		if clicked.is_none() { return Err(format!("No element matches {:?}", selector).into()); }
+		Ok(self)
 	}
 	pub fn click_nth_element(&mut self, context: Option<u64>, selector: &str, index: usize) -> GenericResult<&mut Self>
 	{
-		self.query(context, &format!(r#"document.querySelectorAll({:?})[{}].click()"#, selector, index)).map(move |_| self)
+		let clicked: Option<bool> = self.execute(context, jsq::Document.query_selector_all(selector.to_owned()).index(index).click())?;
+		if clicked.is_none() { return Err(format!("No element matches {:?}[{}]", selector, index).into()); }
+		Ok(self)
 	}
 	pub fn jump_to_anchor_href(&mut self, selector: &str) -> GenericResult<&mut Self>
 	{
@@ -113,12 +422,24 @@ impl RemoteCampus
 		self.session.page().navigate_sync(id2, intersys_link_attrs[href_index].as_str().unwrap()).map(move |_| self)
 	}
 
+	pub fn navigate(&mut self, url: &str) -> GenericResult<&mut Self>
+	{
+		let id = self.new_request_id();
+		self.session.page().navigate_sync(id, url).map(move |_| self)
+	}
 	/// synchronize page
 	pub fn wait_loading(&mut self) -> GenericResult<&mut Self>
 	{
 		self.session.wait_event::<headless_chrome::page::LoadEventFired>().map(move |_| self)
 	}
 }
+impl jsq::Executor for RemoteCampus
+{
+	fn execute<Q: QueryCombinator, T>(&mut self, context: Option<u64>, query: Q) -> GenericResult<T> where Q::ValueTy: jsq::types::Decode<T>
+	{
+		RemoteCampus::execute(self, context, query)
+	}
+}
 
 /// ログインページ
 pub struct LoginPage { remote: RemoteCampus }
@@ -130,21 +451,31 @@ impl LoginPage
 	/// ログインIDフィールドを設定
 	pub fn set_login_id_field(&mut self, login_id: &str) -> GenericResult<&mut Self>
 	{
-		let id = self.remote.new_request_id();
-		self.remote.session.runtime().evaluate_sync(id, &format!(r#"document.querySelector('input[name={:?}]').value = {:?};"#, Self::FORM_NAME_ID, login_id))
-			.map(move |_| self)
+		self.remote.sensitive(|remote|
+		{
+			let id = remote.new_request_id();
+			remote.session.runtime().evaluate_sync(id, &Self::login_id_script(login_id))
+		}).map(move |_| self)
+	}
+	fn login_id_script(login_id: &str) -> String
+	{
+		let field = jsq::Document.query_selector(format!(r#"input[name="{}"]"#, Self::FORM_NAME_ID));
+		format!("{}.value = {};", field, jsq::Literal(login_id))
 	}
 	/// パスワードフィールドを設定
 	pub fn set_password_field(&mut self, pass: &str) -> GenericResult<&mut Self>
 	{
 		let id = self.remote.new_request_id();
-		self.remote.session.dom().get_root_node_sync(id).unwrap().query_selector(&format!(r#"input[name={:?}]"#, Self::FORM_NAME_PASSWORD))?.focus()?;
-		for c in pass.trim_right().chars()
+		self.remote.session.dom().get_root_node_sync(id).unwrap().query_selector(&format!(r#"input[name="{}"]"#, Self::FORM_NAME_PASSWORD))?.focus()?;
+		self.remote.sensitive(|remote|
 		{
-			let id = self.remote.new_request_id();
-			self.remote.session.input().dispatch_key_event_sync(id, headless_chrome::input::KeyEvent::Char, Some(&c.to_string())).unwrap();
-		}
-		Ok(self)
+			for c in pass.trim_right().chars()
+			{
+				let id = remote.new_request_id();
+				remote.session.input().dispatch_key_event_sync(id, headless_chrome::input::KeyEvent::Char, Some(&c.to_string()))?;
+			}
+			Ok(())
+		}).map(move |_: ()| self)
 	}
 	/// IDとパスワードを設定
 	pub fn set_login_info_fields(&mut self, login_id: &str, pass: &str) -> GenericResult<&mut Self>
@@ -282,83 +613,117 @@ impl HomePage
 	const NEWSBOX_LIST: &'static str = "#mainContents .homeNewsBox";
 	const NEWSBOX_CONTENT_ROWS: &'static str = "table:nth-child(2) tr.pointer";
 	const TOALL_LINK_PATH: &'static str = ".toAll a";
-	const COMMONFN_TRANSLATE_NS: &'static str = r#"function translateNotificationState(s) {
-		switch(s) {
-		case "未読": return "Unread"; case "既読": return "Read";
-		case "未回答": return "Unanswered"; case "回答済": return "Answered";
-		case "未提出": return "Unsubmitted"; case "提出済": return "Submitted";
-		default: console.assert(0);
-		}
-	}"#;
 	
-	fn query_all_row_contents<Source: QueryCombinator>(row: Source)
-		-> jsq::Mapping<jsq::QuerySelectorAll<Source>, jsq::Closure<'static, jsq::CustomExpression<jsq::types::String>>>
-		where Source::ValueTy: jsq::types::QueryableElements
-	{
-		row.query_selector_all("td".into()).map_auto("x", jsq::CustomExpression::<jsq::types::String>("x.textContent.trim()".into(), PhantomData))
-	}
 	fn query_rows(index1: usize) -> jsq::QuerySelectorAll<jsq::Document>
 	{
 		jsq::Document.query_selector_all(format!("{}:nth-child({}) {}", Self::NEWSBOX_LIST, index1, Self::NEWSBOX_CONTENT_ROWS))
 	}
-	fn gen_object(values: &[(&str, &str)]) -> jsq::CustomExpression<jsq::types::Object>
-	{
-		jsq::CustomExpression(format!("({{ {} }})",
-			values.into_iter().map(|&(ref k, ref v)| format!("{}: {}", k, v)).collect::<Vec<String>>().join(",")), PhantomData)
-	}
-	fn reformat_date(expr: &str) -> String
-	{
-		format!(r#"{}.replace(/(\d+)\/(\d+)\/(\d+)/, "$1-$2-$3T00:00:00Z")"#, expr)
-	}
-	fn reformat_datetime(expr: &str) -> String
+	/// index1番目(1から)のお知らせ欄の行を取得(`offline::StaticPage`でも実行できる)
+	pub fn extract_rows<E: jsq::Executor, T: TableRow>(executor: &mut E, index1: usize) -> GenericResult<Vec<T>>
 	{
-		format!(r#"{}.replace(/(\d+)\/(\d+)\/(\d+)\s*(\d+:\d+)/, "$1-$2-$3T$4:00Z")"#, expr)
+		executor.execute(None, Self::query_rows(index1).map(table_row::extractor::<T>()))
 	}
 
 	/// 最新のお知らせ(5件?)を取得
-	pub fn acquire_notifications_latest(&mut self) -> GenericResult<Vec<Notification>>
-	{
-		let q: String = self.remote.query_value(None, &Self::query_rows(1).map_auto("r",
-			Self::query_all_row_contents(jsq::CustomExpression::<jsq::types::Element>("r".into(), PhantomData)).map_value_auto("cells", jsqGenObject!{
-				category: "cells[0]", date: &Self::reformat_date("cells[1]"), priority: "cells[2]", title: "cells[3]", from: "cells[4]",
-				state: "translateNotificationState(cells[5])", onClickScript: r#"r.getAttribute("onclick").substring("javascript:".length)"#
-			})).stringify().with_header(Self::COMMONFN_TRANSLATE_NS))?.assume();
-		Ok(serde_json::from_str(&q).expect("Protocol Corruption"))
-	}
+	pub fn acquire_notifications_latest(&mut self) -> GenericResult<Vec<Notification>> { Self::extract_rows(&mut self.remote, 1) }
 	/// 授業関連の最新のお知らせ(〜3件?)を取得
-	pub fn acquire_lecture_notifications_latest(&mut self) -> GenericResult<Vec<ClassNotification>>
-	{
-		let q: String = self.remote.query_value(None, &Self::query_rows(2).map_auto("r",
-			Self::query_all_row_contents(jsq::CustomExpression::<jsq::types::Element>("r".into(), PhantomData)).map_value_auto("cells", jsqGenObject!{
-				category: "cells[0]", date: &Self::reformat_date("cells[1]"), priority: "cells[2]", lectureTitle: "cells[3]", title: "cells[4]",
-				state: "translateNotificationState(cells[5])", onClickScript: r#"r.getAttribute("onclick").substring("javascript:".length)"#
-			})).stringify().with_header(Self::COMMONFN_TRANSLATE_NS))?.assume();
-		Ok(serde_json::from_str(&q).expect("Protocol Corruption"))
-	}
+	pub fn acquire_lecture_notifications_latest(&mut self) -> GenericResult<Vec<ClassNotification>> { Self::extract_rows(&mut self.remote, 2) }
 	/// フィードバックシート回答待ちリストの取得
-	pub fn acquire_feedback_sheets(&mut self) -> GenericResult<Vec<FeedbackSheetNotification>>
+	pub fn acquire_feedback_sheets(&mut self) -> GenericResult<Vec<FeedbackSheetNotification>> { Self::extract_rows(&mut self.remote, 3) }
+	/// 課題回答待ちリストの取得
+	pub fn acquire_homeworks(&mut self) -> GenericResult<Vec<HomeworkNotification>> { Self::extract_rows(&mut self.remote, 4) }
+}
+/// 新着お知らせの監視
+impl HomePage
+{
+	const NOTIFICATION_BINDING: &'static str = "dcWebNotify";
+
+	/// index1番目(1から)のお知らせ欄と、その行を`T`のオブジェクトにする関数の組(`[index1, 欄, 関数]`)
+	fn watched_box<T: TableRow>(index1: usize) -> String
 	{
-		let q: String = self.remote.query_value(None, &Self::query_rows(3).map_auto("r",
-			Self::query_all_row_contents(jsq::CustomExpression::<jsq::types::Element>("r".into(), PhantomData)).map_value_auto("cells", jsqGenObject!{
-				lectureDate: &Self::reformat_date("cells[0]"), lectureTitle: "cells[1]",
-				time: "parseInt(cells[2].replace(/[０-９]/g, x => String.fromCharCode(x.charCodeAt(0) - 65248)))",
-				deadline: &Self::reformat_datetime("cells[3]"), state: "translateNotificationState(cells[4])",
-				onClickScript: r#"r.getAttribute("onclick").substring("javascript:".length)"#
-			})).stringify().with_header(Self::COMMONFN_TRANSLATE_NS))?.assume();
-		Ok(serde_json::from_str(&q).expect("Protocol Corruption"))
+		format!("[{}, {}, {}]", index1,
+			jsq::Document.query_selector(format!("{}:nth-child({})", Self::NEWSBOX_LIST, index1)), table_row::extractor::<T>())
 	}
-	/// 課題回答待ちリストの取得
-	pub fn acquire_homeworks(&mut self) -> GenericResult<Vec<HomeworkNotification>>
+
+	/// お知らせ欄に行が追加されるたびに`NewNotification`を受け取る(既存の行は含まない)
+	pub fn watch_notifications(&mut self) -> GenericResult<NotificationWatcher>
+	{
+		self.remote.add_binding(Self::NOTIFICATION_BINDING)?;
+		self.remote.query_value_with_helpers(None, &Self::notification_observer_script())?;
+		Ok(NotificationWatcher { home: self })
+	}
+	const RELEASE_NOTIFICATION_OBSERVER: &'static str = r#"if(window.dcWebNotificationObserver) {
+			window.dcWebNotificationObserver.disconnect(); delete window.dcWebNotificationObserver;
+		}"#;
+	fn notification_observer_script() -> String
+	{
+		let boxes = [
+			Self::watched_box::<Notification>(1), Self::watched_box::<ClassNotification>(2),
+			Self::watched_box::<FeedbackSheetNotification>(3), Self::watched_box::<HomeworkNotification>(4)
+		].join(", ");
+		format!(r#"(function() {{
+			if(window.dcWebNotificationObserver) return;
+			var observers = [];
+			[{boxes}].forEach(function(b) {{
+				var box = b[1];
+				if(box == null) return;
+				var toObject = b[2];
+				var seen = new Set(box.querySelectorAll({rows}));
+				var observer = new MutationObserver(function() {{
+					Array.prototype.forEach.call(box.querySelectorAll({rows}), function(r) {{
+						if(seen.has(r)) return; seen.add(r);
+						{binding}(JSON.stringify({{ box: b[0], row: toObject(r) }}));
+					}});
+				}});
+				observer.observe(box, {{ childList: true, subtree: true }});
+				observers.push(observer);
+			}});
+			window.dcWebNotificationObserver = {{ disconnect: function() {{ observers.forEach(function(o) {{ o.disconnect(); }}); }} }};
+		}})()"#, boxes = boxes, rows = jsq::Literal(Self::NEWSBOX_CONTENT_ROWS), binding = Self::NOTIFICATION_BINDING)
+	}
+}
+/// 新着お知らせ(追加されたお知らせ欄ごと)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NewNotification
+{
+	Notification(Notification), Class(ClassNotification), FeedbackSheet(FeedbackSheetNotification), Homework(HomeworkNotification)
+}
+/// 新着お知らせの監視(dropで監視を解除)
+pub struct NotificationWatcher<'p> { home: &'p mut HomePage }
+impl<'p> NotificationWatcher<'p>
+{
+	/// 次の新着お知らせを待つ
+	pub fn next_notification(&mut self) -> GenericResult<NewNotification>
+	{
+		#[derive(Deserialize)]
+		struct Payload { #[serde(rename = "box")] box_index: usize, row: serde_json::Value }
+		let payload = self.home.remote.wait_binding_call(HomePage::NOTIFICATION_BINDING)?;
+		let payload: Payload = serde_json::from_str(&payload)?;
+		Ok(match payload.box_index
+		{
+			1 => NewNotification::Notification(Notification::decode_row(payload.row)?),
+			2 => NewNotification::Class(ClassNotification::decode_row(payload.row)?),
+			3 => NewNotification::FeedbackSheet(FeedbackSheetNotification::decode_row(payload.row)?),
+			4 => NewNotification::Homework(HomeworkNotification::decode_row(payload.row)?),
+			n => return Err(format!("Notification from an unknown news box: {}", n).into())
+		})
+	}
+}
+impl<'p> Iterator for NotificationWatcher<'p>
+{
+	type Item = GenericResult<NewNotification>;
+	fn next(&mut self) -> Option<Self::Item> { Some(self.next_notification()) }
+}
+impl<'p> Drop for NotificationWatcher<'p>
+{
+	fn drop(&mut self)
 	{
-		let q: String = self.remote.query_value(None, &Self::query_rows(4).map_auto("r",
-			Self::query_all_row_contents(jsq::CustomExpression::<jsq::types::Element>("r".into(), PhantomData)).map_value_auto("cells", jsqGenObject!{
-				date: &Self::reformat_date("cells[0]"), lectureTitle: "cells[1]", title: "cells[2]",
-				deadline: &Self::reformat_datetime("cells[3]"), state: "translateNotificationState(cells[4])",
-				onClickScript: r#"r.getAttribute("onclick").substring("javascript:".length)"#
-			})).stringify().with_header(Self::COMMONFN_TRANSLATE_NS))?.assume();
-		Ok(serde_json::from_str(&q).expect("Protocol Corruption"))
+		// the page may have already gone
+		self.home.remote.query(None, HomePage::RELEASE_NOTIFICATION_OBSERVER).ok();
+		self.home.remote.remove_binding(HomePage::NOTIFICATION_BINDING).ok();
 	}
 }
+
 /// お知らせ一覧のページ
 pub struct AllNotificationsPage { remote: RemoteCampus }
 /// 休講/補講/教室変更お知らせ一覧のページ
@@ -376,128 +741,71 @@ pub struct AllHomeworkNotificationsPage { remote: RemoteCampus }
 pub trait NotificationListPage : ToplevelPageControl
 {
 	/// 自身が返す通知行の型
-	type NotificationTy : ::serde::de::DeserializeOwned;
-	/// 通知行オブジェクトを構成するJSQ Fragment("cells"にデータが入っているので、それを加工するJSQ Fragment)
-	fn jsqf_notification_gen() -> jsq::ObjectConstructor<'static, 'static>;
+	type NotificationTy : TableRow;
 
 	/// すべての通知を取得
-	fn acquire_notifications(&mut self) -> GenericResult<Vec<Self::NotificationTy>>
-	{
-		let row = jsqCustomExpr!([jsq::types::Element] "r").query_selector_all("td".into())
-			.map_auto("x", jsqCustomExpr!([jsq::types::String] "x.textContent.trim()"))
-			.map_value_auto("cells", Self::jsqf_notification_gen()).into_closure("r");
-		let q = jsq::Document.query_selector_all("#mainContents .homeNewsBox .pointer".into()).map(row).stringify();
-		let qv: String = self.remote_ctrl().query_value(None, &q.with_header(HomePage::COMMONFN_TRANSLATE_NS))?.assume();
-		Ok(serde_json::from_str(&qv).expect("Protocol Corruption"))
-	}
-}
-const REFORMAT_DATE_CELLS_1: &'static str = r#"cells[1].replace(/(\d+)\/(\d+)\/(\d+)/, "$1-$2-$3T00:00:00Z")"#;
-impl NotificationListPage for AllNotificationsPage
-{
-	type NotificationTy = Notification;
-	fn jsqf_notification_gen() -> jsq::ObjectConstructor<'static, 'static>
-	{
-		jsqGenObject!{
-			category: "cells[0]", date: REFORMAT_DATE_CELLS_1, priority: "cells[2]", title: "cells[3]", from: "cells[4]",
-			state: "translateNotificationState(cells[5])", onClickScript: r#"r.getAttribute("onclick").substring("javascript:".length)"#
-		}
-	}
-}
-impl NotificationListPage for AllClassNotificationsPage
-{
-	type NotificationTy = ClassNotification;
-	fn jsqf_notification_gen() -> jsq::ObjectConstructor<'static, 'static>
-	{
-		jsqGenObject!{
-			category: "cells[0]", date: REFORMAT_DATE_CELLS_1, priority: "cells[2]", lectureTitle: "cells[3]", title: "cells[4]",
-			state: "translateNotificationState(cells[5])", onClickScript: r#"r.getAttribute("onclick").substring("javascript:".length)"#
-		}
-	}
-}
-const REFORMAT_DATE_CELLS_0: &'static str = r#"cells[0].replace(/(\d+)\/(\d+)\/(\d+)/, "$1-$2-$3T00:00:00Z")"#;
-impl NotificationListPage for AllLectureNotesPage
-{
-	type NotificationTy = LectureNotification;
-	fn jsqf_notification_gen() -> jsq::ObjectConstructor<'static, 'static>
+	fn acquire_notifications(&mut self) -> GenericResult<Vec<Self::NotificationTy>> where Self: Sized
 	{
-		jsqGenObject!{
-			date: REFORMAT_DATE_CELLS_0, priority: "cells[1]", lectureTitle: "cells[2]", title: "cells[3]",
-			state: "translateNotificationState(cells[4])", onClickScript: r#"r.getAttribute("onclick").substring("javascript:".length)"#
-		}
+		Self::extract_notifications(self.remote_ctrl())
 	}
-}
-impl NotificationListPage for AllLectureNotificationsPage
-{
-	type NotificationTy = LectureNotification;
-	fn jsqf_notification_gen() -> jsq::ObjectConstructor<'static, 'static>
+	/// すべての通知行を取得(`offline::StaticPage`でも実行できる)
+	fn extract_notifications<E: jsq::Executor>(executor: &mut E) -> GenericResult<Vec<Self::NotificationTy>> where Self: Sized
 	{
-		jsqGenObject!{
-			date: REFORMAT_DATE_CELLS_0, priority: "cells[1]", lectureTitle: "cells[2]", title: "cells[3]",
-			state: "translateNotificationState(cells[4])", onClickScript: r#"r.getAttribute("onclick").substring("javascript:".length)"#
-		}
-	}
-}
-const REFORMAT_DATE_CELLS_3: &'static str = r#"cells[3].replace(/(\d+)\/(\d+)\/(\d+)/, "$1-$2-$3T00:00:00Z")"#;
-impl NotificationListPage for AllFeedbackSheetNotificationsPage
-{
-	type NotificationTy = FeedbackSheetNotification;
-	fn jsqf_notification_gen() -> jsq::ObjectConstructor<'static, 'static>
-	{
-		jsqGenObject!{
-			lectureDate: REFORMAT_DATE_CELLS_0, lectureTitle: "cells[1]",
-			time: "parseInt(cells[2].replace(/[０-９]/g, x => String.fromCharCode(x.charCodeAt(0) - 65248)))",
-			deadline: REFORMAT_DATE_CELLS_3, state: "translateNotificationState(cells[4])",
-			onClickScript: r#"r.getAttribute("onclick").substring("javascript:".length)"#
-		}
+		let q = jsq::Document.query_selector_all("#mainContents .homeNewsBox .pointer".into()).map(table_row::extractor::<Self::NotificationTy>());
+		executor.execute(None, q)
 	}
-}
-impl NotificationListPage for AllHomeworkNotificationsPage
-{
-	type NotificationTy = HomeworkNotification;
-	fn jsqf_notification_gen() -> jsq::ObjectConstructor<'static, 'static>
+	/// 通知行の`on_click_script`を実行(ページのグローバル関数を呼ぶので、ページ自身のワールドで実行する)
+	fn run_on_click_script(&mut self, on_click_script: &str) -> GenericResult<()>
 	{
-		jsqGenObject!{
-			date: REFORMAT_DATE_CELLS_0, lectureTitle: "cells[1]", title: "cells[2]",
-			deadline: REFORMAT_DATE_CELLS_3, state: "translateNotificationState(cells[4])",
-			onClickScript: r#"r.getAttribute("onclick").substring("javascript:".length)"#
-		}
+		self.remote_ctrl().query_in_page(None, on_click_script)
 	}
 }
+impl NotificationListPage for AllNotificationsPage { type NotificationTy = Notification; }
+impl NotificationListPage for AllClassNotificationsPage { type NotificationTy = ClassNotification; }
+impl NotificationListPage for AllLectureNotesPage { type NotificationTy = LectureNotification; }
+impl NotificationListPage for AllLectureNotificationsPage { type NotificationTy = LectureNotification; }
+impl NotificationListPage for AllFeedbackSheetNotificationsPage { type NotificationTy = FeedbackSheetNotification; }
+impl NotificationListPage for AllHomeworkNotificationsPage { type NotificationTy = HomeworkNotification; }
 
 /// お知らせ行
-#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)] #[serde(rename_all = "camelCase")]
+#[derive(Serialize, Deserialize, TableRow, Debug, Clone, PartialEq, Eq)] #[serde(rename_all = "camelCase")]
 pub struct Notification
 {
-	pub category: String, pub date: DateTime<Utc>, pub priority: String, pub title: String, pub from: String,
-	pub state: NotificationState, pub on_click_script: String
+	#[column(0)] pub category: String, #[column(1, date)] pub date: DateTime<Utc>, #[column(2)] pub priority: String,
+	#[column(3)] pub title: String, #[column(4)] pub from: String,
+	#[column(5, state)] pub state: NotificationState, #[onclick] pub on_click_script: String
 }
 /// 講義関連お知らせ行
-#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)] #[serde(rename_all = "camelCase")]
+#[derive(Serialize, Deserialize, TableRow, Debug, Clone, PartialEq, Eq)] #[serde(rename_all = "camelCase")]
 pub struct ClassNotification
 {
-	pub category: String, pub date: DateTime<Utc>, pub priority: String, pub lecture_title: String, pub title: String,
-	pub state: NotificationState, pub on_click_script: String
+	#[column(0)] pub category: String, #[column(1, date)] pub date: DateTime<Utc>, #[column(2)] pub priority: String,
+	#[column(3)] pub lecture_title: String, #[column(4)] pub title: String,
+	#[column(5, state)] pub state: NotificationState, #[onclick] pub on_click_script: String
 }
 /// 講義連絡行
-#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)] #[serde(rename_all = "camelCase")]
+#[derive(Serialize, Deserialize, TableRow, Debug, Clone, PartialEq, Eq)] #[serde(rename_all = "camelCase")]
 pub struct LectureNotification
 {
-	pub date: DateTime<Utc>, pub priority: String, pub lecture_title: String, pub title: String,
-	pub state: NotificationState, pub on_click_script: String
+	#[column(0, date)] pub date: DateTime<Utc>, #[column(1)] pub priority: String,
+	#[column(2)] pub lecture_title: String, #[column(3)] pub title: String,
+	#[column(4, state)] pub state: NotificationState, #[onclick] pub on_click_script: String
 }
 /// フィードバックシート回答待ち行
-#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)] #[serde(rename_all = "camelCase")]
+#[derive(Serialize, Deserialize, TableRow, Debug, Clone, PartialEq, Eq)] #[serde(rename_all = "camelCase")]
 pub struct FeedbackSheetNotification
 {
-	pub lecture_date: DateTime<Utc>, pub lecture_title: String, pub time: u32, pub deadline: DateTime<Utc>,
-	pub state: NotificationState, pub on_click_script: String
+	#[column(0, date)] pub lecture_date: DateTime<Utc>, #[column(1)] pub lecture_title: String,
+	#[column(2, digits)] pub time: u32, #[column(3, datetime)] pub deadline: DateTime<Utc>,
+	#[column(4, state)] pub state: NotificationState, #[onclick] pub on_click_script: String
 }
 /// 課題回答待ち行
-#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)] #[serde(rename_all = "camelCase")]
+#[derive(Serialize, Deserialize, TableRow, Debug, Clone, PartialEq, Eq)] #[serde(rename_all = "camelCase")]
 pub struct HomeworkNotification
 {
-	pub date: DateTime<Utc>, pub lecture_title: String, pub title: String, pub deadline: DateTime<Utc>,
-	pub state: NotificationState, pub on_click_script: String
+	#[column(0, date)] pub date: DateTime<Utc>, #[column(1)] pub lecture_title: String,
+	#[column(2)] pub title: String, #[column(3, datetime)] pub deadline: DateTime<Utc>,
+	#[column(4, state)] pub state: NotificationState, #[onclick] pub on_click_script: String
 }
 /// 閲覧状態
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
@@ -506,114 +814,19 @@ pub enum NotificationState
 	Unread, Read, Unanswered, Answered, Unsubmitted, Submitted
 }
 
-#[derive(Debug, PartialEq, Eq)]
-pub enum ScriptContextState { Unloaded, Empty(String), Context(String, u64) }
-impl ScriptContextState
-{
-	fn frameid(&self) -> Option<&str>
-	{
-		match self { &ScriptContextState::Context(ref s, _) | &ScriptContextState::Empty(ref s) => Some(s), _ => None }
-	}
-	fn contextid(&self) -> Option<u64>
-	{
-		match self
-		{
-			&ScriptContextState::Context(_, c) => Some(c), _ => None
-		}
-	}
-	
-	fn navigated(&mut self, fid: String)
-	{
-		if let &mut ScriptContextState::Empty(ref mut s) = self { *s = fid; }
-		else if let ScriptContextState::Context(s, ccid) = replace(self, ScriptContextState::Unloaded)
-		{
-			*self = if s == fid { ScriptContextState::Context(s, ccid) } else { ScriptContextState::Empty(fid) };
-		}
-		else { *self = ScriptContextState::Empty(fid); }
-	}
-	fn try_attach_context(&mut self, fid: &str, cid: u64)
-	{
-		if let &mut ScriptContextState::Context(ref s, ref mut ccid) = self
-		{
-			if s == fid { *ccid = cid; }
-		}
-		else if let ScriptContextState::Empty(s) = replace(self, ScriptContextState::Unloaded)
-		{
-			*self = if s == fid { ScriptContextState::Context(s, cid) } else { ScriptContextState::Empty(s) };
-		}
-	}
-	fn detach_context(&mut self)
-	{
-		*self = match replace(self, ScriptContextState::Unloaded)
-		{
-			ScriptContextState::Context(s, _) => ScriptContextState::Empty(s),
-			c => c
-		};
-	}
-}
-
-#[cfg(feature = "verbose")] use colored::*;
-
-trait Breakability { fn require_break(self) -> bool; }
-impl Breakability for () { fn require_break(self) -> bool { false } }
-impl Breakability for bool { fn require_break(self) -> bool { self } }
-macro_rules! SessionEventLoop
-{
-	{ __SessionMatcher($name: expr, $params: expr) $($ee: ident)::+ => break } =>
-	{
-		if $name == $($ee::)+METHOD_NAME { break; }
-	};
-	{ __SessionMatcher($name: expr, $params: expr) $($ee: ident)::+ => $x: expr } =>
-	{
-		if $name == $($ee::)+METHOD_NAME
-		{
-			if ($x)(serde_json::from_value::<$($ee)::+>($params)?).require_break() { break; }
-		}
-	};
-	{ __SessionMatcher($name: expr, $params: expr) $($ee: ident)::+ => break; $($rest: tt)* } =>
-	{
-		if $name == $($ee::)+METHOD_NAME { break; }
-		else { SessionEventLoop!{ __SessionMatcher($name, $params) $($rest)* } }
-	};
-	{ __SessionMatcher($name: expr, $params: expr) $($ee: ident)::+ => $x: expr; $($rest: tt)* } =>
-	{
-		if $name == $($ee::)+METHOD_NAME
-		{
-			if ($x)(serde_json::from_value::<$($ee)::+>($params)?).require_break() { break; }
-		}
-		else { SessionEventLoop!{ __SessionMatcher($name, $params) $($rest)* } }
-	};
-	($session: expr; { $($content: tt)* }) =>
-	{
-		loop
-		{
-			let s = $session.wait_text()?;
-			#[cfg(feature = "verbose")] println!("{}", format!("<<-- [SessionEventLoop]Received: {}", s).blue().bold());
-			let obj: headless_chrome::SessionReceiveEvent = ::serde_json::from_str(&s)?;
-			match obj
-			{
-				headless_chrome::SessionReceiveEvent::Method { method: name, params } =>
-				{
-					SessionEventLoop!{ __SessionMatcher(name, params) $($content)* }
-				},
-				e@headless_chrome::SessionReceiveEvent::Error { .. } => return Err(e.error_text().unwrap().into()),
-				_ => ()
-			}
-		}
-	}
-}
-
 /// CampusPlan フレームページ
 pub struct CampusPlanFrames<MainFrameCtrlTy: PageControl, MenuFrameCtrlTy: PageControl>
 {
-	remote: RemoteCampus, ph: PhantomData<(MainFrameCtrlTy, MenuFrameCtrlTy)>,
-	ctx_main_frame: ScriptContextState, ctx_menu_frame: ScriptContextState
+	remote: RemoteCampus, ph: PhantomData<(MainFrameCtrlTy, MenuFrameCtrlTy)>
 }
 impl<MainFrameCtrlTy: PageControl, MenuFrameCtrlTy: PageControl> CampusPlanFrames<MainFrameCtrlTy, MenuFrameCtrlTy>
 {
+	const MAIN_FRAME_NAME: &'static str = "MainFrame";
+	const MENU_FRAME_NAME: &'static str = "MenuFrame";
+
 	pub unsafe fn enter(remote: RemoteCampus) -> Self
 	{
-		CampusPlanFrames { remote, ph: PhantomData, ctx_main_frame: ScriptContextState::Unloaded, ctx_menu_frame: ScriptContextState::Unloaded }
+		CampusPlanFrames { remote, ph: PhantomData }
 	}
 	fn continue_enter<NewMainFrameCtrlTy: PageControl, NewMenuFrameCtrlTy: PageControl>(self) -> CampusPlanFrames<NewMainFrameCtrlTy, NewMenuFrameCtrlTy>
 	{
@@ -626,6 +839,10 @@ impl<MainFrameCtrlTy: PageControl, MenuFrameCtrlTy: PageControl> CampusPlanFrame
 		self.remote.query_page_location(Some(cid)).map(|l| l.contains("/blank.html"))
 	}
 }
+impl<MainFrameCtrlTy: PageControl, MenuFrameCtrlTy: PageControl> ToplevelPageControl for CampusPlanFrames<MainFrameCtrlTy, MenuFrameCtrlTy>
+{
+	fn remote_ctrl(&mut self) -> &mut RemoteCampus { &mut self.remote } fn transfer_control(self) -> RemoteCampus { self.remote }
+}
 
 /// Context ops
 impl<MainFrameCtrlTy: PageControl, MenuFrameCtrlTy: PageControl> CampusPlanFrames<MainFrameCtrlTy, MenuFrameCtrlTy>
@@ -635,55 +852,32 @@ impl<MainFrameCtrlTy: PageControl, MenuFrameCtrlTy: PageControl> CampusPlanFrame
 	{
 		let (mut main_completion, mut menu_completion) = (false, !wait_for_menu_context);
 
-		SessionEventLoop!(self.remote.session;
+		while !(main_completion && menu_completion)
 		{
-			page::FrameNavigatedOwned => |e: page::FrameNavigatedOwned|
-			{
-				self.remote.session.dispatch_frame_navigated(&e.borrow());
-				match e.frame.name.as_ref().map(|s| s as &str)
-				{
-					Some("MainFrame") => { self.ctx_main_frame.navigated(e.frame.id); },
-					Some("MenuFrame") => { self.ctx_menu_frame.navigated(e.frame.id); },
-					_ => ()
-				}
-			};
-			runtime::ExecutionContextCreated => |e: runtime::ExecutionContextCreated|
-			{
-				if let Some(aux) = e.context.aux_data
-				{
-					if let Some(fid) = aux.get("frameId").and_then(JValue::as_str)
-					{
-						self.ctx_main_frame.try_attach_context(fid, e.context.id);
-						self.ctx_menu_frame.try_attach_context(fid, e.context.id);
-					}
-				}
-			};
-			runtime::ExecutionContextDestroyed => |e: runtime::ExecutionContextDestroyed|
-			{
-				if Some(e.execution_context_id) == self.ctx_main_frame.contextid() { self.ctx_main_frame.detach_context(); }
-				if Some(e.execution_context_id) == self.ctx_menu_frame.contextid() { self.ctx_menu_frame.detach_context(); }
-			};
-			runtime::ExecutionContextsCleared => |_|
-			{
-				self.ctx_main_frame.detach_context();
-				self.ctx_menu_frame.detach_context();
-			};
-			page::FrameStoppedLoadingOwned => |e: page::FrameStoppedLoadingOwned|
+			if let SessionEvent::FrameStoppedLoading(e) = self.remote.next_event()?
 			{
-				main_completion = main_completion || self.ctx_main_frame.frameid() == Some(&e.frame_id);
-				menu_completion = menu_completion || self.ctx_menu_frame.frameid() == Some(&e.frame_id);
-				main_completion && menu_completion
+				let frames = self.remote.session.frames();
+				main_completion = main_completion || frames.find(FrameQuery::Name(Self::MAIN_FRAME_NAME)).map(|f| &f.id) == Some(&e.frame_id);
+				menu_completion = menu_completion || frames.find(FrameQuery::Name(Self::MENU_FRAME_NAME)).map(|f| &f.id) == Some(&e.frame_id);
 			}
-		});
+		}
+		// each frame gets its own isolated world(created here so that the queries do not wait for it)
+		let main = self.main_frame_context(); self.remote.script_context(Some(main))?;
+		if wait_for_menu_context { let menu = self.menu_frame_context(); self.remote.script_context(Some(menu))?; }
 		Ok(self)
 	}
-	fn main_frame_context(&self) -> u64
+	/// メインフレームのDOMスナップショット
+	pub fn main_frame_snapshot(&mut self) -> GenericResult<DomTree>
+	{
+		self.remote.snapshot(Some(FrameQuery::Name(Self::MAIN_FRAME_NAME)))
+	}
+	fn main_frame_context(&self) -> ExecutionContextID
 	{
-		self.ctx_main_frame.contextid().expect("ExecutionContext for MainFrame has not been created yet")
+		self.remote.frame_context(FrameQuery::Name(Self::MAIN_FRAME_NAME)).expect("ExecutionContext for MainFrame has not been created yet")
 	}
-	fn menu_frame_context(&self) -> u64
+	fn menu_frame_context(&self) -> ExecutionContextID
 	{
-		self.ctx_menu_frame.contextid().expect("ExecutionContext for MenuFrame has not been created yet")
+		self.remote.frame_context(FrameQuery::Name(Self::MENU_FRAME_NAME)).expect("ExecutionContext for MenuFrame has not been created yet")
 	}
 }
 pub type CampusPlanEntryFrames      = CampusPlanFrames<CampusPlanEntryPage,      EmptyMenu>;
@@ -821,13 +1015,18 @@ impl CampusPlanCourseDetailsFrames
 	pub fn parse_profile(&mut self) -> GenericResult<StudentProfile>
 	{
 		let rctx = Some(self.main_frame_context());
+		Self::extract_profile(&mut self.remote, rctx)
+	}
+	/// `parse_profile`の本体(`offline::StaticPage`でも実行できる)
+	pub fn extract_profile<E: jsq::Executor>(executor: &mut E, context: Option<u64>) -> GenericResult<StudentProfile>
+	{
 		let q = jsq::Document.query_selector_all("#TableProfile tr:nth-child(2n) td:nth-child(2n)".into())
-			.map_auto("x", jsq::CustomExpression::<jsq::types::String>("x.textContent.trim()".into(), PhantomData))
-			.map_value_auto("data", jsqGenObject!{
-				id: "data[0]", name: "data[1]", course: "data[2]", grade: "data[3]", semester: "data[4]", address: "data.slice(5, data.length)"
+			.map_auto("x", jsq::var::<jsq::types::Element>("x").text_content().trim())
+			.map_value_auto("cells", jsqGenObject!{
+				id: cell(0), name: cell(1), course: cell(2), grade: cell(3), semester: cell(4),
+				address: jsq::var::<jsq::types::Array<jsq::types::String>>("cells").slice(5, None)
 			}).stringify();
-		let q: String = self.remote.query_value(rctx, &q.to_string())?.assume();
-		Ok(serde_json::from_str(&q).expect("Protocol Corruption"))
+		executor.execute(context, q)
 	}
 	/// 履修テーブルの取得
 	/// ## †履修テーブルの仕組み†
@@ -842,52 +1041,46 @@ impl CampusPlanCourseDetailsFrames
 	pub fn parse_course_table(&mut self) -> GenericResult<CourseTable>
 	{
 		let rctx = Some(self.main_frame_context());
-		let take_link_str = jsqCustomExpr!([jsq::types::Element] "k").query_selector("a".into())
-			.map_value_auto("title_link", jsqCustomExpr!([jsq::types::String] "(!title_link) ? null : title_link.textContent.trim()"))
-			.into_closure("k");
-		let q = jsq::Document.query_selector_all("table.rishu-tbl-cell".into())
-			.map_value_auto("tables", jsqCustomExpr!([jsq::types::Array<jsq::types::Element>] "[tables[3], tables[5]]"))
-			.map_auto("koma", jsqCustomExpr!([jsq::types::Element] "koma").query_selector_all("td.rishu-tbl-cell".into()).map(take_link_str));
-		self.remote.query_value(rctx, &format!(r#"
-			let komas = {};
-			var first_quarter = [], last_quarter = [];
-			for(var i = 0; i < komas[0].length; i += 6)
-			{{
-				first_quarter.push({{
-					monday:   komas[0][i + 0], tuesday: komas[0][i + 1], wednesday: komas[0][i + 2],
-					thursday: komas[0][i + 3], friday:  komas[0][i + 4], saturday:  komas[0][i + 5]
-				}});
-				last_quarter.push({{
-					monday:   komas[1][i + 0], tuesday: komas[1][i + 1], wednesday: komas[1][i + 2],
-					thursday: komas[1][i + 3], friday:  komas[1][i + 4], saturday:  komas[1][i + 5]
-				}});
-			}}
-			JSON.stringify({{ firstQuarter: first_quarter, lastQuarter: last_quarter }})
-		"#, q)).and_then(|s| serde_json::from_str(&s.assume_string()).map_err(From::from))
+		Self::extract_course_table(&mut self.remote, rctx)
+	}
+	/// `parse_course_table`の本体(`offline::StaticPage`でも実行できる)
+	pub fn extract_course_table<E: jsq::Executor>(executor: &mut E, context: Option<u64>) -> GenericResult<CourseTable>
+	{
+		let mut script = jsq::Statements::new();
+		// 空きコマはnull
+		let link_title = script.define("linkTitle", "k",
+			|k: jsq::Var<jsq::types::Element>| k.query_selector("a".into()).text_content().trim());
+		// 1行6コマ(月〜土)
+		let weekly_course = script.define("weeklyCourse", "w", |w: jsq::Var<jsq::types::Array<jsq::types::Optional<jsq::types::String>>>| jsqGenObject!{
+			monday: w.index(0), tuesday: w.index(1), wednesday: w.index(2), thursday: w.index(3), friday: w.index(4), saturday: w.index(5)
+		});
+		let tables = script.bind("tables", jsq::Document.query_selector_all("table.rishu-tbl-cell".into()));
+		let quarter = |n: usize| tables.index(n).query_selector_all("td.rishu-tbl-cell".into()).map(link_title).chunks(6).map(weekly_course);
+		executor.execute(context, script.finish(jsqGenObject!{ firstQuarter: quarter(3), lastQuarter: quarter(5) }.stringify()))
 	}
 	/// 卒業要件集計欄のデータを取得
 	pub fn parse_graduation_requirements_table(&mut self) -> GenericResult<GraduationRequirements>
 	{
 		let rctx = Some(self.main_frame_context());
-		let query_text_content = jsqCustomExpr!([jsq::types::String] "x.textContent.trim()").into_closure("x");
-		self.remote.query_value(rctx, &jsq::Document.query_selector_all("#dgrdSotsugyoYoken tr.text-main td:not(:first-child)".into()).map(query_text_content)
+		Self::extract_graduation_requirements_table(&mut self.remote, rctx)
+	}
+	/// `parse_graduation_requirements_table`の本体(`offline::StaticPage`でも実行できる)
+	pub fn extract_graduation_requirements_table<E: jsq::Executor>(executor: &mut E, context: Option<u64>) -> GenericResult<GraduationRequirements>
+	{
+		// base番目のセルから始まる区分ごとの単位数
+		let units = |base: usize, total: Box<jsq::Fragment>| jsqGenObject!{
+			intercom: cell(base).parse_int(), selfdev: cell(base + 1).parse_int(), general: cell(base + 2).parse_int(),
+			basic: cell(base + 3).parse_int(), practice: cell(base + 4).parse_int(), research: cell(base + 5).parse_int(),
+			totalRequired: cell(base + 6).parse_int(), totalSelected: cell(base + 7).parse_int(), total: total
+		};
+		executor.execute(context, jsq::Document.query_selector_all("#dgrdSotsugyoYoken tr.text-main td:not(:first-child)".into())
+			.map_auto("x", jsq::var::<jsq::types::Element>("x").text_content().trim())
 			.map_value_auto("cells", jsqGenObject!{
-				requirements: &jsqGenObject!{
-					intercom: "parseInt(cells[0])", selfdev:  "parseInt(cells[1])", general:  "parseInt(cells[2])",
-					basic:    "parseInt(cells[3])", practice: "parseInt(cells[4])", research: "parseInt(cells[5])",
-					totalRequired: "parseInt(cells[6])", totalSelected: "parseInt(cells[7])", total: "0"
-				}.to_string(),
-				mastered: &jsqGenObject!{
-					intercom: "parseInt(cells[9 + 0])", selfdev:  "parseInt(cells[9 + 1])", general:  "parseInt(cells[9 + 2])",
-					basic:    "parseInt(cells[9 + 3])", practice: "parseInt(cells[9 + 4])", research: "parseInt(cells[9 + 5])",
-					totalRequired: "parseInt(cells[9 + 6])", totalSelected: "parseInt(cells[9 + 7])", total: "parseInt(cells[9 + 8])"
-				}.to_string(),
-				current: &jsqGenObject!{
-					intercom: "parseInt(cells[18 + 0])", selfdev:  "parseInt(cells[18 + 1])", general:  "parseInt(cells[18 + 2])",
-					basic:    "parseInt(cells[18 + 3])", practice: "parseInt(cells[18 + 4])", research: "parseInt(cells[18 + 5])",
-					totalRequired: "parseInt(cells[18 + 6])", totalSelected: "parseInt(cells[18 + 7])", total: "parseInt(cells[18 + 8])"
-				}.to_string()
-			}).stringify().to_string()).and_then(|x| serde_json::from_str(&x.assume_string()).map_err(From::from))
+				// 要件の行には合計欄がない
+				requirements: units(0, Box::new(jsq::Literal(0))),
+				mastered: units(9, Box::new(cell(9 + 8).parse_int())),
+				current: units(18, Box::new(cell(18 + 8).parse_int()))
+			}).stringify())
 	}
 }
 /// 学生プロファイル
@@ -942,71 +1135,52 @@ pub struct CategorizedUnits
 
 /// 出欠状況参照ページ
 pub enum AttendanceDetailsPage { }
+/// テーブルはポストバック後の読み込み完了(`access_details`の`wait_frame_context`)時点で描画済み。
+/// ポストバックの遷移で実行コンテキストが破棄されるので、1回の評価(`jsq::wait_for`など)で遷移をまたいで待つことはできない
 impl CampusPlanAttendanceDetailsFrames
 {
 	const TABLE_ID: &'static str = "dg";
 	const BY_PERIOD_TABLE_ID: &'static str = "dgKikanbetsu";
-	const COMMONCODE: &'static str = r#"function toPeriod(s) {
-		switch(s) {
-		case "1 Q": return "FirstQuarter"; case "2 Q": return "SecondQuarter";
-		case "3 Q": return "ThirdQuarter";  case "4 Q": return "FourthQuarter";
-		case "前期": return "FirstStage"; case "後期": return "LateStage";
-		case "通年": return "WholeYear"; default: console.assert(false);
-		}
-	}"#;
 	
 	/// 今年度の出欠状況テーブルを取得
 	pub fn parse_current_year_table(&mut self) -> GenericResult<Vec<SubjectAttendanceState>>
 	{
 		let rctx = Some(self.main_frame_context());
+		Self::extract_current_year_table(&mut self.remote, rctx)
+	}
+	/// `parse_current_year_table`の本体(`offline::StaticPage`でも実行できる)
+	pub fn extract_current_year_table<E: jsq::Executor>(executor: &mut E, context: Option<u64>) -> GenericResult<Vec<SubjectAttendanceState>>
+	{
 		let cells = jsq::Document.query_selector_all(format!("#{} tr:not(:first-child) td", Self::TABLE_ID))
-			.map_auto("x", jsqCustomExpr!([jsq::types::String] "x.textContent.trim()"));
+			.map_auto("x", jsq::var::<jsq::types::Element>("x").text_content().trim());
+		// 1行21セル(うち出欠状態が15日分)
+		let row = |n: usize| jsq::var::<jsq::types::Array<jsq::types::String>>("row").index(n);
 		let objgen = jsqGenObject!{
-			code: "cells[i + 0]", name: "cells[i + 1]", period: "toPeriod(cells[i + 2])", week: "toWeekName(cells[i + 3])",
+			code: row(0), name: row(1), period: helper_library::to_period(row(2)), week: helper_library::to_week_name(row(3)),
 			// 半角にしてからparseInt
-			time: "parseInt(cells[i + 4].replace(/[０-９]/g, x => String.fromCharCode(x.charCodeAt(0) - 65248)))",
-			rate: "parseFloat(cells[i + 5])", states: r#"cells.slice(i + 6, i + 6 + 15).map(x =>
-			{
-				if(!x) return [0, 0, "NoData"];
-				var date = x.match(/(\d+)\/(\d+)/);
-				if(x.includes("公認欠席"))  return [parseInt(date[1]), parseInt(date[2]), "Authorized"];
-				else if(x.includes("欠席")) return [parseInt(date[1]), parseInt(date[2]), "Absence"];
-				else if(x.includes("出席")) return [parseInt(date[1]), parseInt(date[2]), "Presence"];
-				else return [parseInt(date[1]), parseInt(date[2]), "NoData"];
-			})"#
+			time: helper_library::to_half_width_digits(row(4)).parse_int(),
+			rate: row(5).parse_float(),
+			states: jsq::var::<jsq::types::Array<jsq::types::String>>("row").slice(6, None)
+				.map_auto("x", helper_library::to_attendance_state(jsq::var("x")))
 		};
-		self.remote.query_value(rctx, &format!(r#"
-			{}
-			function toWeekName(s)
-			{{
-				switch(s)
-				{{
-				case "月曜日": return "Monday"; case "火曜日": return "Tuesday"; case "水曜日": return "Wednesday";
-				case "木曜日": return "Thursday"; case "金曜日": return "Friday"; case "土曜日": return "Saturday";
-				default: console.assert(false);
-				}}
-			}}
-
-			let cells = {};
-			var subjects = [];
-			for(var i = 0; i < cells.length; i += 15 + 6) subjects.push({});
-			JSON.stringify(subjects)
-		"#, Self::COMMONCODE, cells, objgen)).and_then(|s| serde_json::from_str(&s.assume_string()).map_err(From::from))
+		executor.execute(context, cells.chunks(15 + 6).map_auto("row", objgen).stringify())
 	}
 	/// 期間別出席率テーブルの取得
 	pub fn parse_attendance_rates(&mut self) -> GenericResult<Vec<PeriodAttendanceRate>>
 	{
 		let rctx = Some(self.main_frame_context());
+		Self::extract_attendance_rates(&mut self.remote, rctx)
+	}
+	/// `parse_attendance_rates`の本体(`offline::StaticPage`でも実行できる)
+	pub fn extract_attendance_rates<E: jsq::Executor>(executor: &mut E, context: Option<u64>) -> GenericResult<Vec<PeriodAttendanceRate>>
+	{
 		let q_cells = jsq::Document.query_selector_all(format!("#{} tr:not(:first-child) td", Self::BY_PERIOD_TABLE_ID))
-			.map_auto("x", jsqCustomExpr!([jsq::types::String] "x.textContent.trim()"));
-		let q_objcon = jsqGenObject!{ firstYear: "parseInt(row[0])", startingPeriod: "toPeriod(row[1])", rates: "parseFloat(row[2])" }
-			.into_closure("row");
-		let q: String = self.remote.query_value(rctx, &format!(r#"{}
-			let cells2 = {}; var ret = [];
-			for(var i = 0; i < cells2.length; i += 3) ret.push(({})(cells2.slice(i, i + 3)));
-			JSON.stringify(ret)
-		"#, Self::COMMONCODE, q_cells, q_objcon))?.assume();
-		Ok(serde_json::from_str(&q).expect("Protocol Corruption"))
+			.map_auto("x", jsq::var::<jsq::types::Element>("x").text_content().trim());
+		let row = |n: usize| jsq::var::<jsq::types::Array<jsq::types::String>>("row").index(n);
+		let q_objcon = jsqGenObject!{
+			firstYear: row(0).parse_int(), startingPeriod: helper_library::to_period(row(1)), rates: row(2).parse_float()
+		}.into_closure("row");
+		executor.execute(context, q_cells.chunks(3).map(q_objcon).stringify())
 	}
 }
 /// 出欠テーブル: 科目行
@@ -1050,3 +1224,109 @@ pub enum AttendanceState
 	#[doc = "データなし"] NoData,
 	#[doc = "出席"] Presence, #[doc = "欠席"] Absence, #[doc = "公認欠席"] Authorized
 }
+
+#[cfg(test)]
+mod tests
+{
+	use super::*;
+	use offline::StaticPage;
+	use js_syntax::{self, Validated};
+
+	fn date(y: i32, m: u32, d: u32, h: u32, min: u32) -> DateTime<Utc> { Utc.ymd(y, m, d).and_hms(h, min, 0) }
+
+	#[test]
+	fn home_page()
+	{
+		let mut page = Validated(StaticPage::parse("https://dcs.cc.dendai.ac.jp/", include_str!("../tests/fixtures/home.html")));
+		let n: Vec<Notification> = HomePage::extract_rows(&mut page, 1).unwrap();
+		assert_eq!(n.len(), 2);
+		assert_eq!(n[0], Notification
+		{
+			category: "大学から".to_owned(), date: date(2018, 4, 2, 0, 0), priority: "重要".to_owned(),
+			title: "健康診断のお知らせ & 日程".to_owned(), from: "学生課".to_owned(),
+			state: NotificationState::Unread, on_click_script: r#"openNotification("1024")"#.to_owned()
+		});
+		assert_eq!(n[1].state, NotificationState::Read);
+		let c: Vec<ClassNotification> = HomePage::extract_rows(&mut page, 2).unwrap();
+		assert_eq!(c[0].lecture_title, "情報工学概論");
+		let f: Vec<FeedbackSheetNotification> = HomePage::extract_rows(&mut page, 3).unwrap();
+		assert_eq!((f[0].time, f[0].deadline, f[0].state.clone()), (3, date(2018, 4, 16, 23, 59), NotificationState::Unanswered));
+		let h: Vec<HomeworkNotification> = HomePage::extract_rows(&mut page, 4).unwrap();
+		assert_eq!(h.iter().map(|h| h.deadline).collect::<Vec<_>>(), vec![date(2018, 4, 19, 0, 0), date(2018, 4, 10, 10, 0)]);
+		assert_eq!(h[1].state, NotificationState::Submitted);
+	}
+	#[test]
+	fn notification_list_pages()
+	{
+		let mut page = Validated(StaticPage::parse("https://dcs.cc.dendai.ac.jp/", include_str!("../tests/fixtures/all_notifications.html")));
+		let n = AllNotificationsPage::extract_notifications(&mut page).unwrap();
+		assert_eq!(n.len(), 3);
+		assert_eq!((&n[0].title as &str, n[0].state.clone()), ("健康診断のお知らせ & 日程", NotificationState::Unread));
+		assert_eq!((n[2].date, &n[2].from as &str), (date(2018, 3, 20, 0, 0), "情報工学科"));
+		let mut page = Validated(StaticPage::parse("https://dcs.cc.dendai.ac.jp/", include_str!("../tests/fixtures/lecture_notifications.html")));
+		let l = AllLectureNotificationsPage::extract_notifications(&mut page).unwrap();
+		assert_eq!(l, vec![
+			LectureNotification
+			{
+				date: date(2018, 4, 11, 0, 0), priority: "重要".to_owned(), lecture_title: "プログラミング演習".to_owned(),
+				title: "演習室の変更について".to_owned(), state: NotificationState::Unread,
+				on_click_script: r#"openLectureNotification("301")"#.to_owned()
+			},
+			LectureNotification
+			{
+				date: date(2018, 4, 6, 0, 0), priority: "通常".to_owned(), lecture_title: "線形代数".to_owned(),
+				title: "教科書について".to_owned(), state: NotificationState::Read,
+				on_click_script: r#"openLectureNotification("296")"#.to_owned()
+			}
+		]);
+	}
+	#[test]
+	fn scripts()
+	{
+		// the scripts not executed through `Executor`
+		let scripts = [
+			LoginPage::login_id_script("17fi000\"</script>"), HomePage::notification_observer_script(),
+			HomePage::RELEASE_NOTIFICATION_OBSERVER.to_owned(),
+			jsq::Document.query_selector_all("a".into()).index(2).click().to_string(),
+			jsq::Document.query_selector("#mainContents .toAll a".into()).click().to_string()
+		];
+		for s in &scripts { if let Err(e) = js_syntax::validate(s) { panic!("{}\n{}", s, e); } }
+	}
+	#[test]
+	fn course_details_page()
+	{
+		let mut page = Validated(StaticPage::parse("https://portal.sa.dendai.ac.jp/", include_str!("../tests/fixtures/course_details.html")));
+		let p = CampusPlanCourseDetailsFrames::extract_profile(&mut page, None).unwrap();
+		assert_eq!((&p.id as &str, &p.name as &str, &p.grade as &str), ("1701234", "電大 太郎", "2年"));
+		assert_eq!(p.address, vec!["〒101-0000", "東京都千代田区", "神田1-2-3"]);
+		let t = CampusPlanCourseDetailsFrames::extract_course_table(&mut page, None).unwrap();
+		assert_eq!(t.first_quarter.len(), 2);
+		assert_eq!(t.first_quarter[0].monday, Some("情報工学概論".to_owned()));
+		assert_eq!(t.first_quarter[0].wednesday, Some("線形代数".to_owned()));
+		assert_eq!(t.first_quarter[1].monday, None);
+		assert_eq!(t.last_quarter[0].saturday, Some("体育".to_owned()));
+		let g = CampusPlanCourseDetailsFrames::extract_graduation_requirements_table(&mut page, None).unwrap();
+		assert_eq!((g.requirements.basic, g.requirements.total_selected, g.requirements.total), (20, 84, 0));
+		assert_eq!((g.mastered.intercom, g.mastered.total), (4, 34));
+		assert_eq!((g.current.practice, g.current.total), (6, 15));
+	}
+	#[test]
+	fn attendance_details_page()
+	{
+		let mut page = Validated(StaticPage::parse("https://portal.sa.dendai.ac.jp/", include_str!("../tests/fixtures/attendance_details.html")));
+		let s = CampusPlanAttendanceDetailsFrames::extract_current_year_table(&mut page, None).unwrap();
+		assert_eq!(s.len(), 2);
+		assert_eq!((s[0].period, s[0].week, s[0].time, s[0].rate), (Period::FirstQuarter, Week::Monday, 1, 95.5));
+		assert_eq!(&s[0].states[..4], &[
+			(4, 9, AttendanceState::Presence), (4, 16, AttendanceState::Presence),
+			(4, 23, AttendanceState::Absence), (5, 7, AttendanceState::Authorized)
+		]);
+		assert_eq!(s[0].states[14], (0, 0, AttendanceState::NoData));
+		assert_eq!((s[1].period, s[1].week, s[1].time, s[1].rate), (Period::FirstStage, Week::Wednesday, 2, 100.0));
+		let r = CampusPlanAttendanceDetailsFrames::extract_attendance_rates(&mut page, None).unwrap();
+		assert_eq!(r, vec![
+			PeriodAttendanceRate { first_year: 2017, starting_period: Period::FirstStage, rates: 98.2 },
+			PeriodAttendanceRate { first_year: 2017, starting_period: Period::LateStage, rates: 91.0 }
+		]);
+	}
+}
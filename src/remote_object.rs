@@ -0,0 +1,140 @@
+//! Handles to remote JavaScript objects
+
+use headless_chrome::{Session, RequestID};
+use headless_chrome::runtime::{RemoteObject, RemoteObjectID, CallArgument, EvaluateResult, ExceptionDetails};
+use serde_json::Value as JValue;
+use std::rc::Rc;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use GenericResult;
+
+/// Objects waiting for `Runtime.releaseObject`, and which handles still refer to living objects
+#[derive(Default)]
+pub struct ReleaseState
+{
+	pending: Vec<(RemoteObjectID, Option<String>)>,
+	/// bumped when every object has gone(contexts cleared, reconnection)
+	epoch: u64,
+	/// bumped when the group is released at once
+	group_epochs: HashMap<String, u64>
+}
+impl ReleaseState
+{
+	pub fn is_empty(&self) -> bool { self.pending.is_empty() }
+	/// Takes out the objects to be released
+	pub fn take_pending(&mut self) -> Vec<RemoteObjectID> { self.pending.drain(..).map(|(oid, _)| oid).collect() }
+	/// Every object has gone: queued objects are discarded and the living handles are not queued anymore
+	pub fn invalidate_all(&mut self) { self.epoch += 1; self.pending.clear(); }
+	/// The group has been released by `Runtime.releaseObjectGroup`
+	pub fn invalidate_group(&mut self, group: &str)
+	{
+		*self.group_epochs.entry(group.to_owned()).or_insert(0) += 1;
+		self.pending.retain(|&(_, ref g)| g.as_ref().map(|s| s as &str) != Some(group));
+	}
+	fn generation(&self, group: Option<&str>) -> (u64, u64)
+	{
+		(self.epoch, group.and_then(|g| self.group_epochs.get(g).cloned()).unwrap_or(0))
+	}
+}
+/// Shared with the handles. Flushed by the session before its next request
+pub type ReleaseQueue = Rc<RefCell<ReleaseState>>;
+
+/// Owned reference to a remote object. Released when dropped, unless taken out with `into_object`.
+/// Primitive values have no object id and are simply carried
+pub struct RemoteObjectHandle { object: Option<RemoteObject>, group: Option<String>, release_queue: ReleaseQueue, generation: (u64, u64) }
+impl RemoteObjectHandle
+{
+	pub fn new(object: RemoteObject, group: Option<String>, release_queue: ReleaseQueue) -> Self
+	{
+		let generation = release_queue.borrow().generation(group.as_ref().map(|s| s as &str));
+		RemoteObjectHandle { object: Some(object), group, release_queue, generation }
+	}
+	pub fn object(&self) -> &RemoteObject { self.object.as_ref().unwrap() }
+	pub fn object_id(&self) -> Option<&str> { self.object().object_id.as_ref().map(|s| s as &str) }
+	/// The object group(objects created from this handle join the same group)
+	pub fn group(&self) -> Option<&str> { self.group.as_ref().map(|s| s as &str) }
+	/// Value of a primitive(or a serialized) object
+	pub fn value(&self) -> Option<&JValue> { self.object().value.as_ref() }
+	/// Takes the object out without releasing it(e.g. released later with its group)
+	pub fn into_object(mut self) -> RemoteObject { self.object.take().unwrap() }
+
+	/// Own properties of the object. Property values are wrapped into handles of the same group
+	pub fn properties(&self, session: &mut Session, id: RequestID) -> GenericResult<Vec<(String, RemoteObjectHandle)>>
+	{
+		let oid = self.require_id()?;
+		let r = session.runtime().get_own_properties_sync(id, oid)?;
+		if let Some(e) = r.exception_details { return Err(exception_error("getting properties", &e)); }
+		Ok(r.result.into_iter().filter_map(|p| match p.value
+		{
+			Some(v) => Some((p.name, RemoteObjectHandle::new(v, self.group.clone(), self.release_queue.clone()))),
+			// accessor properties are not invoked
+			None => None
+		}).collect())
+	}
+	/// A property of the object(`None` if missing or an accessor)
+	pub fn property(&self, session: &mut Session, id: RequestID, name: &str) -> GenericResult<Option<RemoteObjectHandle>>
+	{
+		self.properties(session, id).map(|v| v.into_iter().find(|&(ref n, _)| n == name).map(|(_, h)| h))
+	}
+	/// Calls `function_declaration` with the object as `this`. The result stays remote
+	pub fn call_function(&self, session: &mut Session, id: RequestID, function_declaration: &str, arguments: &[CallArgument])
+		-> GenericResult<RemoteObjectHandle>
+	{
+		let r = self.call(session, id, function_declaration, arguments, false)?;
+		Ok(RemoteObjectHandle::new(r, self.group.clone(), self.release_queue.clone()))
+	}
+	/// Calls `function_declaration` with the object as `this` and returns the serialized result
+	pub fn call_function_value(&self, session: &mut Session, id: RequestID, function_declaration: &str, arguments: &[CallArgument])
+		-> GenericResult<RemoteObject>
+	{
+		self.call(session, id, function_declaration, arguments, true)
+	}
+	fn call(&self, session: &mut Session, id: RequestID, function_declaration: &str, arguments: &[CallArgument], return_by_value: bool)
+		-> GenericResult<RemoteObject>
+	{
+		let oid = self.require_id()?;
+		let EvaluateResult { result, exception_details } = session.runtime().call_function_on_sync(id, oid, function_declaration,
+			arguments, return_by_value, self.group.as_ref().map(|s| s as &str))?;
+		match exception_details
+		{
+			Some(e) => Err(exception_error("calling function", &e)), None => Ok(result)
+		}
+	}
+	/// This object as an argument of `call_function`
+	pub fn as_argument(&self) -> CallArgument
+	{
+		match self.object_id()
+		{
+			Some(oid) => CallArgument::object(oid),
+			None => CallArgument { value: self.object().value.clone(), unserializable_value: self.object().unserializable_value.clone(), object_id: None }
+		}
+	}
+	/// DOM node id of the object(the document must have been requested in the session)
+	pub fn node_id(&self, session: &mut Session, id: RequestID) -> GenericResult<isize>
+	{
+		let oid = self.require_id()?;
+		session.dom().request_node_sync(id, oid)
+	}
+
+	fn require_id(&self) -> GenericResult<&str>
+	{
+		self.object_id().ok_or_else(|| format!("Not a remote object: {:?}", self.object()).into())
+	}
+}
+impl Drop for RemoteObjectHandle
+{
+	fn drop(&mut self)
+	{
+		if let Some(oid) = self.object.take().and_then(|o| o.object_id)
+		{
+			let mut q = self.release_queue.borrow_mut();
+			// objects released with their group or gone with their context are not released twice
+			if q.generation(self.group.as_ref().map(|s| s as &str)) == self.generation { q.pending.push((oid, self.group.take())); }
+		}
+	}
+}
+
+fn exception_error(action: &str, e: &ExceptionDetails) -> Box<::std::error::Error>
+{
+	format!("Exception in {}: {} {:?}", action, e.text, e.exception.as_ref().and_then(|x| x.description.as_ref())).into()
+}
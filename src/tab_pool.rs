@@ -0,0 +1,55 @@
+//! Pool of warm DigitalCampus tabs sharing one browser connection
+
+use browser::BrowserConnection;
+use remote_campus::RemoteCampus;
+use GenericResult;
+
+/// Hands out ready-made `RemoteCampus` instances and recycles them.
+/// Tabs share cookies, so a tab opened after a login starts logged in
+pub struct TabPool
+{
+	browser: BrowserConnection, initial_url: String, ua_override: Option<String>,
+	idle: Vec<RemoteCampus>, max_idle: usize
+}
+impl TabPool
+{
+	const DEFAULT_MAX_IDLE: usize = 4;
+
+	pub fn new(browser: BrowserConnection, initial_url: &str, ua_override: Option<&str>) -> Self
+	{
+		TabPool
+		{
+			browser, initial_url: initial_url.to_owned(), ua_override: ua_override.map(ToOwned::to_owned),
+			idle: Vec::new(), max_idle: Self::DEFAULT_MAX_IDLE
+		}
+	}
+	/// Maximum number of tabs kept for reuse. Extra released tabs are closed
+	pub fn set_max_idle(&mut self, max_idle: usize) -> &mut Self
+	{
+		self.max_idle = max_idle;
+		while self.idle.len() > max_idle { self.idle.pop(); }
+		self
+	}
+	pub fn idle_count(&self) -> usize { self.idle.len() }
+	pub fn browser(&self) -> &BrowserConnection { &self.browser }
+
+	/// Takes a tab out of the pool. A new tab showing the initial url is opened if no idle one is left
+	pub fn acquire(&mut self) -> GenericResult<RemoteCampus>
+	{
+		if let Some(r) = self.idle.pop() { return Ok(r); }
+		// attach before navigating so that the load event is not missed
+		let session = self.browser.open_page("about:blank")?;
+		let mut r = RemoteCampus::with_session(session, self.ua_override.as_ref().map(|s| s as &str))?;
+		r.navigate(&self.initial_url)?.wait_loading()?;
+		Ok(r)
+	}
+	/// Returns a tab to the pool(page controllers give it back with `transfer_control`).
+	/// The tab is brought back to the initial url with the default settings, or closed if that fails
+	pub fn release(&mut self, mut tab: RemoteCampus)
+	{
+		if self.idle.len() >= self.max_idle { return; }
+		let initial_url = &self.initial_url;
+		let r = tab.reset_settings().and_then(|t| t.navigate(initial_url)?.wait_loading().map(drop));
+		if r.is_ok() { self.idle.push(tab); }
+	}
+}
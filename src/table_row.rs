@@ -0,0 +1,152 @@
+//! Table row extraction: `#[derive(TableRow)]` generates both the cell mapping and the decoding from one struct
+
+use std::marker::PhantomData;
+use std::fmt::{Display, Result as FmtResult, Formatter};
+use std::mem::replace;
+use serde::de::DeserializeOwned;
+use serde_json;
+pub use serde_json::Value as JValue;
+use jsquery as jsq;
+use jsquery::QueryCombinator;
+use offline::{self, Environment};
+use GenericResult;
+
+/// Row of a table. Implemented by `#[derive(TableRow)]`
+pub trait TableRow: Sized
+{
+	/// Object constructing the row from the cell texts(`cells`) and the row element(`r`)
+	fn jsqf_row_object() -> jsq::ObjectConstructor;
+	/// Decodes the constructed object
+	fn decode_row(value: JValue) -> GenericResult<Self>;
+}
+/// Value type of a row object
+pub struct Row<T>(PhantomData<T>);
+impl<T: TableRow> jsq::types::Decode<T> for Row<T> { fn decode(value: JValue) -> GenericResult<T> { T::decode_row(value) } }
+impl<T> jsq::types::Value for Row<T> { type Base = Row<T>; const NULLABLE: bool = false; }
+impl<T, R> jsq::types::Lift<R> for Row<T> { type Output = R; }
+/// Row object fragment
+pub struct RowObject<T: TableRow>(jsq::ObjectConstructor, PhantomData<T>);
+impl<T: TableRow> QueryCombinator for RowObject<T> { type ValueTy = Row<T>; }
+impl<T: TableRow> Display for RowObject<T> { fn fmt(&self, fmt: &mut Formatter) -> FmtResult { self.0.fmt(fmt) } }
+impl<T: TableRow> jsq::Evaluate for RowObject<T>
+{
+	fn evaluate(&self, env: &mut Environment) -> GenericResult<offline::Value> { self.0.evaluate(env) }
+}
+
+/// Texts of the cells in the row element `r`
+pub type RowCells = jsq::Mapping<jsq::QuerySelectorAll<jsq::Var<'static, jsq::types::Element>>,
+	jsq::Closure<'static, jsq::Chain<jsq::Chain<jsq::Var<'static, jsq::types::Element>, jsq::TextContent>, jsq::Trim>>>;
+pub fn row_cells() -> RowCells
+{
+	jsq::var("r").query_selector_all("td".into()).map_auto("x", jsq::var::<jsq::types::Element>("x").text_content().trim())
+}
+/// Closure extracting a `T` from a row element
+pub type Extractor<T> = jsq::Closure<'static, jsq::ValueMapping<RowCells, jsq::Closure<'static, RowObject<T>>>>;
+pub fn extractor<T: TableRow>() -> Extractor<T>
+{
+	row_cells().map_value_auto("cells", RowObject(T::jsqf_row_object(), PhantomData)).into_closure("r")
+}
+
+/// Takes a field out of the row object(for `decode_row`)
+pub fn decode_field<T: DeserializeOwned>(value: &mut JValue, name: &str) -> GenericResult<T>
+{
+	let v = value.get_mut(name).map(|v| replace(v, JValue::Null)).unwrap_or(JValue::Null);
+	serde_json::from_value(v).map_err(|e| format!("Invalid value for the field {}: {}", name, e).into())
+}
+
+/// Converters for `#[column(n, converter)]`
+pub mod convert
+{
+	use jsquery as jsq;
+	use jsquery::QueryCombinator;
+	use helper_library::{self, HelperCall};
+
+	/// n-th cell(`cells[n]`)
+	pub type Cell = jsq::Chain<jsq::Var<'static, jsq::types::Array<jsq::types::String>>, jsq::Index>;
+	/// Text of the cell(default)
+	pub fn text(n: usize) -> Cell { jsq::var("cells").index(n) }
+	/// "yyyy/mm/dd" to `DateTime<Utc>`(at 00:00)
+	pub fn date(n: usize) -> jsq::Chain<Cell, jsq::Replace> { text(n).replace(r"^(\d+)/(\d+)/(\d+)$", "$1-$2-$3T00:00:00Z") }
+	/// "yyyy/mm/dd hh:mm"(or a date only) to `DateTime<Utc>`
+	pub fn datetime(n: usize) -> jsq::Chain<jsq::Chain<Cell, jsq::Replace>, jsq::Replace>
+	{
+		text(n).replace(r"^(\d+)/(\d+)/(\d+)\s*(\d+:\d+)$", "$1-$2-$3T$4:00Z").replace(r"^(\d+)/(\d+)/(\d+)$", "$1-$2-$3T00:00:00Z")
+	}
+	/// Integer
+	pub fn int(n: usize) -> jsq::Chain<Cell, jsq::ParseInt> { text(n).parse_int() }
+	/// Real number
+	pub fn float(n: usize) -> jsq::Chain<Cell, jsq::ParseFloat> { text(n).parse_float() }
+	/// Integer written in full-width digits
+	pub fn digits(n: usize) -> jsq::Chain<HelperCall<Cell, jsq::types::String>, jsq::ParseInt>
+	{
+		helper_library::to_half_width_digits(text(n)).parse_int()
+	}
+	/// "未読"など to `NotificationState`
+	pub fn state(n: usize) -> HelperCall<Cell, jsq::types::String> { helper_library::translate_notification_state(text(n)) }
+	/// "1 Q"/"前期"など to `Period`
+	pub fn period(n: usize) -> HelperCall<Cell, jsq::types::String> { helper_library::to_period(text(n)) }
+	/// "月曜日"など to `Week`
+	pub fn week(n: usize) -> HelperCall<Cell, jsq::types::String> { helper_library::to_week_name(text(n)) }
+	/// Script of the row's onclick handler(without "javascript:")
+	pub fn onclick() -> jsq::Chain<jsq::Chain<jsq::Var<'static, jsq::types::Element>, jsq::Attribute>, jsq::Replace>
+	{
+		jsq::var("r").attribute("onclick").replace("^javascript:", "")
+	}
+}
+
+/// Implementation of `#[derive(TableRow)]`
+#[macro_export] #[doc(hidden)]
+macro_rules! __dc_web_table_row
+{
+	($name: ident { $($field: ident : $conv: ident ( $($arg: expr),* )),* }) =>
+	{
+		impl $crate::table_row::TableRow for $name
+		{
+			fn jsqf_row_object() -> $crate::jsquery::ObjectConstructor
+			{
+				$crate::jsquery::ObjectConstructor(vec![$(
+					(stringify!($field), Box::new($crate::table_row::convert::$conv($($arg),*)) as Box<$crate::jsquery::Fragment>)
+				),*])
+			}
+			fn decode_row(mut value: $crate::table_row::JValue) -> Result<Self, Box<::std::error::Error>>
+			{
+				Ok($name { $($field: $crate::table_row::decode_field(&mut value, stringify!($field))?),* })
+			}
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests
+{
+	use super::*;
+
+	#[derive(TableRow, Debug, PartialEq)]
+	struct Homework
+	{
+		#[column(0, date)] date: String, #[column(1)] title: Option<String>,
+		#[column(2, digits)] time: u32, #[onclick] script: String
+	}
+
+	#[test]
+	fn row_object()
+	{
+		assert_eq!(Homework::jsqf_row_object().to_string(), concat!(
+			r#"({ date: ((cells)[0]).replace(new RegExp("^(\\d+)/(\\d+)/(\\d+)$", ""), "$1-$2-$3T00:00:00Z"),"#,
+			r#"title: (cells)[1],"#,
+			r#"time: parseInt((dcWeb.toHalfWidthDigits((cells)[2])), 10),"#,
+			r#"script: (_v => _v == null ? null : _v.replace(new RegExp("^javascript:", ""), ""))((r).getAttribute("onclick")) })"#));
+	}
+	#[test]
+	fn decode()
+	{
+		let v = serde_json::from_str(r#"{ "date": "2018-04-01T00:00:00Z", "title": null, "time": 3, "script": "open()" }"#).unwrap();
+		assert_eq!(Homework::decode_row(v).unwrap(), Homework
+		{
+			date: "2018-04-01T00:00:00Z".to_owned(), title: None, time: 3, script: "open()".to_owned()
+		});
+		let missing = serde_json::from_str(r#"{ "date": "2018-04-01T00:00:00Z", "title": "a", "script": "" }"#).unwrap();
+		let e = Homework::decode_row(missing).unwrap_err();
+		assert!(e.to_string().contains("time"));
+	}
+}